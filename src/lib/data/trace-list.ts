@@ -34,6 +34,8 @@ import { resolvedColorToHex } from "../utils/color.js";
 import { hashAny } from "../utils/hash.js";
 import type { InterpolationStrategy } from "../../../dist/wasm/libchartium.js";
 import { isUnit } from "unitlib";
+import { NumericDateRepresentation } from "../utils/numericDateRepresentation.js";
+import { Duration } from "../utils/duration.js";
 import {
   enumerate,
   filter,
@@ -154,6 +156,29 @@ export function randomUint() {
   return (Math.random() * 2 ** 32) >>> 0;
 }
 
+const MILLISECONDS_PER_TICK = { s: 1000, ms: 1, us: 1e-3, ns: 1e-6 };
+
+/**
+ * Timestamps are loaded as seconds since the bundle's epoch rather than as ticks
+ * since the Unix epoch, so that they stay exact. Describes the x values of such
+ * a bundle, given the unit the timestamps were described by.
+ */
+function rebaseOnEpoch(
+  unit: DataUnit,
+  bundle: { time_unit(): string | undefined; epoch(): bigint | undefined },
+): DataUnit {
+  const timeUnit = bundle.time_unit() as keyof typeof MILLISECONDS_PER_TICK;
+  const epoch = bundle.epoch();
+  if (!(unit instanceof NumericDateRepresentation)) return unit;
+  if (timeUnit === undefined || epoch === undefined) return unit;
+
+  const ms = Number(epoch) * MILLISECONDS_PER_TICK[timeUnit];
+  return NumericDateRepresentation.from({
+    duration: Duration.from({ seconds: 1 }),
+    relativeTo: unit.relativeTo.add(ms, "ms"),
+  });
+}
+
 export class TraceList {
   #params: Readonly<TraceListParams>;
   private constructor(params: TraceListParams) {
@@ -222,6 +247,7 @@ export class TraceList {
     if (range.type === "Everywhere") {
       throw Error("Unexpected error while parsing the trace list's range.");
     }
+    xDataUnit = rebaseOnEpoch(xDataUnit, bundle);
 
     let tl = new TraceList({
       handles,
@@ -274,16 +300,17 @@ export class TraceList {
     if (range.type === "Everywhere") {
       throw Error("Unexpected error while parsing the trace list's range.");
     }
+    const xDataUnit = rebaseOnEpoch(x.unit, bundle);
 
     let tl = new TraceList({
       handles,
-      range: toChartRange(range.value, x.unit),
+      range: toChartRange(range.value, xDataUnit),
       rangeArbitrary: false,
-      bundles: [new Bundle(bundle, x.unit, y.unit)],
+      bundles: [new Bundle(bundle, xDataUnit, y.unit)],
       labels: new Map(),
       styles: oxidizeStyleSheet(style),
       precomputedColorIndices: undefined,
-      xDataUnit: x.unit,
+      xDataUnit,
       yDataUnit: y.unit,
       randomSeed: randomUint(),
     });
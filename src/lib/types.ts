@@ -37,7 +37,7 @@ export type TypedArray =
   | Float32Array
   | Float64Array;
 
-export type TypeOfData =
+type IntegerType =
   | "i8"
   | "u8"
   | "i16"
@@ -46,8 +46,27 @@ export type TypeOfData =
   | "u32"
   | "i64"
   | "u64"
+  | "DateTime";
+
+type ScalarType =
+  | IntegerType
+  | "f16"
+  | "bf16"
   | "f32"
-  | "f64";
+  | "f64"
+  | "DateTimeMs"
+  | "DateTimeUs"
+  | "DateTimeNs";
+
+/**
+ * Little-endian scalar types, their big-endian variants with a `_be` suffix,
+ * booleans and fixed-point decimals, e.g. `scaled(i32, 0.001)`.
+ */
+export type TypeOfData =
+  | ScalarType
+  | `${ScalarType}_be`
+  | "bool"
+  | `scaled(${IntegerType | `${IntegerType}_be`}, ${number})`;
 
 export type VariantHandle = number;
 export type VariantHandleArray = Uint32Array;
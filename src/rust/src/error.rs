@@ -0,0 +1,183 @@
+use std::fmt;
+
+use wasm_bindgen::JsValue;
+
+#[cfg(feature = "arrow")]
+use crate::structs::ArrowLoadError;
+use crate::{
+    data::TraceHandle,
+    structs::{BundleFileError, CsvError},
+};
+
+/// Errors returned from the wasm API.
+///
+/// They are converted into JS `Error`s named `ChartiumError`, with a `code`
+/// property holding [`ChartError::code`] so that callers can tell them apart.
+#[derive(Debug)]
+pub enum ChartError {
+    UnknownType(String),
+    NoData,
+    LengthMismatch {
+        what: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+    EmptyList(&'static str),
+    OutOfRange {
+        what: &'static str,
+        index: usize,
+        len: usize,
+    },
+    /// A bundle was freed while it was still part of a `BundleVec`
+    DroppedBundle,
+    /// None of the given bundles contains the trace
+    UnknownTrace(TraceHandle),
+    /// Two traces being put into one bundle share the handle
+    DuplicateTrace(TraceHandle),
+    /// x values meant to be ascending aren't
+    UnsortedX,
+    /// An x value is NaN or infinite
+    NonFiniteX(f64),
+    Expression(String),
+    /// An option passed to a bundle wrapper is out of its domain
+    InvalidOption(String),
+    WebGl(&'static str),
+    Serialization(String),
+    BundleFile(BundleFileError),
+    #[cfg(feature = "arrow")]
+    Arrow(ArrowLoadError),
+    #[cfg(feature = "parquet")]
+    Parquet(parquet::errors::ParquetError),
+    Csv(CsvError),
+    /// An exception thrown by a JS callback, passed on as it is
+    Js(JsValue),
+}
+
+pub type Result<T, E = ChartError> = std::result::Result<T, E>;
+
+impl ChartError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ChartError::UnknownType(_) => "UNKNOWN_TYPE",
+            ChartError::NoData => "NO_DATA",
+            ChartError::LengthMismatch { .. } => "LENGTH_MISMATCH",
+            ChartError::EmptyList(_) => "EMPTY_LIST",
+            ChartError::OutOfRange { .. } => "OUT_OF_RANGE",
+            ChartError::DroppedBundle => "DROPPED_BUNDLE",
+            ChartError::UnknownTrace(_) => "UNKNOWN_TRACE",
+            ChartError::DuplicateTrace(_) => "DUPLICATE_TRACE",
+            ChartError::UnsortedX => "UNSORTED_X",
+            ChartError::NonFiniteX(_) => "NON_FINITE_X",
+            ChartError::Expression(_) => "EXPRESSION",
+            ChartError::InvalidOption(_) => "INVALID_OPTION",
+            ChartError::WebGl(_) => "WEBGL",
+            ChartError::Serialization(_) => "SERIALIZATION",
+            ChartError::BundleFile(_) => "BUNDLE_FILE",
+            #[cfg(feature = "arrow")]
+            ChartError::Arrow(_) => "ARROW",
+            #[cfg(feature = "parquet")]
+            ChartError::Parquet(_) => "PARQUET",
+            ChartError::Csv(_) => "CSV",
+            ChartError::Js(_) => "JS",
+        }
+    }
+}
+
+impl fmt::Display for ChartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChartError::UnknownType(name) => write!(f, "unknown data type {name}"),
+            ChartError::NoData => write!(f, "there are no data points"),
+            ChartError::LengthMismatch {
+                what,
+                expected,
+                actual,
+            } => write!(f, "expected {expected} {what}, got {actual}"),
+            ChartError::EmptyList(what) => write!(f, "the list of {what} is empty"),
+            ChartError::OutOfRange { what, index, len } => {
+                write!(f, "{what} {index} is out of range, there are only {len}")
+            }
+            ChartError::DroppedBundle => write!(f, "the bundle has already been freed"),
+            ChartError::UnknownTrace(handle) => write!(f, "there is no trace with handle {handle}"),
+            ChartError::DuplicateTrace(handle) => {
+                write!(f, "there are several traces with handle {handle}")
+            }
+            ChartError::UnsortedX => write!(f, "the x values are not in ascending order"),
+            ChartError::NonFiniteX(x) => write!(f, "x value {x} is not a finite number"),
+            ChartError::Expression(e) => write!(f, "invalid expression: {e}"),
+            ChartError::InvalidOption(e) => write!(f, "invalid option: {e}"),
+            ChartError::WebGl(what) => write!(f, "webgl error: {what}"),
+            ChartError::Serialization(e) => write!(f, "serialization failed: {e}"),
+            ChartError::BundleFile(e) => e.fmt(f),
+            #[cfg(feature = "arrow")]
+            ChartError::Arrow(e) => e.fmt(f),
+            #[cfg(feature = "parquet")]
+            ChartError::Parquet(e) => write!(f, "invalid parquet file: {e}"),
+            ChartError::Csv(e) => e.fmt(f),
+            ChartError::Js(e) => write!(f, "{e:?}"),
+        }
+    }
+}
+
+impl From<ChartError> for JsValue {
+    fn from(error: ChartError) -> Self {
+        if let ChartError::Js(value) = error {
+            return value;
+        }
+
+        let js_error = js_sys::Error::new(&error.to_string());
+        js_error.set_name("ChartiumError");
+        let _ = js_sys::Reflect::set(
+            &js_error,
+            &JsValue::from_str("code"),
+            &JsValue::from_str(error.code()),
+        );
+
+        js_error.into()
+    }
+}
+
+impl From<JsValue> for ChartError {
+    fn from(value: JsValue) -> Self {
+        ChartError::Js(value)
+    }
+}
+
+impl From<serde_wasm_bindgen::Error> for ChartError {
+    fn from(e: serde_wasm_bindgen::Error) -> Self {
+        ChartError::Serialization(e.to_string())
+    }
+}
+
+impl From<BundleFileError> for ChartError {
+    fn from(e: BundleFileError) -> Self {
+        ChartError::BundleFile(e)
+    }
+}
+
+#[cfg(feature = "arrow")]
+impl From<ArrowLoadError> for ChartError {
+    fn from(e: ArrowLoadError) -> Self {
+        ChartError::Arrow(e)
+    }
+}
+
+#[cfg(feature = "arrow")]
+impl From<arrow_schema::ArrowError> for ChartError {
+    fn from(e: arrow_schema::ArrowError) -> Self {
+        ChartError::Arrow(ArrowLoadError::Arrow(e))
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl From<parquet::errors::ParquetError> for ChartError {
+    fn from(e: parquet::errors::ParquetError) -> Self {
+        ChartError::Parquet(e)
+    }
+}
+
+impl From<CsvError> for ChartError {
+    fn from(e: CsvError) -> Self {
+        ChartError::Csv(e)
+    }
+}
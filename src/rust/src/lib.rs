@@ -3,6 +3,7 @@
 use wasm_bindgen::prelude::*;
 
 pub mod data;
+pub mod error;
 pub mod renderers;
 pub mod structs;
 pub mod trace;
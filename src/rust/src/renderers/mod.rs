@@ -27,9 +27,17 @@ pub struct TraceData {
 }
 
 impl TraceData {
-    pub fn compute(bundle: &BundleRc, handle: TraceHandle, x_range: NumericRange) -> Self {
+    /// Collects the points of a trace, decimated to roughly `density` points per unit of x
+    pub fn compute(
+        bundle: &BundleRc,
+        handle: TraceHandle,
+        x_range: NumericRange,
+        density: f64,
+    ) -> Self {
+        let buckets = (x_range.len() * density).ceil() as usize;
+
         let data = bundle
-            .iter_in_range_with_neighbors_f64(handle, x_range)
+            .iter_in_range_decimated_f64(handle, x_range, buckets)
             .with_origin_at(x_range.from, 0.0)
             .map(|(x, y)| (x as f32, y as f32))
             .collect();
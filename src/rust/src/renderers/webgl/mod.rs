@@ -11,6 +11,7 @@ use web_sys::{
 
 use crate::{
     data::{BundleHandle, TraceHandle},
+    error::ChartError,
     structs::AdaptiveGrid,
     trace::{extensions::PointIteratorExtension, BundleRange, BundleRc, BundleWeak},
     trace_styles::TraceStyle,
@@ -107,14 +108,16 @@ impl WebGlRenderer {
         context: WebGl2RenderingContext,
         programs: &WebGlPrograms,
         present_canvas: OffscreenCanvas,
-    ) -> Result<WebGlRenderer, JsValue> {
+    ) -> Result<WebGlRenderer, ChartError> {
         context.enable(WebGl2RenderingContext::BLEND);
         context.blend_func(
             WebGl2RenderingContext::SRC_ALPHA,
             WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA,
         );
 
-        let brushpoint_buffer = context.create_buffer().unwrap();
+        let brushpoint_buffer = context
+            .create_buffer()
+            .ok_or(ChartError::WebGl("could not create the brush point buffer"))?;
 
         context.bind_buffer(
             WebGl2RenderingContext::ARRAY_BUFFER,
@@ -225,7 +228,9 @@ impl WebGlRenderer {
             .get_mut(&(bundle.handle(), trace_handle))
         {
             Some(geometry) => {
-                if !geometry.is_stale(bundle, style, job, (self.width, self.height)) {
+                if !geometry.is_stale(bundle, style, job, (self.width, self.height))
+                    && geometry.is_exact(job, self.width)
+                {
                     return geometry.clone();
                 } else {
                     let mut geometry = geometry.clone();
@@ -286,7 +291,10 @@ impl WebGlRenderer {
                 stack_cache[0..in_stack_idx]
                     .iter()
                     .for_each(|(bundle, trace, _)| {
-                        let bundle = bundle.upgrade().unwrap();
+                        // freed bundles no longer contribute to the stack
+                        let Some(bundle) = bundle.upgrade() else {
+                            return;
+                        };
 
                         let x_range = match bundle.range() {
                             BundleRange::Bounded { from, to } => NumericRange::new(from, to),
@@ -28,15 +28,13 @@ impl WebGlTrace {
 
         let TraceGeometry {
             x_range,
-            line_vertex_count,
             line_buffer,
+            segments,
             arc_length_buffer,
             fill_buffer,
             ..
         } = &self.geometry;
 
-        let points = *line_vertex_count as i32;
-
         gl.uniform2f(
             Some(&programs.trace_origin),
             (job.common.x_range.from - x_range.from) as f32,
@@ -44,10 +42,7 @@ impl WebGlTrace {
         );
 
         match (fill_buffer, style.get_fill()) {
-            (
-                Some((vertex_count, buffer)),
-                TraceFillStyle::ToZeroY | TraceFillStyle::ToNextInStack,
-            ) => {
+            (Some((_, buffer)), TraceFillStyle::ToZeroY | TraceFillStyle::ToNextInStack) => {
                 let opacity = style.get_fill_opacity();
 
                 gl.uniform4f(
@@ -82,11 +77,11 @@ impl WebGlTrace {
                     0,
                 );
                 gl.enable_vertex_attrib_array(ctx.vertex_position_ptr);
-                gl.draw_arrays(
-                    WebGl2RenderingContext::TRIANGLE_STRIP,
-                    0,
-                    *vertex_count as i32,
-                );
+
+                // every point has two vertices in the fill buffer
+                for &(first, count) in segments {
+                    gl.draw_arrays(WebGl2RenderingContext::TRIANGLE_STRIP, first * 2, count * 2);
+                }
             }
             _ => {
                 // noop
@@ -173,11 +168,15 @@ impl WebGlTrace {
                 8,        // two f32
                 from * 8, // multiples of f32 pairs
             );
-            gl.draw_arrays_instanced(WebGl2RenderingContext::LINE_STRIP, 0, points, count);
+            for &(first, points) in segments {
+                gl.draw_arrays_instanced(WebGl2RenderingContext::LINE_STRIP, first, points, count);
+            }
         }
 
         if matches!(style.get_points(), TracePointsStyle::Show) {
-            gl.draw_arrays(WebGl2RenderingContext::POINTS, 0, points);
+            for &(first, points) in segments {
+                gl.draw_arrays(WebGl2RenderingContext::POINTS, first, points);
+            }
         }
     }
 }
@@ -15,12 +15,22 @@ use super::WebGlRenderer;
 
 #[derive(Clone)]
 pub struct TraceGeometry {
+    /// Range the vertices were computed for, they are stored as offsets from its start
     pub x_range: NumericRange,
+    /// Range of the bundle at the time the geometry was computed
+    pub bundle_range: NumericRange,
+    pub version: u64,
+
+    /// Horizontal pixels per unit of x the line was decimated for
+    pub density: f64,
 
     // Points and line tuff
     pub line_vertex_count: usize,
     pub line_buffer: WebGlBuffer,
 
+    /// `(first, count)` of every run of vertices without missing values
+    pub segments: Vec<(i32, i32)>,
+
     // Arc length stuff
     pub arc_pixel_ratio: (f64, f64),
     pub arc_length_buffer: WebGlBuffer,
@@ -30,6 +40,26 @@ pub struct TraceGeometry {
 }
 
 impl TraceGeometry {
+    /// Whether the line was decimated for a very different zoom level.
+    /// Zooming in requires more detail, zooming out far enough makes the line wastefully detailed.
+    fn is_density_stale(&self, density: f64) -> bool {
+        density > self.density * 2.0 || density * 8.0 < self.density
+    }
+
+    /// Whether the vertices, which are `f32` offsets from the start of `x_range`,
+    /// are accurate to a fraction of a pixel at the current zoom level
+    pub fn is_exact(&self, job: &RenderJobCommon, width: u32) -> bool {
+        self.x_range.len() <= max_exact_offset(job, width)
+    }
+
+    /// Whether the geometry covers the visible part of the bundle
+    fn covers_view(&self, job: &RenderJobCommon) -> bool {
+        let from = job.x_range.from.max(self.bundle_range.from);
+        let to = job.x_range.to.min(self.bundle_range.to);
+
+        from > to || (self.x_range.from <= from && to <= self.x_range.to)
+    }
+
     pub fn destroy(self, ctx: &WebGl2RenderingContext) {
         ctx.delete_buffer(Some(&self.line_buffer));
         ctx.delete_buffer(Some(&self.arc_length_buffer));
@@ -46,12 +76,15 @@ impl TraceGeometry {
         job: &RenderJobCommon,
         renderer_extents: (u32, u32),
     ) -> bool {
-        let x_range = match bundle.range() {
-            BundleRange::Bounded { from, to } => NumericRange::new(from, to),
-            BundleRange::Everywhere => job.x_range,
-        };
+        if bundle_x_range(bundle, job) != self.bundle_range || bundle.version() != self.version {
+            return true;
+        }
 
-        if x_range != self.x_range {
+        if !self.covers_view(job) {
+            return true;
+        }
+
+        if self.is_density_stale(renderer_extents.0 as f64 / job.x_range.len()) {
             return true;
         }
 
@@ -82,16 +115,20 @@ impl TraceGeometry {
         style: &TraceStyle,
         job: &RenderJobCommon,
     ) -> bool {
-        let x_range = match bundle.range() {
-            BundleRange::Bounded { from, to } => NumericRange::new(from, to),
-            BundleRange::Everywhere => job.x_range,
-        };
+        if bundle_x_range(bundle, job) != self.bundle_range || bundle.version() != self.version {
+            return false;
+        }
 
-        if x_range != self.x_range {
+        if !self.covers_view(job) || !self.is_exact(job, renderer.width) {
             return false;
         }
 
-        let data = Lazy::new(|| TraceData::compute(bundle, trace, x_range));
+        if self.is_density_stale(renderer.width as f64 / job.x_range.len()) {
+            return false;
+        }
+
+        let (x_range, density) = (self.x_range, self.density);
+        let data = Lazy::new(|| TraceData::compute(bundle, trace, x_range, density));
 
         if !style.get_line().is_solid() {
             let pr_x = renderer.width as f64 / job.x_range.len();
@@ -123,18 +160,21 @@ impl TraceGeometry {
         style: &TraceStyle,
         job: &RenderJobCommon,
     ) -> Self {
-        let x_range = match bundle.range() {
-            BundleRange::Bounded { from, to } => NumericRange::new(from, to),
-            BundleRange::Everywhere => job.x_range,
-        };
+        let bundle_range = bundle_x_range(bundle, job);
+        let x_range = line_range(bundle_range, job, renderer.width);
 
-        let data = TraceData::compute(bundle, trace, x_range);
+        let density = renderer.width as f64 / job.x_range.len();
+        let data = TraceData::compute(bundle, trace, x_range, density);
         let (pixel_ratio, length_buffer) = create_arc_length_buffer(renderer, &data, job);
 
         Self {
             x_range,
+            bundle_range,
+            version: bundle.version(),
+            density,
             line_vertex_count: data.data.len(),
             line_buffer: create_trace_buffer(renderer, &data),
+            segments: gap_segments(&data),
             arc_pixel_ratio: pixel_ratio,
             arc_length_buffer: length_buffer,
             fill_buffer: match style.fill {
@@ -157,10 +197,7 @@ impl TraceGeometry {
         static TRACE_BUFFER: Mutex<TraceData> = Mutex::new(TraceData { data: Vec::new() });
         static AREA_BUFFER: Mutex<Vec<f32>> = Mutex::new(Vec::new());
 
-        let x_range = match bundle.range() {
-            BundleRange::Bounded { from, to } => NumericRange::new(from, to),
-            BundleRange::Everywhere => job.x_range,
-        };
+        let x_range = bundle_x_range(bundle, job);
 
         let mut trace = TRACE_BUFFER.lock().unwrap();
         let mut area = AREA_BUFFER.lock().unwrap();
@@ -182,8 +219,13 @@ impl TraceGeometry {
 
         Self {
             x_range,
+            bundle_range: x_range,
+            version: bundle.version(),
+            // stacked areas are summed from all of the points, see `get_stacked_trace_geometry`
+            density: renderer.width as f64 / job.x_range.len(),
             line_vertex_count: trace.data.len(),
             line_buffer: create_trace_buffer(renderer, &trace),
+            segments: gap_segments(&trace),
             arc_pixel_ratio: pixel_ratio,
             arc_length_buffer: arc_buffer,
             fill_buffer: Some((trace.data.len() * 2, renderer.create_buffer(&area))),
@@ -191,6 +233,58 @@ impl TraceGeometry {
     }
 }
 
+fn bundle_x_range(bundle: &BundleRc, job: &RenderJobCommon) -> NumericRange {
+    match bundle.range() {
+        BundleRange::Bounded { from, to } => NumericRange::new(from, to),
+        BundleRange::Everywhere => job.x_range,
+    }
+}
+
+/// The largest offset from the start of a geometry that `f32` vertices
+/// can hold accurately to an eighth of a pixel
+fn max_exact_offset(job: &RenderJobCommon, width: u32) -> f64 {
+    job.x_range.len() / width as f64 / 8. / f32::EPSILON as f64
+}
+
+/// The range to compute a line for. It's the whole bundle unless that is too long
+/// for the vertices to be exact, e.g. when zoomed in on nanosecond timestamps,
+/// in which case it's the visible range with a screen's worth of margin on each side.
+fn line_range(bundle_range: NumericRange, job: &RenderJobCommon, width: u32) -> NumericRange {
+    if bundle_range.len() <= max_exact_offset(job, width) {
+        return bundle_range;
+    }
+
+    let margin = job.x_range.len();
+    NumericRange::new(
+        (job.x_range.from - margin).max(bundle_range.from),
+        (job.x_range.to + margin).min(bundle_range.to),
+    )
+}
+
+/// Splits the trace into runs of points with present values, so that lines
+/// and fills aren't drawn across missing samples
+fn gap_segments(trace: &TraceData) -> Vec<(i32, i32)> {
+    let mut segments = Vec::new();
+    let mut start = None;
+
+    for (i, (_, y)) in trace.data.iter().enumerate() {
+        match (start, y.is_nan()) {
+            (None, false) => start = Some(i),
+            (Some(s), true) => {
+                segments.push((s as i32, (i - s) as i32));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(s) = start {
+        segments.push((s as i32, (trace.data.len() - s) as i32));
+    }
+
+    segments
+}
+
 fn create_trace_buffer(renderer: &WebGlRenderer, trace: &TraceData) -> WebGlBuffer {
     renderer.create_buffer(unsafe {
         core::slice::from_raw_parts(
@@ -227,48 +321,33 @@ fn create_arc_length_buffer(
         );
     }
 
-    let lengths: Vec<f32> = {
-        let (first_x, first_y) = (
-            x_pixel_ratio * (trace.data[0].0 as f64 - job.x_range.from),
-            y_pixel_ratio * (trace.data[0].1 as f64 - job.y_range.from),
-        );
+    // the length restarts from zero at the beginning of every gap segment
+    let mut last: Option<(f64, f64)> = None;
+    let mut length_so_far = 0.0;
+
+    let lengths: Vec<f32> = trace
+        .data
+        .iter()
+        .map(|&(x, y)| {
+            let (x, y) = (
+                x_pixel_ratio * (x as f64 - job.x_range.from),
+                y_pixel_ratio * (y as f64 - job.y_range.from),
+            );
+
+            if y.is_nan() {
+                last = None;
+                return 0.;
+            }
 
-        #[derive(Clone, Debug)]
-        struct State {
-            length_so_far: f64,
-            last_x: f64,
-            last_y: f64,
-        }
+            length_so_far = match last {
+                Some((last_x, last_y)) => length_so_far + (last_x - x).hypot(last_y - y),
+                None => 0.,
+            };
+            last = Some((x, y));
 
-        let initial_state = State {
-            length_so_far: 0.0,
-            last_x: first_x,
-            last_y: first_y,
-        };
-
-        trace
-            .data
-            .iter()
-            .map(|(x, y)| {
-                (
-                    x_pixel_ratio * (*x as f64 - job.x_range.from),
-                    y_pixel_ratio * (*y as f64 - job.y_range.from),
-                )
-            })
-            .scan(initial_state, |state: &mut State, (x, y): (f64, f64)| {
-                let len_sqr: f64 = (state.last_x - x).powi(2) + (state.last_y - y).powi(2);
-                if !len_sqr.is_nan() {
-                    *state = State {
-                        last_x: x,
-                        last_y: y,
-                        length_so_far: state.length_so_far + len_sqr.sqrt(),
-                    };
-                };
-
-                Some(state.length_so_far as f32)
-            })
-            .collect()
-    };
+            length_so_far as f32
+        })
+        .collect();
 
     (
         (x_pixel_ratio, y_pixel_ratio),
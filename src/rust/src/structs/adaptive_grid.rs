@@ -132,8 +132,8 @@ impl<'a, I: Iterator<Item = (f64, f64)>> Iterator for SumAddIterator<'a, I> {
             match state {
                 SumAddIteratorState::Append => {
                     self.grid.x.push(x);
-                    self.grid.y.push(y);
-                    self.grid.overlay.push(y);
+                    self.grid.y.push(present_or_zero(y));
+                    self.grid.overlay.push(present_or_zero(y));
 
                     self.prev_point = Some((x, y));
 
@@ -142,8 +142,8 @@ impl<'a, I: Iterator<Item = (f64, f64)>> Iterator for SumAddIterator<'a, I> {
                 SumAddIteratorState::Prepend { until, idx } => {
                     if x < until {
                         self.grid.x.insert(idx, x);
-                        self.grid.y.insert(idx, y);
-                        self.grid.overlay.insert(idx, y);
+                        self.grid.y.insert(idx, present_or_zero(y));
+                        self.grid.overlay.insert(idx, present_or_zero(y));
 
                         self.prev_point = Some((x, y));
                         self.state = Some(SumAddIteratorState::Prepend {
@@ -175,7 +175,9 @@ impl<'a, I: Iterator<Item = (f64, f64)>> Iterator for SumAddIterator<'a, I> {
 
                         self.grid.x.insert(grid_idx, x);
                         self.grid.y.insert(grid_idx, grid_y);
-                        self.grid.overlay.insert(grid_idx, grid_y + y);
+                        self.grid
+                            .overlay
+                            .insert(grid_idx, grid_y + present_or_zero(y));
 
                         self.prev_point = Some((x, y));
 
@@ -186,7 +188,7 @@ impl<'a, I: Iterator<Item = (f64, f64)>> Iterator for SumAddIterator<'a, I> {
                         });
                         self.prev_point = Some((x, y));
 
-                        self.grid.overlay[grid_idx] = grid_y + y;
+                        self.grid.overlay[grid_idx] = grid_y + present_or_zero(y);
 
                         return Some((grid_x, grid_y, grid_y + y));
                     } else {
@@ -200,7 +202,7 @@ impl<'a, I: Iterator<Item = (f64, f64)>> Iterator for SumAddIterator<'a, I> {
                         // unwrap safe by assumption B
                         let y = interpolate(self.prev_point.unwrap(), (x, y), grid_x);
 
-                        self.grid.overlay[grid_idx] = grid_y + y;
+                        self.grid.overlay[grid_idx] = grid_y + present_or_zero(y);
 
                         return Some((grid_x, grid_y, grid_y + y));
                     }
@@ -210,10 +212,29 @@ impl<'a, I: Iterator<Item = (f64, f64)>> Iterator for SumAddIterator<'a, I> {
     }
 }
 
+/// Linearly interpolates between two points, the result is missing (NaN)
+/// if either of the points is missing
 fn interpolate(prev: (f64, f64), next: (f64, f64), at: f64) -> f64 {
+    if prev.1.is_nan() || next.1.is_nan() {
+        return f64::NAN;
+    }
+
+    if prev.0 == next.0 {
+        return next.1;
+    }
+
     prev.1 + (next.1 - prev.1) * (at - prev.0) / (next.0 - prev.0)
 }
 
+/// A missing sample doesn't add anything to the layers stacked on top of it
+fn present_or_zero(y: f64) -> f64 {
+    if y.is_nan() {
+        0.
+    } else {
+        y
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::AdaptiveGrid;
@@ -245,4 +266,20 @@ mod tests {
             vec![(0.5, 1. + 2. + 0.5), (1., 1. + 1.), (2., 1. - 1.), (3., 1.)],
         );
     }
+
+    #[test]
+    fn gaps_do_not_propagate() {
+        let mut grid = AdaptiveGrid::new();
+        let out = grid
+            .sum_add_points([(0.0, 1.0), (1.0, f64::NAN), (2.0, 1.0)])
+            .map(|(_, _, y)| y)
+            .collect::<Vec<_>>();
+        assert!(out[1].is_nan());
+
+        let out = grid
+            .sum_add_points([(0.0, 1.0), (1.0, 1.0), (2.0, 1.0)])
+            .map(|(x, _, y)| (x, y))
+            .collect::<Vec<_>>();
+        assert_eq!(out, vec![(0., 2.), (1., 1.), (2., 2.)]);
+    }
 }
@@ -0,0 +1,443 @@
+//! Ingestion of Arrow IPC streams and files.
+//!
+//! The x column may be any integer, floating point, date or timestamp column. Dates and
+//! timestamps are kept as exact integers in their unit and exposed relative to the first one,
+//! see [`TimeAxis`](crate::trace::TimeAxis). All the other columns are y columns and must be
+//! numeric or boolean, dates and timestamps among them are converted to seconds since
+//! the Unix epoch. Columns keep their type if all
+//! the y columns share it and are widened otherwise. Nulls in a y column become missing
+//! samples, rows with a null or NaN x are dropped.
+
+use std::{fmt, io::Cursor};
+
+use arrow_array::{cast::AsArray, types::*, Array, ArrowPrimitiveType, RecordBatch};
+use arrow_ipc::reader::{FileReader, StreamReader};
+use arrow_schema::{ArrowError, DataType, Schema, TimeUnit};
+use num_traits::ToPrimitive;
+
+use crate::{
+    data::TraceHandle,
+    error::ChartError,
+    trace::{BundleRc, EpochUnit},
+};
+
+use super::{
+    columns::{XColumn, YColumns},
+    data_types::Storage,
+    rows_into_bundle,
+};
+
+/// Files start with this magic, while streams start with a message length
+const FILE_MAGIC: &[u8; 6] = b"ARROW1";
+
+#[derive(Debug)]
+pub enum ArrowLoadError {
+    Arrow(ArrowError),
+    MissingColumn(String),
+    UnsupportedType { column: String, data_type: DataType },
+    HandleCountMismatch { columns: usize, handles: usize },
+}
+
+impl fmt::Display for ArrowLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArrowLoadError::Arrow(e) => write!(f, "invalid arrow data: {e}"),
+            ArrowLoadError::MissingColumn(name) => write!(f, "there is no column named {name}"),
+            ArrowLoadError::UnsupportedType { column, data_type } => {
+                write!(f, "column {column} has unsupported type {data_type}")
+            }
+            ArrowLoadError::HandleCountMismatch { columns, handles } => {
+                write!(f, "got {handles} trace handles for {columns} y columns")
+            }
+        }
+    }
+}
+
+impl From<ArrowError> for ArrowLoadError {
+    fn from(e: ArrowError) -> Self {
+        ArrowLoadError::Arrow(e)
+    }
+}
+
+type RecordBatches = Box<dyn Iterator<Item = Result<RecordBatch, ArrowError>>>;
+
+/// Decodes an Arrow IPC file or stream into a bundle.
+///
+/// The x column is looked up by name, defaulting to the first column. `handles`
+/// are assigned to the remaining columns in the order they appear in the schema.
+pub fn read_arrow_ipc(
+    data: Vec<u8>,
+    x_column: Option<&str>,
+    handles: &[TraceHandle],
+) -> Result<BundleRc, ChartError> {
+    let (schema, batches): (_, RecordBatches) = if data.starts_with(FILE_MAGIC) {
+        let reader = FileReader::try_new(Cursor::new(data), None)?;
+        (reader.schema(), Box::new(reader))
+    } else {
+        let reader = StreamReader::try_new(Cursor::new(data), None)?;
+        (reader.schema(), Box::new(reader))
+    };
+
+    let mut sink = RecordBatchSink::new(&schema, x_column, None, handles)?;
+    for batch in batches {
+        sink.push(&batch?)?;
+    }
+
+    sink.finish(handles)
+}
+
+/// Collects the x and y columns of consecutive record batches.
+pub(super) struct RecordBatchSink {
+    x_idx: usize,
+    y_indices: Vec<usize>,
+
+    x: XColumn,
+    ys: YColumns,
+}
+
+impl RecordBatchSink {
+    /// Looks up the columns in `schema`. The x column defaults to the first column,
+    /// the y columns to all the other columns in schema order.
+    pub(super) fn new(
+        schema: &Schema,
+        x_column: Option<&str>,
+        y_columns: Option<&[String]>,
+        handles: &[TraceHandle],
+    ) -> Result<Self, ChartError> {
+        let fields = schema.fields();
+        if fields.is_empty() {
+            return Err(ChartError::NoData);
+        }
+
+        let index_of = |name: &str| {
+            schema
+                .index_of(name)
+                .map_err(|_| ArrowLoadError::MissingColumn(name.to_string()))
+        };
+
+        let x_idx = x_column.map_or(Ok(0), index_of)?;
+        let y_indices: Vec<usize> = match y_columns {
+            Some(names) => names
+                .iter()
+                .map(|name| index_of(name))
+                .collect::<Result<_, _>>()?,
+            None => (0..fields.len()).filter(|&i| i != x_idx).collect(),
+        };
+
+        if y_indices.len() != handles.len() {
+            return Err(ArrowLoadError::HandleCountMismatch {
+                columns: y_indices.len(),
+                handles: handles.len(),
+            }
+            .into());
+        }
+
+        let x_type = fields[x_idx].data_type();
+        let x_unit = time_unit(x_type);
+        let x_storage = match storage_of(x_type) {
+            _ if x_unit.is_some() => Storage::I64,
+            Some(storage) if !is_boolean(x_type) => storage,
+            _ => return Err(unsupported(schema, x_idx).into()),
+        };
+        let y_storages = y_indices
+            .iter()
+            .map(|&i| storage_of(fields[i].data_type()).ok_or_else(|| unsupported(schema, i)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            x_idx,
+            x: XColumn::of(x_storage, x_unit, 0),
+            ys: YColumns::of(Storage::common(y_storages), y_indices.len(), 0),
+            y_indices,
+        })
+    }
+
+    /// Appends the rows of a batch with the same schema as the one the sink was created with.
+    pub(super) fn push(&mut self, batch: &RecordBatch) -> Result<(), ArrowLoadError> {
+        let schema = batch.schema();
+        let x_array = batch.column(self.x_idx).as_ref();
+        let rows = present_rows(x_array);
+
+        decode_column(x_array, rows.as_deref(), true, &mut self.x)
+            .ok_or_else(|| unsupported(&schema, self.x_idx))?;
+        for (column, &i) in self.y_indices.iter().enumerate() {
+            let mut sink = (&mut self.ys, column);
+            decode_column(batch.column(i).as_ref(), rows.as_deref(), false, &mut sink)
+                .ok_or_else(|| unsupported(&schema, i))?;
+        }
+
+        Ok(())
+    }
+
+    /// Sorts the rows by x, unless they are sorted already.
+    pub(super) fn finish(self, handles: &[TraceHandle]) -> Result<BundleRc, ChartError> {
+        rows_into_bundle(self.x, self.ys, handles)
+    }
+}
+
+fn unsupported(schema: &Schema, i: usize) -> ArrowLoadError {
+    let field = schema.field(i);
+
+    ArrowLoadError::UnsupportedType {
+        column: field.name().clone(),
+        data_type: field.data_type().clone(),
+    }
+}
+
+/// The type values of a column are stored as, dates and timestamps as seconds.
+/// `None` for types which can't be loaded.
+fn storage_of(data_type: &DataType) -> Option<Storage> {
+    use DataType::*;
+
+    let storage = match data_type {
+        Int8 => Storage::I8,
+        Int16 => Storage::I16,
+        Int32 => Storage::I32,
+        Int64 | Date32 | Timestamp(TimeUnit::Second, _) => Storage::I64,
+        UInt8 | Boolean => Storage::U8,
+        UInt16 => Storage::U16,
+        UInt32 => Storage::U32,
+        UInt64 => Storage::U64,
+        Float16 | Float32 => Storage::F32,
+        Float64 | Date64 | Timestamp(_, _) => Storage::F64,
+        _ => return None,
+    };
+
+    Some(storage)
+}
+
+/// Unit of the dates or timestamps of a column, days are counted in seconds
+fn time_unit(data_type: &DataType) -> Option<EpochUnit> {
+    use DataType::*;
+
+    match data_type {
+        Date32 | Timestamp(TimeUnit::Second, _) => Some(EpochUnit::S),
+        Date64 | Timestamp(TimeUnit::Millisecond, _) => Some(EpochUnit::Ms),
+        Timestamp(TimeUnit::Microsecond, _) => Some(EpochUnit::Us),
+        Timestamp(TimeUnit::Nanosecond, _) => Some(EpochUnit::Ns),
+        _ => None,
+    }
+}
+
+fn is_boolean(data_type: &DataType) -> bool {
+    matches!(data_type, DataType::Boolean)
+}
+
+/// The rows with an x value, `None` if all of them have one
+fn present_rows(x: &dyn Array) -> Option<Vec<bool>> {
+    fn not_nan<T: ArrowPrimitiveType>(x: &dyn Array) -> Vec<bool>
+    where
+        T::Native: ToPrimitive,
+    {
+        x.as_primitive::<T>()
+            .iter()
+            .map(|v| v.and_then(|v| v.to_f64()).is_some_and(|v| !v.is_nan()))
+            .collect()
+    }
+
+    let rows = match x.data_type() {
+        DataType::Float16 => not_nan::<Float16Type>(x),
+        DataType::Float32 => not_nan::<Float32Type>(x),
+        DataType::Float64 => not_nan::<Float64Type>(x),
+        _ => x.logical_nulls()?.iter().collect(),
+    };
+
+    rows.contains(&false).then_some(rows)
+}
+
+/// A column values get decoded into
+trait ColumnSink {
+    fn push<T: ToPrimitive + Copy>(&mut self, value: Option<T>);
+}
+
+impl ColumnSink for XColumn {
+    fn push<T: ToPrimitive + Copy>(&mut self, value: Option<T>) {
+        // rows without an x are skipped before they get here
+        if let Some(value) = value {
+            self.push_cast(value);
+        }
+    }
+}
+
+impl ColumnSink for (&mut YColumns, usize) {
+    fn push<T: ToPrimitive + Copy>(&mut self, value: Option<T>) {
+        match value {
+            Some(value) => self.0.push_cast(self.1, value),
+            None => self.0.push_missing(self.1),
+        }
+    }
+}
+
+/// Appends the values of `rows` to `sink`, all rows if not given. Dates and timestamps
+/// are appended as integers in their [`time_unit`] if `ticks` is set, and as seconds otherwise.
+/// Returns `None` for unsupported types.
+fn decode_column(
+    array: &dyn Array,
+    rows: Option<&[bool]>,
+    ticks: bool,
+    sink: &mut impl ColumnSink,
+) -> Option<()> {
+    use DataType::*;
+
+    let mut decode = Decoder { array, rows, sink };
+    let scale = match time_unit(array.data_type()) {
+        Some(unit) if !ticks => unit.seconds(),
+        _ => 1.,
+    };
+
+    match array.data_type() {
+        Int8 => decode.primitive::<Int8Type, _>(|v| v),
+        Int16 => decode.primitive::<Int16Type, _>(|v| v),
+        Int32 => decode.primitive::<Int32Type, _>(|v| v),
+        Int64 => decode.primitive::<Int64Type, _>(|v| v),
+        UInt8 => decode.primitive::<UInt8Type, _>(|v| v),
+        UInt16 => decode.primitive::<UInt16Type, _>(|v| v),
+        UInt32 => decode.primitive::<UInt32Type, _>(|v| v),
+        UInt64 => decode.primitive::<UInt64Type, _>(|v| v),
+        Float16 => decode.primitive::<Float16Type, _>(|v| v),
+        Float32 => decode.primitive::<Float32Type, _>(|v| v),
+        Float64 => decode.primitive::<Float64Type, _>(|v| v),
+        Date32 => decode.primitive::<Date32Type, _>(|days| days as i64 * 86_400),
+        Date64 if ticks => decode.primitive::<Date64Type, _>(|ms| ms),
+        Date64 => decode.primitive::<Date64Type, _>(|ms| ms as f64 * scale),
+        Timestamp(TimeUnit::Second, _) => decode.primitive::<TimestampSecondType, _>(|s| s),
+        Timestamp(unit, _) if ticks => match unit {
+            TimeUnit::Millisecond => decode.primitive::<TimestampMillisecondType, _>(|t| t),
+            TimeUnit::Microsecond => decode.primitive::<TimestampMicrosecondType, _>(|t| t),
+            _ => decode.primitive::<TimestampNanosecondType, _>(|t| t),
+        },
+        Timestamp(unit, _) => match unit {
+            TimeUnit::Millisecond => {
+                decode.primitive::<TimestampMillisecondType, _>(|t| t as f64 * scale)
+            }
+            TimeUnit::Microsecond => {
+                decode.primitive::<TimestampMicrosecondType, _>(|t| t as f64 * scale)
+            }
+            _ => decode.primitive::<TimestampNanosecondType, _>(|t| t as f64 * scale),
+        },
+        Boolean => decode.values(array.as_boolean().iter().map(|v| v.map(u8::from))),
+        _ => return None,
+    }
+
+    Some(())
+}
+
+struct Decoder<'a, S> {
+    array: &'a dyn Array,
+    rows: Option<&'a [bool]>,
+    sink: &'a mut S,
+}
+
+impl<S: ColumnSink> Decoder<'_, S> {
+    fn primitive<T: ArrowPrimitiveType, V: ToPrimitive + Copy>(
+        &mut self,
+        convert: impl Fn(T::Native) -> V,
+    ) {
+        let values = self.array.as_primitive::<T>().iter();
+        self.values(values.map(|v| v.map(&convert)));
+    }
+
+    fn values<V: ToPrimitive + Copy>(&mut self, values: impl Iterator<Item = Option<V>>) {
+        match self.rows {
+            Some(rows) => values
+                .zip(rows)
+                .filter(|(_, present)| **present)
+                .for_each(|(v, _)| self.sink.push(v)),
+            None => values.for_each(|v| self.sink.push(v)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{
+        Float32Array, Int64Array, RecordBatch, TimestampMillisecondArray, UInt8Array,
+    };
+    use arrow_ipc::writer::StreamWriter;
+    use arrow_schema::{DataType, Field, Schema, TimeUnit};
+
+    use super::read_arrow_ipc;
+    use crate::types::NumericRange;
+
+    #[test]
+    fn reads_stream_with_nulls() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("value", DataType::Float32, true),
+            Field::new(
+                "time",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                true,
+            ),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Float32Array::from(vec![Some(1.), None, Some(3.), Some(4.)])),
+                Arc::new(TimestampMillisecondArray::from(vec![
+                    Some(1500),
+                    Some(2500),
+                    Some(500),
+                    None,
+                ])),
+            ],
+        )
+        .unwrap();
+
+        let mut data = Vec::new();
+        let mut writer = StreamWriter::try_new(&mut data, &schema).unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        assert!(read_arrow_ipc(data.clone(), Some("timestamp"), &[1]).is_err());
+
+        let bundle = read_arrow_ipc(data, Some("time"), &[1]).unwrap();
+        let points: Vec<_> = bundle
+            .iter_in_range_f64(1, NumericRange::new(0., 10.))
+            .collect();
+
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[0], (0., 3.));
+        assert_eq!(points[1], (1., 1.));
+        assert_eq!(points[2].0, 2.);
+        assert!(points[2].1.is_nan());
+        assert_eq!(bundle.epoch(), Some(500));
+        assert_eq!(bundle.stored_types(), Some(("i64", "f32")));
+    }
+
+    #[test]
+    fn keeps_column_types() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("time", DataType::Int64, false),
+            Field::new("a", DataType::UInt8, true),
+            Field::new("b", DataType::UInt8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![3, 1, 2])),
+                Arc::new(UInt8Array::from(vec![Some(30), None, Some(20)])),
+                Arc::new(UInt8Array::from(vec![Some(3), Some(1), Some(2)])),
+            ],
+        )
+        .unwrap();
+
+        let mut data = Vec::new();
+        let mut writer = StreamWriter::try_new(&mut data, &schema).unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        let bundle = read_arrow_ipc(data, None, &[1, 2]).unwrap();
+        assert_eq!(bundle.stored_types(), Some(("i64", "u8")));
+
+        let rows: Vec<_> = bundle
+            .iter_many_in_range_f64(vec![1, 2], NumericRange::new(0., 10.))
+            .collect();
+        assert!(rows[0][1].is_nan());
+        assert_eq!(rows[0][2], 1.);
+        assert_eq!(rows[1..], [vec![2., 20., 2.], vec![3., 30., 3.]]);
+    }
+}
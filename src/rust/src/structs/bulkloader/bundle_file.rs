@@ -0,0 +1,504 @@
+//! Versioned binary container for persisting bundles.
+//!
+//! All numbers are little-endian. The layout of version 2 is:
+//! * magic `CHRTBNDL`, format version as `u16`
+//! * bundle kind as `u8`, see [`BundleKind`]
+//! * x and y type names, each as a `u8` length followed by UTF-8 bytes
+//! * time unit of the x values as `u8`, see [`time_unit_tag`], and their epoch as `i64`
+//! * trace count as `u32`, followed by the trace handles as `u32`s
+//! * point count as `u64`, the x range as two `f64`s
+//! * the x column followed by one y column per trace handle
+
+use std::{collections::HashMap, fmt};
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    data::TraceHandle,
+    error::ChartError,
+    trace::{
+        Bundle, BundleRange, BundleRc, ConstantBatch, EpochUnit, InterpolationStrategy, TimeAxis,
+    },
+    types::NumericRange,
+};
+
+use super::{
+    columns::{XColumn, YColumns},
+    data_types::{type_desc, Storage},
+};
+
+const MAGIC: &[u8; 8] = b"CHRTBNDL";
+const FORMAT_VERSION: u16 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BundleKind {
+    /// Traces sampled at the same x values
+    Sampled = 0,
+    /// A constant value per trace, valid everywhere
+    Constant = 1,
+}
+
+#[derive(Debug)]
+pub enum BundleFileError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    UnknownKind(u8),
+    UnknownType(String),
+    UnknownTimeUnit(u8),
+    Truncated,
+    /// The bundle can't be saved without changing its points
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for BundleFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BundleFileError::BadMagic => write!(f, "not a bundle file"),
+            BundleFileError::UnsupportedVersion(v) => {
+                write!(f, "unsupported bundle file version {v}")
+            }
+            BundleFileError::UnknownKind(k) => write!(f, "unknown bundle kind {k}"),
+            BundleFileError::UnknownType(t) => write!(f, "unknown data type {t}"),
+            BundleFileError::UnknownTimeUnit(u) => write!(f, "unknown time unit {u}"),
+            BundleFileError::Truncated => write!(f, "bundle file is truncated"),
+            BundleFileError::Unsupported(why) => write!(f, "the bundle can't be saved, {why}"),
+        }
+    }
+}
+
+pub struct BundleFileHeader {
+    pub kind: BundleKind,
+    pub x_type: String,
+    pub y_type: String,
+    /// Set if the x values are timestamps, which are stored in its unit
+    pub time_axis: Option<TimeAxis>,
+    pub handles: Vec<TraceHandle>,
+    pub point_count: usize,
+    pub x_range: NumericRange,
+}
+
+/// Serializes a bundle whose traces share their x values, keeping the types the bundle
+/// stores its values as and its time axis. Appendable bundles are saved as a snapshot of their current points.
+///
+/// Fails for bundles without any points and for ragged bundles, which would come back
+/// with every trace padded to the x values of all of them.
+pub fn write_bundle_file(bundle: &dyn Bundle) -> Result<Vec<u8>, ChartError> {
+    let mut handles = bundle.traces();
+    handles.sort_unstable();
+
+    let (kind, rows, x_range) = match bundle.range() {
+        BundleRange::Everywhere => {
+            let row = std::iter::once(0.)
+                .chain(handles.iter().map(|&h| {
+                    bundle
+                        .value_at(h, 0., InterpolationStrategy::None)
+                        .map_or(f64::NAN, |(_, y)| y)
+                }))
+                .collect();
+
+            (BundleKind::Constant, vec![row], NumericRange::new(0., 0.))
+        }
+        BundleRange::Bounded { from, to } => {
+            let x_range = NumericRange::new(from, to);
+            let rows: Vec<Vec<f64>> = bundle
+                .iter_many_in_range_f64(handles.clone(), x_range)
+                .collect();
+
+            if rows.is_empty() {
+                return Err(ChartError::NoData);
+            }
+            if handles
+                .iter()
+                .any(|&h| bundle.iter_in_range_f64(h, x_range).count() != rows.len())
+            {
+                return Err(BundleFileError::Unsupported("its traces don't share x values").into());
+            }
+
+            (BundleKind::Sampled, rows, x_range)
+        }
+    };
+
+    let time_axis = bundle.time_axis();
+    let (x_type, y_type) = stored_types(bundle, &rows);
+    let [x_desc, y_desc] = [x_type, y_type].map(|t| type_desc(t).unwrap());
+
+    let mut out = Vec::with_capacity(64 + rows.len() * (x_desc.size + handles.len() * y_desc.size));
+
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.push(kind as u8);
+    for name in [x_type, y_type] {
+        out.push(name.len() as u8);
+        out.extend_from_slice(name.as_bytes());
+    }
+    out.push(time_unit_tag(time_axis.map(|axis| axis.unit)));
+    out.extend_from_slice(&time_axis.map_or(0, |axis| axis.epoch).to_le_bytes());
+    out.extend_from_slice(&(handles.len() as u32).to_le_bytes());
+    for handle in &handles {
+        out.extend_from_slice(&handle.to_le_bytes());
+    }
+    out.extend_from_slice(&(rows.len() as u64).to_le_bytes());
+    out.extend_from_slice(&x_range.from.to_le_bytes());
+    out.extend_from_slice(&x_range.to.to_le_bytes());
+
+    // wide integers are written from the values the bundle stores rather than their `f64`s
+    let exact = |storage: Storage, trace: Option<TraceHandle>| {
+        matches!(storage, Storage::I64 | Storage::U64)
+            .then(|| bundle.integer_column(trace))
+            .flatten()
+            .filter(|column| column.len() == rows.len())
+    };
+
+    match exact(x_desc.storage, None) {
+        Some(column) => column
+            .into_iter()
+            .for_each(|x| encode_integer(x_desc.storage, x, &mut out)),
+        None => {
+            for row in &rows {
+                match time_axis {
+                    Some(axis) => out.extend_from_slice(&axis.to_timestamp(row[0]).to_le_bytes()),
+                    None => encode(x_desc.storage, row[0], &mut out),
+                }
+            }
+        }
+    }
+    for (col, &handle) in handles.iter().enumerate() {
+        match exact(y_desc.storage, Some(handle)) {
+            Some(column) => column
+                .into_iter()
+                .for_each(|y| encode_integer(y_desc.storage, y, &mut out)),
+            None => {
+                for row in &rows {
+                    encode(y_desc.storage, row[col + 1], &mut out);
+                }
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// The types the columns are written as. Values keep the type the bundle stores them as,
+/// except for integer y columns with missing samples, which only a float can represent.
+/// Timestamps are written as `i64` ticks of their time unit. Other bundles are written
+/// as `i64` x values if all of them are integral, and as `f64` otherwise.
+fn stored_types(bundle: &dyn Bundle, rows: &[Vec<f64>]) -> (&'static str, &'static str) {
+    let stored = bundle.stored_types();
+
+    let x_type = match stored {
+        _ if bundle.time_axis().is_some() => "i64",
+        Some((x_type, _)) => x_type,
+        _ if rows.iter().all(|row| row[0].fract() == 0.) => "i64",
+        _ => "f64",
+    };
+
+    let y_type = match stored {
+        Some((_, y_type)) if y_type.starts_with('f') => y_type,
+        Some((_, y_type)) if !rows.iter().any(|row| row[1..].iter().any(|y| y.is_nan())) => y_type,
+        _ => "f64",
+    };
+
+    (x_type, y_type)
+}
+
+/// Appends `value` converted to `storage` as little-endian bytes
+fn encode(storage: Storage, value: f64, out: &mut Vec<u8>) {
+    match storage {
+        Storage::U8 => out.push(value as u8),
+        Storage::I8 => out.extend_from_slice(&(value as i8).to_le_bytes()),
+        Storage::U16 => out.extend_from_slice(&(value as u16).to_le_bytes()),
+        Storage::I16 => out.extend_from_slice(&(value as i16).to_le_bytes()),
+        Storage::U32 => out.extend_from_slice(&(value as u32).to_le_bytes()),
+        Storage::I32 => out.extend_from_slice(&(value as i32).to_le_bytes()),
+        Storage::I64 => out.extend_from_slice(&(value as i64).to_le_bytes()),
+        Storage::U64 => out.extend_from_slice(&(value as u64).to_le_bytes()),
+        Storage::F32 => out.extend_from_slice(&(value as f32).to_le_bytes()),
+        Storage::F64 => out.extend_from_slice(&value.to_le_bytes()),
+    }
+}
+
+/// Appends an integer of a 64-bit `storage` as little-endian bytes
+fn encode_integer(storage: Storage, value: i128, out: &mut Vec<u8>) {
+    match storage {
+        Storage::U64 => out.extend_from_slice(&(value as u64).to_le_bytes()),
+        _ => out.extend_from_slice(&(value as i64).to_le_bytes()),
+    }
+}
+
+/// The byte a time unit is stored as, zero if the x values aren't timestamps
+fn time_unit_tag(unit: Option<EpochUnit>) -> u8 {
+    match unit {
+        None => 0,
+        Some(EpochUnit::S) => 1,
+        Some(EpochUnit::Ms) => 2,
+        Some(EpochUnit::Us) => 3,
+        Some(EpochUnit::Ns) => 4,
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    cursor: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], BundleFileError> {
+        let end = self
+            .cursor
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or(BundleFileError::Truncated)?;
+
+        let bytes = &self.data[self.cursor..end];
+        self.cursor = end;
+        Ok(bytes)
+    }
+
+    fn take_array<const L: usize>(&mut self) -> Result<[u8; L], BundleFileError> {
+        Ok(self.take(L)?.try_into().unwrap())
+    }
+
+    fn take_string(&mut self) -> Result<String, BundleFileError> {
+        let len = self.take_array::<1>()?[0] as usize;
+        let bytes = self.take(len)?;
+
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| BundleFileError::UnknownType(String::from_utf8_lossy(bytes).into()))
+    }
+}
+
+fn read_header(reader: &mut Reader) -> Result<BundleFileHeader, BundleFileError> {
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(BundleFileError::BadMagic);
+    }
+
+    let version = u16::from_le_bytes(reader.take_array()?);
+    if version != FORMAT_VERSION {
+        return Err(BundleFileError::UnsupportedVersion(version));
+    }
+
+    let kind = match reader.take_array::<1>()?[0] {
+        0 => BundleKind::Sampled,
+        1 => BundleKind::Constant,
+        k => return Err(BundleFileError::UnknownKind(k)),
+    };
+
+    let x_type = reader.take_string()?;
+    let y_type = reader.take_string()?;
+
+    let unit = match reader.take_array::<1>()?[0] {
+        0 => None,
+        1 => Some(EpochUnit::S),
+        2 => Some(EpochUnit::Ms),
+        3 => Some(EpochUnit::Us),
+        4 => Some(EpochUnit::Ns),
+        u => return Err(BundleFileError::UnknownTimeUnit(u)),
+    };
+    let epoch = i64::from_le_bytes(reader.take_array()?);
+    let time_axis = unit.map(|unit| TimeAxis::new(unit, epoch));
+
+    let trace_count = u32::from_le_bytes(reader.take_array()?) as usize;
+    let handles = reader
+        .take(
+            trace_count
+                .checked_mul(4)
+                .ok_or(BundleFileError::Truncated)?,
+        )?
+        .chunks_exact(4)
+        .map(|h| TraceHandle::from_le_bytes(h.try_into().unwrap()))
+        .collect();
+
+    let point_count = u64::from_le_bytes(reader.take_array()?) as usize;
+    let from = f64::from_le_bytes(reader.take_array()?);
+    let to = f64::from_le_bytes(reader.take_array()?);
+
+    Ok(BundleFileHeader {
+        kind,
+        x_type,
+        y_type,
+        time_axis,
+        handles,
+        point_count,
+        x_range: NumericRange::new(from, to),
+    })
+}
+
+/// Reads just the header of a bundle file, without decoding its data.
+pub fn read_bundle_file_header(data: &[u8]) -> Result<BundleFileHeader, BundleFileError> {
+    read_header(&mut Reader { data, cursor: 0 })
+}
+
+pub fn read_bundle_file(data: &[u8]) -> Result<BundleRc, ChartError> {
+    let mut reader = Reader { data, cursor: 0 };
+    let header = read_header(&mut reader)?;
+
+    let [x_desc, y_desc] = [&header.x_type, &header.y_type]
+        .map(|t| type_desc(t).map_err(|_| BundleFileError::UnknownType(t.clone())));
+    let (x_desc, y_desc) = (x_desc?, y_desc?);
+
+    let point_count = header.point_count;
+    let column_len = |size: usize| {
+        point_count
+            .checked_mul(size)
+            .ok_or(BundleFileError::Truncated)
+    };
+
+    let x_bytes = reader.take(column_len(x_desc.size)?)?;
+    // columns are checked against the file length before anything gets allocated
+    let mut y_bytes = Vec::new();
+    for _ in &header.handles {
+        y_bytes.push(reader.take(column_len(y_desc.size)?)?);
+    }
+
+    if point_count == 0 {
+        return Err(ChartError::NoData);
+    }
+
+    match header.kind {
+        BundleKind::Constant => {
+            let ys = HashMap::from_iter(
+                header.handles.iter().copied().zip(
+                    y_bytes
+                        .iter()
+                        .map(|column| y_desc.decode(&column[..y_desc.size])),
+                ),
+            );
+
+            Ok(BundleRc::new(ConstantBatch::new(ys)))
+        }
+        BundleKind::Sampled => {
+            let unit = header.time_axis.map(|axis| axis.unit);
+            let mut x = XColumn::of(x_desc.storage, unit.or(x_desc.unit), point_count);
+            for value in x_bytes.chunks_exact(x_desc.size) {
+                x.push_raw(&x_desc, value);
+            }
+
+            let mut y = YColumns::with_capacity(&y_desc, y_bytes.len(), point_count);
+            for (column, bytes) in y_bytes.iter().enumerate() {
+                for value in bytes.chunks_exact(y_desc.size) {
+                    y.push_raw(column, &y_desc, value);
+                }
+            }
+
+            let epoch = header.time_axis.map(|axis| axis.epoch);
+            x.into_bundle(y, &header.handles, epoch)
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl BundleRc {
+    /// Serializes the bundle into a self-describing binary file,
+    /// which can be loaded again using `Bulkloader.from_bundle_file`.
+    pub fn to_bundle_file(&self) -> Result<Vec<u8>, ChartError> {
+        write_bundle_file(&**self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_bundle_file, write_bundle_file, BundleFileError};
+    use crate::{
+        error::ChartError,
+        trace::{
+            Batch, Bundle, BundleRange, EpochUnit, LiveBatch, RaggedBatch, RetentionPolicy,
+            TimeAxis,
+        },
+        types::NumericRange,
+    };
+
+    #[test]
+    fn roundtrip() {
+        let batch = Batch::new(
+            vec![10i64, 20, 30],
+            vec![1., 2., f64::NAN, 4., 5., 6.],
+            &[5, 3],
+        )
+        .unwrap();
+        let file = write_bundle_file(&batch).unwrap();
+
+        let bundle = read_bundle_file(&file).unwrap();
+        assert!(
+            matches!(bundle.range(), BundleRange::Bounded { from, to } if from == 10. && to == 30.)
+        );
+
+        let rows: Vec<_> = bundle
+            .iter_many_in_range_f64(vec![3, 5], NumericRange::new(0., 100.))
+            .collect();
+        assert_eq!(rows[0], vec![10., 4., 1.]);
+        assert!(rows[2][2].is_nan());
+
+        assert!(read_bundle_file(&file[..file.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn keeps_stored_types() {
+        let batch = Batch::<u32, u8>::new(vec![1, 2, 3], vec![4, 5, 6, 7, 8, 9], &[1, 2]).unwrap();
+        let file = write_bundle_file(&batch).unwrap();
+
+        let bundle = read_bundle_file(&file).unwrap();
+        assert_eq!(bundle.stored_types(), Some(("u32", "u8")));
+        assert_eq!(bundle.memory_footprint(), batch.memory_footprint());
+        assert_eq!(
+            bundle
+                .iter_many_in_range_f64(vec![1, 2], NumericRange::new(0., 5.))
+                .collect::<Vec<_>>(),
+            batch
+                .iter_many_in_range_f64(vec![1, 2], NumericRange::new(0., 5.))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn keeps_wide_integers() {
+        let big = (1u64 << 53) + 1;
+        let batch =
+            Batch::<i64, u64>::new(vec![0, (1 << 60) + 1], vec![big, u64::MAX], &[1]).unwrap();
+        let file = write_bundle_file(&batch).unwrap();
+
+        let bundle = read_bundle_file(&file).unwrap();
+        assert_eq!(bundle.stored_types(), Some(("i64", "u64")));
+        assert_eq!(bundle.integer_column(None), batch.integer_column(None));
+        assert_eq!(
+            bundle.integer_column(Some(1)),
+            Some(vec![big as i128, u64::MAX as i128])
+        );
+    }
+
+    #[test]
+    fn keeps_time_axis() {
+        let epoch = 1_700_000_000_000_000_000;
+        let axis = TimeAxis::new(EpochUnit::Ns, epoch);
+        let batch = Batch::new(vec![epoch, epoch + 1, epoch + 3], vec![1., 2., 3.], &[1])
+            .unwrap()
+            .with_time_axis(axis);
+        let file = write_bundle_file(&batch).unwrap();
+
+        let bundle = read_bundle_file(&file).unwrap();
+        assert_eq!(bundle.time_axis(), Some(axis));
+        assert_eq!(bundle.stored_types(), Some(("i64", "f64")));
+        assert_eq!(
+            bundle
+                .iter_in_range_f64(1, NumericRange::new(0., 1.))
+                .map(|(x, _)| axis.to_timestamp(x) - epoch)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 3]
+        );
+    }
+
+    #[test]
+    fn rejects_bundles_it_cant_restore() {
+        let live = LiveBatch::new(&[1], RetentionPolicy::default());
+        assert!(matches!(write_bundle_file(&live), Err(ChartError::NoData)));
+
+        let ragged = RaggedBatch::new(vec![
+            (1, vec![0., 1.], vec![1., 2.]),
+            (2, vec![0.5], vec![3.]),
+        ])
+        .unwrap();
+        assert!(matches!(
+            write_bundle_file(&ragged),
+            Err(ChartError::BundleFile(BundleFileError::Unsupported(_)))
+        ));
+    }
+}
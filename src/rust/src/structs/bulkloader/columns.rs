@@ -0,0 +1,360 @@
+use num_traits::{NumCast, ToPrimitive};
+
+use crate::{
+    data::TraceHandle,
+    error::Result,
+    trace::{Batch, Bundle, BundleRc, EpochUnit, TimeAxis, ValidityMask, LOD_MIN_POINTS, N},
+};
+
+use super::data_types::{Storage, TypeDescriptor};
+
+/// A numeric type columns are stored as
+trait Native: N + NumCast + Copy + 'static {
+    fn decode(desc: &TypeDescriptor, bytes: &[u8]) -> Self;
+    fn from_f64(value: f64) -> Self;
+
+    /// Converts a value of another primitive type, saturating values out of range
+    fn cast<T: ToPrimitive + Copy>(value: T) -> Self {
+        <Self as NumCast>::from(value)
+            .unwrap_or_else(|| <Self as Native>::from_f64(value.to_f64().unwrap_or(f64::NAN)))
+    }
+}
+
+macro_rules! native {
+    ( int: $($i:ty),+; float: $($f:ty),+ ) => {
+        $(
+            impl Native for $i {
+                fn decode(desc: &TypeDescriptor, bytes: &[u8]) -> Self {
+                    desc.decode_int(bytes) as $i
+                }
+                fn from_f64(value: f64) -> Self {
+                    value as $i
+                }
+            }
+        )+
+        $(
+            impl Native for $f {
+                fn decode(desc: &TypeDescriptor, bytes: &[u8]) -> Self {
+                    desc.decode(bytes) as $f
+                }
+                fn from_f64(value: f64) -> Self {
+                    value as $f
+                }
+            }
+        )+
+    };
+}
+
+native!(int: u8, i8, u16, i16, u32, i32, i64, u64; float: f32, f64);
+
+/// Decoded x values. Only a few types are kept to limit the number of batch types,
+/// the other ones are widened to the closest of them.
+enum XValues {
+    U32(Vec<u32>),
+    I64(Vec<i64>),
+    U64(Vec<u64>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+}
+
+macro_rules! with_x_values {
+    ( $values:expr, $x:ident => $body:expr ) => {
+        match $values {
+            XValues::U32($x) => $body,
+            XValues::I64($x) => $body,
+            XValues::U64($x) => $body,
+            XValues::F32($x) => $body,
+            XValues::F64($x) => $body,
+        }
+    };
+}
+
+pub(super) struct XColumn {
+    values: XValues,
+    /// Set for timestamps, which are kept in their own unit so that they don't lose precision
+    unit: Option<EpochUnit>,
+}
+
+impl XColumn {
+    pub fn with_capacity(desc: &TypeDescriptor, capacity: usize) -> Self {
+        Self::of(desc.storage, desc.unit, capacity)
+    }
+
+    /// A column for values of `storage`, timestamps if `unit` is set
+    pub fn of(storage: Storage, unit: Option<EpochUnit>, capacity: usize) -> Self {
+        let values = match storage {
+            Storage::U8 | Storage::U16 | Storage::U32 => XValues::U32(Vec::with_capacity(capacity)),
+            Storage::I8 | Storage::I16 | Storage::I32 | Storage::I64 => {
+                XValues::I64(Vec::with_capacity(capacity))
+            }
+            Storage::U64 => XValues::U64(Vec::with_capacity(capacity)),
+            Storage::F32 => XValues::F32(Vec::with_capacity(capacity)),
+            Storage::F64 => XValues::F64(Vec::with_capacity(capacity)),
+        };
+
+        Self { values, unit }
+    }
+
+    /// Decodes and appends a value of the type the column was created for
+    pub fn push_raw(&mut self, desc: &TypeDescriptor, bytes: &[u8]) {
+        with_x_values!(&mut self.values, x => x.push(Native::decode(desc, bytes)))
+    }
+
+    /// Appends a value of any primitive type, in the unit of the column
+    pub fn push_cast<T: ToPrimitive + Copy>(&mut self, value: T) {
+        with_x_values!(&mut self.values, x => x.push(Native::cast(value)))
+    }
+
+    pub fn len(&self) -> usize {
+        with_x_values!(&self.values, x => x.len())
+    }
+
+    /// Sorts the values, returning the order the rows of the y columns need to be put in,
+    /// or `None` if the values were sorted already
+    pub fn sort(&mut self) -> Option<Vec<usize>> {
+        with_x_values!(&mut self.values, x => {
+            if x.is_sorted() {
+                return None;
+            }
+
+            let mut order: Vec<usize> = (0..x.len()).collect();
+            order.sort_by(|&a, &b| {
+                x[a].partial_cmp(&x[b])
+                    .unwrap_or_else(|| x[a].as_f64().total_cmp(&x[b].as_f64()))
+            });
+            *x = order.iter().map(|&i| x[i]).collect();
+
+            Some(order)
+        })
+    }
+
+    /// Builds a batch with one column of `y` per handle. Timestamps are exposed
+    /// as seconds since `epoch`, which defaults to the first of them so that they
+    /// stay exact to their unit.
+    pub fn into_bundle(
+        self,
+        y: YColumns,
+        handles: &[TraceHandle],
+        epoch: Option<i64>,
+    ) -> Result<BundleRc> {
+        let first = with_x_values!(&self.values, x => x.first().and_then(|x| x.to_i64()));
+        let time_axis = self
+            .unit
+            .map(|unit| TimeAxis::new(unit, epoch.or(first).unwrap_or(0)));
+
+        with_x_values!(self.values, x => y.into_bundle(x, handles, time_axis))
+    }
+}
+
+/// Decoded y values, one column per trace, kept in the type they were loaded as
+pub(super) struct YColumns {
+    values: YValues,
+    /// Missing samples of columns whose type can't hold a NaN,
+    /// a column gets its mask once one of its samples goes missing
+    missing: Vec<Option<ValidityMask>>,
+}
+
+enum YValues {
+    U8(Vec<Vec<u8>>),
+    I8(Vec<Vec<i8>>),
+    U16(Vec<Vec<u16>>),
+    I16(Vec<Vec<i16>>),
+    U32(Vec<Vec<u32>>),
+    I32(Vec<Vec<i32>>),
+    I64(Vec<Vec<i64>>),
+    U64(Vec<Vec<u64>>),
+    F32(Vec<Vec<f32>>),
+    F64(Vec<Vec<f64>>),
+}
+
+macro_rules! with_y_columns {
+    ( $columns:expr, $y:ident => $body:expr ) => {
+        match $columns {
+            YValues::U8($y) => $body,
+            YValues::I8($y) => $body,
+            YValues::U16($y) => $body,
+            YValues::I16($y) => $body,
+            YValues::U32($y) => $body,
+            YValues::I32($y) => $body,
+            YValues::I64($y) => $body,
+            YValues::U64($y) => $body,
+            YValues::F32($y) => $body,
+            YValues::F64($y) => $body,
+        }
+    };
+}
+
+impl YColumns {
+    pub fn with_capacity(desc: &TypeDescriptor, columns: usize, capacity: usize) -> Self {
+        Self::of(desc.storage, columns, capacity)
+    }
+
+    /// `columns` empty columns for values of `storage`
+    pub fn of(storage: Storage, columns: usize, capacity: usize) -> Self {
+        // cloning an empty vector wouldn't keep its capacity
+        fn empty<T>(columns: usize, capacity: usize) -> Vec<Vec<T>> {
+            (0..columns).map(|_| Vec::with_capacity(capacity)).collect()
+        }
+
+        let values = match storage {
+            Storage::U8 => YValues::U8(empty(columns, capacity)),
+            Storage::I8 => YValues::I8(empty(columns, capacity)),
+            Storage::U16 => YValues::U16(empty(columns, capacity)),
+            Storage::I16 => YValues::I16(empty(columns, capacity)),
+            Storage::U32 => YValues::U32(empty(columns, capacity)),
+            Storage::I32 => YValues::I32(empty(columns, capacity)),
+            Storage::I64 => YValues::I64(empty(columns, capacity)),
+            Storage::U64 => YValues::U64(empty(columns, capacity)),
+            Storage::F32 => YValues::F32(empty(columns, capacity)),
+            Storage::F64 => YValues::F64(empty(columns, capacity)),
+        };
+
+        Self {
+            values,
+            missing: vec![None; columns],
+        }
+    }
+
+    /// Number of columns
+    pub fn len(&self) -> usize {
+        with_y_columns!(&self.values, y => y.len())
+    }
+
+    /// Decodes and appends a value to a column
+    pub fn push_raw(&mut self, column: usize, desc: &TypeDescriptor, bytes: &[u8]) {
+        with_y_columns!(&mut self.values, y => y[column].push(Native::decode(desc, bytes)))
+    }
+
+    /// Appends a value of any primitive type to a column
+    pub fn push_cast<T: ToPrimitive + Copy>(&mut self, column: usize, value: T) {
+        with_y_columns!(&mut self.values, y => y[column].push(Native::cast(value)));
+
+        if let Some(missing) = &mut self.missing[column] {
+            missing.push(true);
+        }
+    }
+
+    /// Appends a missing sample to a column
+    pub fn push_missing(&mut self, column: usize) {
+        let len = with_y_columns!(&mut self.values, y => {
+            y[column].push(Native::from_f64(f64::NAN));
+            y[column].len()
+        });
+
+        let float = matches!(self.values, YValues::F32(_) | YValues::F64(_));
+        if !float {
+            self.missing[column]
+                .get_or_insert_with(|| ValidityMask::new_valid(len - 1))
+                .push(false);
+        }
+    }
+
+    /// Puts the rows of every column in the given order, see [`XColumn::sort`]
+    pub fn reorder(&mut self, order: &[usize]) {
+        with_y_columns!(&mut self.values, y => {
+            for column in y.iter_mut() {
+                *column = order.iter().map(|&i| column[i]).collect();
+            }
+        });
+
+        for missing in self.missing.iter_mut().flatten() {
+            *missing = order.iter().map(|&i| missing.is_valid(i)).collect();
+        }
+    }
+
+    /// Missing samples of all the columns one after another, if there are any
+    fn validity(&self, rows: usize) -> Option<ValidityMask> {
+        if self.missing.iter().all(Option::is_none) {
+            return None;
+        }
+
+        let validity = self.missing.iter().flat_map(|missing| {
+            (0..rows).map(move |i| missing.as_ref().is_none_or(|m| m.is_valid(i)))
+        });
+        Some(validity.collect())
+    }
+
+    fn into_bundle<X: Native>(
+        self,
+        x: Vec<X>,
+        handles: &[TraceHandle],
+        time_axis: Option<TimeAxis>,
+    ) -> Result<BundleRc> {
+        let validity = self.validity(x.len());
+
+        with_y_columns!(self.values, y => batch_bundle(x, y, handles, time_axis, validity))
+    }
+}
+
+fn batch_bundle<X: Native, Y: Native>(
+    x: Vec<X>,
+    y: Vec<Vec<Y>>,
+    handles: &[TraceHandle],
+    time_axis: Option<TimeAxis>,
+    validity: Option<ValidityMask>,
+) -> Result<BundleRc> {
+    let mut batch = Batch::from_columns(x, y, handles)?;
+    if let Some(time_axis) = time_axis {
+        batch = batch.with_time_axis(time_axis);
+    }
+    if let Some(validity) = validity {
+        batch = batch.with_validity(validity)?;
+    }
+
+    // the level-of-detail pyramid isn't worth building for small batches
+    if batch.point_count() >= LOD_MIN_POINTS {
+        batch = batch.with_lod();
+    }
+
+    Ok(BundleRc::new(batch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{XColumn, YColumns};
+    use crate::{
+        structs::bulkloader::data_types::type_desc,
+        trace::{Batch, Bundle, InterpolationStrategy},
+    };
+
+    #[test]
+    fn keeps_loaded_types() {
+        let (x_desc, y_desc) = (type_desc("u16").unwrap(), type_desc("u8").unwrap());
+        let mut x = XColumn::with_capacity(&x_desc, 3);
+        let mut y = YColumns::with_capacity(&y_desc, 2, 3);
+
+        for i in 0..3u8 {
+            x.push_raw(&x_desc, &(i as u16 * 300).to_le_bytes());
+            y.push_raw(0, &y_desc, &[i]);
+            y.push_raw(1, &y_desc, &[255 - i]);
+        }
+        let bundle = x.into_bundle(y, &[1, 2], None).unwrap();
+
+        let typed = Batch::<u32, u8>::new(vec![0, 300, 600], vec![0, 1, 2, 255, 254, 253], &[1, 2])
+            .unwrap();
+        assert_eq!(bundle.memory_footprint(), typed.memory_footprint());
+        assert_eq!(
+            bundle.value_at(2, 600., InterpolationStrategy::None),
+            Some((600., 253.))
+        );
+    }
+
+    #[test]
+    fn keeps_u64_above_i64_range() {
+        let desc = type_desc("u64").unwrap();
+        let mut x = XColumn::with_capacity(&desc, 2);
+        let mut y = YColumns::with_capacity(&desc, 1, 2);
+
+        for value in [1u64 << 63, u64::MAX] {
+            x.push_raw(&desc, &value.to_le_bytes());
+            y.push_raw(0, &desc, &value.to_le_bytes());
+        }
+        let bundle = x.into_bundle(y, &[1], None).unwrap();
+
+        assert_eq!(
+            bundle.value_at(1, u64::MAX as f64, InterpolationStrategy::None),
+            Some((u64::MAX as f64, u64::MAX as f64))
+        );
+        assert!(bundle.range().bounds().0 > 0.);
+    }
+}
@@ -0,0 +1,559 @@
+// https://github.com/madonoharu/tsify/issues/42
+#![allow(non_snake_case)]
+
+//! Parsing of delimited text files.
+//!
+//! The delimiter and the presence of a header row are detected from the first rows
+//! unless given explicitly. Column types are inferred from a sample of the data rows:
+//! columns of numbers become traces, while text columns are left out. The x column
+//! may also contain ISO-8601 timestamps, which are kept as exact nanoseconds and exposed
+//! as seconds since the first of them.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    data::TraceHandle,
+    error::ChartError,
+    trace::{BundleRc, EpochUnit},
+    utils::calendar::{parse_iso8601, parse_iso8601_nanos},
+};
+
+use super::{
+    columns::{XColumn, YColumns},
+    data_types::Storage,
+    rows_into_bundle,
+};
+
+const DELIMITERS: [u8; 4] = [b',', b'\t', b';', b'|'];
+
+/// Number of lines looked at when detecting the delimiter
+const DELIMITER_SAMPLE_LINES: usize = 20;
+
+/// Number of rows looked at when inferring column types
+const SAMPLE_ROWS: usize = 1000;
+
+const MISSING: [&str; 6] = ["", "nan", "na", "n/a", "null", "none"];
+
+#[derive(Tsify, Serialize, Deserialize, Clone, Default, Debug)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvOptions {
+    /// Field delimiter, detected if not given.
+    #[tsify(optional)]
+    pub delimiter: Option<char>,
+    /// Whether the first row holds column names, detected if not given.
+    #[tsify(optional)]
+    pub header: Option<bool>,
+    /// Name of the x column, the first column is used if not given.
+    /// Columns of files without a header are named by their index.
+    #[tsify(optional)]
+    pub x_column: Option<String>,
+    /// Unit of numeric x values holding epoch timestamps. Integer timestamps are kept
+    /// exact and exposed as seconds since the first of them, fractional ones are
+    /// converted to seconds since the Unix epoch.
+    #[tsify(optional)]
+    pub epoch_unit: Option<EpochUnit>,
+}
+
+#[derive(Tsify, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvRowError {
+    /// One-based line number where the row starts.
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub enum CsvError {
+    MissingColumn(String),
+    TextXColumn(String),
+    NoValueColumns,
+    /// Fields can only be split by a single byte
+    NonAsciiDelimiter(char),
+    /// The trace handle assigned to the column isn't a `u32`
+    InvalidHandle(String),
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsvError::MissingColumn(name) => write!(f, "there is no column named {name}"),
+            CsvError::TextXColumn(name) => {
+                write!(f, "x column {name} contains neither numbers nor timestamps")
+            }
+            CsvError::NoValueColumns => write!(f, "the file contains no numeric columns"),
+            CsvError::InvalidHandle(name) => {
+                write!(
+                    f,
+                    "the handle of column {name} is not an unsigned 32-bit integer"
+                )
+            }
+            CsvError::NonAsciiDelimiter(d) => {
+                write!(f, "delimiter {d:?} is not an ASCII character")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ColumnKind {
+    Integer,
+    Float,
+    Timestamp,
+    Text,
+}
+
+impl ColumnKind {
+    fn of(field: &str) -> Option<ColumnKind> {
+        if is_missing(field) {
+            None
+        } else if field.parse::<i64>().is_ok() {
+            Some(ColumnKind::Integer)
+        } else if field.parse::<f64>().is_ok() {
+            Some(ColumnKind::Float)
+        } else if parse_iso8601(field).is_some() {
+            Some(ColumnKind::Timestamp)
+        } else {
+            Some(ColumnKind::Text)
+        }
+    }
+}
+
+/// Tally of the kinds of values seen in a column.
+/// The prevailing kind wins, so that a few malformed values get reported as such.
+#[derive(Clone, Copy, Default)]
+struct KindCounts {
+    integer: usize,
+    float: usize,
+    timestamp: usize,
+    text: usize,
+}
+
+impl KindCounts {
+    fn add(&mut self, field: &str) {
+        match ColumnKind::of(field) {
+            Some(ColumnKind::Integer) => self.integer += 1,
+            Some(ColumnKind::Float) => self.float += 1,
+            Some(ColumnKind::Timestamp) => self.timestamp += 1,
+            Some(ColumnKind::Text) => self.text += 1,
+            None => {}
+        }
+    }
+
+    /// The prevailing kind, `None` if all the values are missing
+    fn kind(&self) -> Option<ColumnKind> {
+        let numeric = self.integer + self.float;
+        if numeric + self.timestamp + self.text == 0 {
+            None
+        } else if numeric >= self.timestamp && numeric >= self.text {
+            Some(if self.float > 0 {
+                ColumnKind::Float
+            } else {
+                ColumnKind::Integer
+            })
+        } else if self.timestamp >= self.text {
+            Some(ColumnKind::Timestamp)
+        } else {
+            Some(ColumnKind::Text)
+        }
+    }
+}
+
+fn is_missing(field: &str) -> bool {
+    MISSING.iter().any(|m| field.eq_ignore_ascii_case(m))
+}
+
+/// A row of fields along with the line it starts at
+struct Record {
+    line: usize,
+    fields: Vec<String>,
+}
+
+/// Splits text into records, honoring double-quoted fields which may contain
+/// delimiters, line breaks and doubled quotes. Blank lines are skipped.
+fn records(text: &str, delimiter: u8) -> impl Iterator<Item = Result<Record, CsvRowError>> + '_ {
+    let bytes = text.as_bytes();
+    let mut pos = 0;
+    let mut line = 1;
+
+    std::iter::from_fn(move || loop {
+        if pos >= bytes.len() {
+            return None;
+        }
+
+        let start_line = line;
+        let mut fields = Vec::new();
+        let mut field = Vec::new();
+        let mut quoted = false;
+        let mut was_quoted = false;
+
+        loop {
+            let Some(&b) = bytes.get(pos) else {
+                if quoted {
+                    return Some(Err(CsvRowError {
+                        line: start_line,
+                        message: "unterminated quoted field".into(),
+                    }));
+                }
+                break;
+            };
+            pos += 1;
+
+            match b {
+                b'"' if quoted => {
+                    if bytes.get(pos) == Some(&b'"') {
+                        field.push(b'"');
+                        pos += 1;
+                    } else {
+                        quoted = false;
+                    }
+                }
+                b'"' if field.iter().all(u8::is_ascii_whitespace) => {
+                    field.clear();
+                    quoted = true;
+                    was_quoted = true;
+                }
+                b'\n' => {
+                    line += 1;
+                    if !quoted {
+                        break;
+                    }
+                    field.push(b);
+                }
+                b'\r' if !quoted => {}
+                b if b == delimiter && !quoted => {
+                    fields.push(finish_field(&field, was_quoted));
+                    field.clear();
+                    was_quoted = false;
+                }
+                _ => field.push(b),
+            }
+        }
+
+        if fields.is_empty() && field.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        fields.push(finish_field(&field, was_quoted));
+
+        return Some(Ok(Record {
+            line: start_line,
+            fields,
+        }));
+    })
+}
+
+fn finish_field(field: &[u8], quoted: bool) -> String {
+    let field = String::from_utf8_lossy(field);
+    if quoted {
+        field.into_owned()
+    } else {
+        field.trim().to_string()
+    }
+}
+
+/// Picks the delimiter splitting the first lines into the same, largest number of fields
+fn detect_delimiter(text: &str) -> u8 {
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .take(DELIMITER_SAMPLE_LINES)
+        .collect();
+
+    DELIMITERS
+        .into_iter()
+        .map(|d| {
+            let counts: Vec<usize> = lines
+                .iter()
+                .map(|l| l.bytes().filter(|&b| b == d).count())
+                .collect();
+            let consistent = counts.windows(2).all(|w| w[0] == w[1]);
+
+            (d, consistent, counts.first().copied().unwrap_or(0))
+        })
+        .filter(|&(_, _, count)| count > 0)
+        .max_by_key(|&(_, consistent, count)| (consistent, count))
+        .map_or(b',', |(d, _, _)| d)
+}
+
+/// The first row is a header if it has a non-value where the next row has a value
+fn detect_header(first: &Record, second: Option<&Record>) -> bool {
+    let is_text = |f: &String| ColumnKind::of(f) == Some(ColumnKind::Text);
+
+    match second {
+        Some(second) => first
+            .fields
+            .iter()
+            .zip(&second.fields)
+            .any(|(a, b)| is_text(a) && !is_text(b)),
+        None => first.fields.iter().any(is_text),
+    }
+}
+
+/// Result of parsing a delimited text file.
+#[wasm_bindgen]
+pub struct CsvImport {
+    bundle: BundleRc,
+    names: Vec<String>,
+    handles: Vec<TraceHandle>,
+    errors: Vec<CsvRowError>,
+}
+
+#[wasm_bindgen]
+impl CsvImport {
+    pub fn bundle(&self) -> BundleRc {
+        self.bundle.clone()
+    }
+
+    /// Names of the loaded y columns
+    pub fn names(&self) -> Vec<String> {
+        self.names.clone()
+    }
+
+    /// Trace handles of the loaded y columns, in the same order as `names`
+    pub fn handles(&self) -> Vec<TraceHandle> {
+        self.handles.clone()
+    }
+
+    /// Rows which couldn't be parsed and were left out
+    pub fn errors(&self) -> Result<Vec<JsValue>, serde_wasm_bindgen::Error> {
+        self.errors
+            .iter()
+            .map(serde_wasm_bindgen::to_value)
+            .collect()
+    }
+}
+
+/// Parses a delimited text file, `handle_for` assigns a trace handle to each column name.
+pub fn read_csv(
+    text: &str,
+    options: &CsvOptions,
+    mut handle_for: impl FnMut(&str) -> TraceHandle,
+) -> Result<CsvImport, ChartError> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let delimiter = match options.delimiter {
+        Some(d) if d.is_ascii() => d as u8,
+        Some(d) => return Err(CsvError::NonAsciiDelimiter(d).into()),
+        None => detect_delimiter(text),
+    };
+
+    let mut errors = Vec::new();
+    let mut rows = records(text, delimiter);
+    let mut next_row = |errors: &mut Vec<CsvRowError>| {
+        rows.by_ref()
+            .find_map(|r| r.map_err(|e| errors.push(e)).ok())
+    };
+
+    let first = next_row(&mut errors).ok_or(ChartError::NoData)?;
+    let second = next_row(&mut errors);
+
+    let header = options
+        .header
+        .unwrap_or_else(|| detect_header(&first, second.as_ref()));
+
+    // only the rows the column types are inferred from are held at once,
+    // the rest are decoded into the columns as they are read
+    let (names, mut sample): (Vec<String>, Vec<Record>) = if header {
+        (first.fields, second.into_iter().collect())
+    } else {
+        (
+            (0..first.fields.len()).map(|i| i.to_string()).collect(),
+            [first].into_iter().chain(second).collect(),
+        )
+    };
+    while sample.len() < SAMPLE_ROWS {
+        match next_row(&mut errors) {
+            Some(record) => sample.push(record),
+            None => break,
+        }
+    }
+
+    let mut counts = vec![KindCounts::default(); names.len()];
+    for record in &sample {
+        for (count, field) in counts.iter_mut().zip(&record.fields) {
+            count.add(field);
+        }
+    }
+    let kinds: Vec<Option<ColumnKind>> = counts.iter().map(KindCounts::kind).collect();
+
+    let x_idx = match &options.x_column {
+        Some(name) => names
+            .iter()
+            .position(|n| n == name)
+            .ok_or_else(|| CsvError::MissingColumn(name.clone()))?,
+        None => 0,
+    };
+    let x_kind = kinds[x_idx].unwrap_or(ColumnKind::Text);
+    if x_kind == ColumnKind::Text {
+        return Err(CsvError::TextXColumn(names[x_idx].clone()).into());
+    }
+
+    let y_indices: Vec<usize> = (0..names.len())
+        .filter(|&i| i != x_idx)
+        .filter(|&i| {
+            matches!(
+                kinds[i],
+                None | Some(ColumnKind::Integer | ColumnKind::Float)
+            )
+        })
+        .collect();
+    if y_indices.is_empty() {
+        return Err(CsvError::NoValueColumns.into());
+    }
+
+    // integers and timestamps are kept as they are, rather than rounded to the nearest `f64`
+    let x_unit = match x_kind {
+        ColumnKind::Timestamp => Some(EpochUnit::Ns),
+        ColumnKind::Integer => options.epoch_unit,
+        _ => None,
+    };
+    let epoch_scale = options.epoch_unit.map_or(1., EpochUnit::seconds);
+
+    let mut x = match x_kind {
+        ColumnKind::Float => XColumn::of(Storage::F64, None, sample.len()),
+        _ => XColumn::of(Storage::I64, x_unit, sample.len()),
+    };
+    let mut ys = YColumns::of(Storage::F64, y_indices.len(), sample.len());
+
+    let mut push_x = |field: &str| match x_kind {
+        ColumnKind::Integer => field.parse::<i64>().ok().map(|v| x.push_cast(v)),
+        ColumnKind::Timestamp => parse_iso8601_nanos(field).map(|v| x.push_cast(v)),
+        _ => field
+            .parse::<f64>()
+            .ok()
+            .map(|v| x.push_cast(v * epoch_scale)),
+    };
+
+    let mut row_ys = Vec::with_capacity(y_indices.len());
+    let mut sample = sample.into_iter();
+
+    'rows: while let Some(record) = sample.next().or_else(|| next_row(&mut errors)) {
+        let malformed = |message: String| CsvRowError {
+            line: record.line,
+            message,
+        };
+
+        if record.fields.len() != names.len() {
+            errors.push(malformed(format!(
+                "expected {} fields, found {}",
+                names.len(),
+                record.fields.len()
+            )));
+            continue;
+        }
+
+        row_ys.clear();
+        for &i in &y_indices {
+            let field = &record.fields[i];
+            match field.parse::<f64>() {
+                Ok(y) => row_ys.push(Some(y)),
+                Err(_) if is_missing(field) => row_ys.push(None),
+                Err(_) => {
+                    errors.push(malformed(format!(
+                        "invalid value \"{field}\" in column {}",
+                        names[i]
+                    )));
+                    continue 'rows;
+                }
+            }
+        }
+
+        if push_x(&record.fields[x_idx]).is_none() {
+            errors.push(malformed(format!(
+                "invalid x value \"{}\"",
+                record.fields[x_idx]
+            )));
+            continue;
+        }
+        for (column, &y) in row_ys.iter().enumerate() {
+            match y {
+                Some(y) => ys.push_cast(column, y),
+                None => ys.push_missing(column),
+            }
+        }
+    }
+
+    let names: Vec<String> = y_indices.iter().map(|&i| names[i].clone()).collect();
+    let handles: Vec<TraceHandle> = names.iter().map(|n| handle_for(n)).collect();
+
+    let bundle = rows_into_bundle(x, ys, &handles)?;
+    errors.sort_by_key(|e| e.line);
+
+    Ok(CsvImport {
+        bundle,
+        names,
+        handles,
+        errors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_csv, CsvOptions};
+    use crate::types::NumericRange;
+
+    #[test]
+    fn infers_layout_and_reports_bad_rows() {
+        let text = "time;host;cpu;mem\n\
+                    2024-01-01T00:00:10Z;a;1.5;10\n\
+                    2024-01-01T00:00:00Z;b;0.5;\n\
+                    \n\
+                    2024-01-01T00:00:20Z;c;oops;30\n\
+                    2024-01-01T00:00:30Z;d;2.5\n\
+                    2024-01-01T01:00:30+01:00;\"e;f\";3.5;40\n";
+
+        let mut next_handle = 10;
+        let import = read_csv(text, &CsvOptions::default(), |_| {
+            next_handle += 1;
+            next_handle
+        })
+        .unwrap();
+
+        assert_eq!(import.names, vec!["cpu", "mem"]);
+        assert_eq!(import.handles, vec![11, 12]);
+        assert_eq!(
+            import.errors.iter().map(|e| e.line).collect::<Vec<_>>(),
+            vec![5, 6]
+        );
+
+        assert_eq!(import.bundle.epoch(), Some(1_704_067_200_000_000_000));
+        let rows: Vec<_> = import
+            .bundle
+            .iter_many_in_range_f64(vec![11, 12], NumericRange::new(0., 60.))
+            .collect();
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0][..2], [0., 0.5]);
+        assert!(rows[0][2].is_nan());
+        assert_eq!(rows[1], vec![10., 1.5, 10.]);
+        assert_eq!(rows[2], vec![30., 3.5, 40.]);
+    }
+
+    #[test]
+    fn reads_headerless_tsv() {
+        let text = "1\t5\t6\n2\t7\t8\n";
+        let import = read_csv(text, &CsvOptions::default(), |name| name.parse().unwrap()).unwrap();
+
+        assert_eq!(import.handles, vec![1, 2]);
+        assert_eq!(import.bundle.point_count(), 2);
+
+        let options = CsvOptions {
+            delimiter: Some('§'),
+            ..Default::default()
+        };
+        assert!(read_csv(text, &options, |_| 1).is_err());
+    }
+
+    #[test]
+    fn decodes_rows_past_the_sample() {
+        let text: String = (0..2500).map(|i| format!("{i},{}\n", i * 2)).collect();
+        let import = read_csv(&text, &CsvOptions::default(), |_| 1).unwrap();
+
+        assert_eq!(import.bundle.point_count(), 2500);
+        assert!(import.errors.is_empty());
+    }
+}
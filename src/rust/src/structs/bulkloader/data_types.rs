@@ -1,20 +1,113 @@
 use std::collections::HashMap;
 
+use half::{bf16, f16};
 use lazy_static::lazy_static;
 
+use crate::{error::ChartError, trace::EpochUnit};
+
+/// Numeric type decoded values are stored as
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Storage {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    I64,
+    U64,
+    F32,
+    F64,
+}
+
+impl Storage {
+    /// A type holding the values of all the given types, keeping it if they are the same
+    #[cfg(feature = "arrow")]
+    pub fn common(storages: impl IntoIterator<Item = Storage>) -> Storage {
+        let mut storages = storages.into_iter();
+        let Some(first) = storages.next() else {
+            return Storage::F64;
+        };
+
+        storages.fold(first, |common, storage| match (common, storage) {
+            (a, b) if a == b => a,
+            (Storage::U64 | Storage::F32 | Storage::F64, _)
+            | (_, Storage::U64 | Storage::F32 | Storage::F64) => Storage::F64,
+            _ => Storage::I64,
+        })
+    }
+}
+
+#[derive(Clone)]
 pub struct TypeDescriptor {
-    #[allow(unused)]
-    pub name: String,
     pub size: usize,
     pub parser: fn(&[u8]) -> f64,
+    /// Parses integer types without going through `f64`, which can't hold every `i64`.
+    /// Widened to `i128` so that `u64` values above `i64::MAX` don't wrap around.
+    pub int_parser: Option<fn(&[u8]) -> i128>,
+    /// Factor the parsed values get multiplied by
+    pub scale: f64,
+    /// Set for timestamps, which `parser` converts to seconds
+    pub unit: Option<EpochUnit>,
+    /// The narrowest type holding every decoded value
+    pub storage: Storage,
+    float: bool,
 }
 
 impl TypeDescriptor {
-    pub fn new(name: impl Into<String>, size: usize, parser: fn(&[u8]) -> f64) -> Self {
+    pub fn new(size: usize, parser: fn(&[u8]) -> f64) -> Self {
         Self {
-            name: name.into(),
             size,
             parser,
+            int_parser: None,
+            scale: 1.,
+            unit: None,
+            storage: Storage::F64,
+            float: false,
+        }
+    }
+
+    fn with_int(mut self, int_parser: fn(&[u8]) -> i128) -> Self {
+        self.int_parser = Some(int_parser);
+        self
+    }
+
+    fn with_storage(mut self, storage: Storage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    fn with_unit(mut self, unit: EpochUnit) -> Self {
+        self.unit = Some(unit);
+        self
+    }
+
+    /// Marks the type as having a fractional part
+    fn with_float(mut self) -> Self {
+        self.float = true;
+        self
+    }
+
+    /// Whether values of this type can have a fractional part
+    pub fn is_float(&self) -> bool {
+        self.float
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> f64 {
+        let value = (self.parser)(bytes);
+
+        if self.scale == 1. {
+            value
+        } else {
+            value * self.scale
+        }
+    }
+
+    /// Decodes the value as an integer, exactly unless it is scaled or not an integer type
+    pub fn decode_int(&self, bytes: &[u8]) -> i128 {
+        match self.int_parser {
+            Some(int_parser) if self.scale == 1. => int_parser(bytes),
+            _ => self.decode(bytes) as i128,
         }
     }
 }
@@ -23,6 +116,9 @@ macro_rules! type_map {
     ( "DateTime" ) => {
         u32
     };
+    ( "i8" ) => {
+        i8
+    };
     ( "i16" ) => {
         i16
     };
@@ -52,42 +148,196 @@ macro_rules! type_map {
     };
 }
 
+/// Registers the little-endian type under its name and the big-endian one with a `_be` suffix
 macro_rules! type_desc {
-    ( $m:expr, $s:expr, $t:ty ) => {
+    ( $m:expr, $s:expr, $t:ty, $storage:ident $(.$with:ident($($arg:expr)?))* ) => {
         $m.insert(
             $s,
-            TypeDescriptor::new($s, std::mem::size_of::<$t>(), |a| <$t>::from_le_bytes(a.try_into().unwrap()) as f64),
-        )
+            TypeDescriptor::new(std::mem::size_of::<$t>(), |a| <$t>::from_le_bytes(a.try_into().unwrap()) as f64)
+                .with_int(|a| <$t>::from_le_bytes(a.try_into().unwrap()) as i128)
+                .with_storage(Storage::$storage)
+                $(.$with($($arg)?))*,
+        );
+        $m.insert(
+            concat!($s, "_be"),
+            TypeDescriptor::new(std::mem::size_of::<$t>(), |a| <$t>::from_be_bytes(a.try_into().unwrap()) as f64)
+                .with_int(|a| <$t>::from_be_bytes(a.try_into().unwrap()) as i128)
+                .with_storage(Storage::$storage)
+                $(.$with($($arg)?))*,
+        );
     };
-    ( $m: expr, [ $($s:expr, $t:ty),+ ] ) => {
+    ( $m: expr, [ $($s:expr, $t:ty, $storage:ident $(.$with:ident($($arg:expr)?))*),+ ] ) => {
         $(
-            type_desc!($m, $s, $t);
+            type_desc!($m, $s, $t, $storage $(.$with($($arg)?))*);
         )+
     };
     ( $m:expr ) => {
         type_desc!($m, [
-            "DateTime", type_map!("DateTime"),
-            "i16", type_map!("i16"),
-            "i32", type_map!("i32"),
-            "i64", type_map!("i64"),
-
-            "u8", type_map!("u8"),
-            "u16", type_map!("u16"),
-            "u32", type_map!("u32"),
-            "u64", type_map!("u64"),
-
-            "f32", type_map!("f32"),
-            "f64", type_map!("f64")
+            "DateTime", type_map!("DateTime"), U32.with_unit(EpochUnit::S),
+            "i8", type_map!("i8"), I8,
+            "i16", type_map!("i16"), I16,
+            "i32", type_map!("i32"), I32,
+            "i64", type_map!("i64"), I64,
+
+            "u8", type_map!("u8"), U8,
+            "u16", type_map!("u16"), U16,
+            "u32", type_map!("u32"), U32,
+            "u64", type_map!("u64"), U64,
+
+            "f32", type_map!("f32"), F32.with_float(),
+            "f64", type_map!("f64"), F64.with_float()
         ])
     };
 }
 
+/// Registers an `i64` epoch timestamp in the given unit. Parsed as a float it is converted
+/// to seconds, while the integer parser keeps it in its unit.
+macro_rules! datetime_desc {
+    ( $m:expr, $s:expr, $unit:expr, $per_second:expr ) => {
+        $m.insert(
+            $s,
+            TypeDescriptor::new(8, |a| {
+                i64::from_le_bytes(a.try_into().unwrap()) as f64 / $per_second
+            })
+            .with_int(|a| i64::from_le_bytes(a.try_into().unwrap()) as i128)
+            .with_storage(Storage::I64)
+            .with_unit($unit),
+        );
+        $m.insert(
+            concat!($s, "_be"),
+            TypeDescriptor::new(8, |a| {
+                i64::from_be_bytes(a.try_into().unwrap()) as f64 / $per_second
+            })
+            .with_int(|a| i64::from_be_bytes(a.try_into().unwrap()) as i128)
+            .with_storage(Storage::I64)
+            .with_unit($unit),
+        );
+    };
+}
+
 lazy_static! {
     pub static ref TYPE_SIZES: HashMap<&'static str, TypeDescriptor> = {
         let mut m = HashMap::new();
 
         type_desc!(m);
 
+        m.insert(
+            "bool",
+            TypeDescriptor::new(1, |a| (a[0] != 0) as u8 as f64)
+                .with_int(|a| (a[0] != 0) as i128)
+                .with_storage(Storage::U8),
+        );
+
+        m.insert(
+            "f16",
+            TypeDescriptor::new(2, |a| f16::from_le_bytes([a[0], a[1]]).to_f64())
+                .with_storage(Storage::F32)
+                .with_float(),
+        );
+        m.insert(
+            "f16_be",
+            TypeDescriptor::new(2, |a| f16::from_be_bytes([a[0], a[1]]).to_f64())
+                .with_storage(Storage::F32)
+                .with_float(),
+        );
+        m.insert(
+            "bf16",
+            TypeDescriptor::new(2, |a| bf16::from_le_bytes([a[0], a[1]]).to_f64())
+                .with_storage(Storage::F32)
+                .with_float(),
+        );
+        m.insert(
+            "bf16_be",
+            TypeDescriptor::new(2, |a| bf16::from_be_bytes([a[0], a[1]]).to_f64())
+                .with_storage(Storage::F32)
+                .with_float(),
+        );
+
+        datetime_desc!(m, "DateTimeMs", EpochUnit::Ms, 1e3);
+        datetime_desc!(m, "DateTimeUs", EpochUnit::Us, 1e6);
+        datetime_desc!(m, "DateTimeNs", EpochUnit::Ns, 1e9);
+
         m
     };
 }
+
+/// Looks up the descriptor of a data type by its name.
+///
+/// Besides the registered types, this accepts fixed-point decimals written as
+/// `scaled(<integer type>, <factor>)`, e.g. `scaled(i32_be, 0.001)`, whose values
+/// are the stored integers multiplied by the factor.
+pub fn type_desc(name: &str) -> Result<TypeDescriptor, ChartError> {
+    let unknown = || ChartError::UnknownType(name.to_string());
+
+    if let Some(desc) = TYPE_SIZES.get(name) {
+        return Ok(desc.clone());
+    }
+
+    let (base, factor) = name
+        .strip_prefix("scaled(")
+        .and_then(|args| args.strip_suffix(')'))
+        .and_then(|args| args.split_once(','))
+        .ok_or_else(unknown)?;
+
+    let base = TYPE_SIZES
+        .get(base.trim())
+        .filter(|base| !base.is_float() && base.unit.is_none())
+        .ok_or_else(unknown)?;
+    let factor: f64 = factor
+        .trim()
+        .parse()
+        .ok()
+        .filter(|f: &f64| f.is_finite())
+        .ok_or_else(unknown)?;
+
+    let float = factor.fract() != 0.;
+    Ok(TypeDescriptor {
+        scale: factor,
+        storage: if float { Storage::F64 } else { Storage::I64 },
+        float,
+        ..base.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::type_desc;
+    use crate::trace::EpochUnit;
+
+    #[test]
+    fn decodes_extended_types() {
+        let decode = |name: &str, bytes: &[u8]| type_desc(name).unwrap().decode(bytes);
+
+        assert_eq!(decode("i8", &[0xff]), -1.);
+        assert_eq!(decode("u16_be", &[1, 2]), 258.);
+        assert_eq!(decode("bool", &[7]), 1.);
+        assert_eq!(decode("f16", &[0x00, 0x3c]), 1.);
+        assert_eq!(decode("bf16_be", &[0x3f, 0x80]), 1.);
+        assert_eq!(decode("DateTimeMs", &1500i64.to_le_bytes()), 1.5);
+        assert_eq!(
+            decode("DateTimeNs_be", &(-2_000_000_000i64).to_be_bytes()),
+            -2.
+        );
+        assert_eq!(
+            decode("scaled(i16_be, 0.25)", &(-1234i16).to_be_bytes()),
+            -308.5
+        );
+
+        let ns = type_desc("DateTimeNs").unwrap();
+        let timestamp = 1_700_000_000_123_456_789i64;
+        assert_eq!(ns.decode_int(&timestamp.to_le_bytes()), timestamp as i128);
+        assert_eq!(
+            type_desc("u64")
+                .unwrap()
+                .decode_int(&u64::MAX.to_le_bytes()),
+            u64::MAX as i128
+        );
+
+        assert!(type_desc("f64_be").unwrap().is_float());
+        assert!(!type_desc("DateTimeUs").unwrap().is_float());
+        assert_eq!(type_desc("DateTime_be").unwrap().unit, Some(EpochUnit::S));
+
+        assert!(type_desc("scaled(f32, 2)").is_err());
+        assert!(type_desc("i128").is_err());
+    }
+}
@@ -1,76 +1,124 @@
+#[cfg(feature = "arrow")]
+mod arrow;
+mod bundle_file;
+mod columns;
+mod csv;
 mod data_types;
+#[cfg(feature = "parquet")]
+mod parquet;
+mod row_decoder;
+
+#[cfg(feature = "arrow")]
+pub use arrow::*;
+pub use bundle_file::*;
+pub use csv::*;
+#[cfg(feature = "parquet")]
+pub use parquet::*;
 
 use std::collections::HashMap;
 
 use js_sys::{Float64Array, Uint8Array};
-use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+use wasm_bindgen::{prelude::*, JsCast};
+use web_sys::AbortSignal;
 
 use crate::{
     data::TraceHandle,
-    structs::bulkloader::data_types::TYPE_SIZES,
-    trace::{Batch, BundleRc, ConstantBatch},
+    error::{ChartError, Result},
+    trace::{BundleRc, ConstantBatch, RaggedBatch, TimeAxis},
 };
 
-use self::data_types::TypeDescriptor;
+use self::{
+    columns::{XColumn, YColumns},
+    data_types::{type_desc, Storage, TypeDescriptor},
+    row_decoder::RowDecoder,
+};
 
 #[wasm_bindgen]
 pub struct Bulkloader {
     handles: Vec<TraceHandle>,
-    x_desc: &'static TypeDescriptor,
-    y_desc: &'static TypeDescriptor,
-    data: Vec<u8>,
+    decoder: RowDecoder,
 }
 
+/// Size of the pieces `from_array` copies out of the JS array at once
+const ARRAY_CHUNK_BYTES: usize = 1 << 16;
+
 #[wasm_bindgen]
 impl Bulkloader {
+    /// ### Loads rows from a stream, decoding them as the chunks arrive
+    /// * `on_progress` is called after every chunk with the number of bytes and rows read so far
+    /// * aborting `signal` cancels the stream and rejects with the signal's reason
     pub async fn from_stream(
         handles: Vec<TraceHandle>,
         x_type: String,
         y_type: String,
         stream: wasm_streams::readable::sys::ReadableStream,
-    ) -> Result<Bulkloader, JsValue> {
-        use js_sys::Uint8Array;
-
-        const ALLOC_ROWS: usize = 1440;
-
-        let x_desc = TYPE_SIZES.get(x_type.as_str()).unwrap();
-        let y_desc = TYPE_SIZES.get(y_type.as_str()).unwrap();
-        let row_len_bytes = x_desc.size + y_desc.size * handles.len();
-        let default_alloc = ALLOC_ROWS * row_len_bytes;
+        on_progress: Option<js_sys::Function>,
+        signal: Option<AbortSignal>,
+    ) -> Result<Bulkloader> {
+        let x_desc = type_desc(&x_type)?;
+        let y_desc = type_desc(&y_type)?;
+
+        if let Some(signal) = signal.as_ref().filter(|s| s.aborted()) {
+            return Err(abort_reason(signal).into());
+        }
 
-        let mut buffer: Vec<u8> = Vec::with_capacity(default_alloc);
+        let mut decoder = RowDecoder::new(x_desc, y_desc, handles.len());
 
         let mut stream = wasm_streams::ReadableStream::from_raw(stream);
-        let mut reader = stream.try_get_reader()?;
-
-        let mut cursor = 0;
+        let mut reader = stream.try_get_reader().map_err(JsValue::from)?;
+
+        // a pending read only settles once the stream gets cancelled
+        let on_abort = match &signal {
+            Some(signal) => {
+                let raw_reader = reader.as_raw().clone();
+                let abort_signal = signal.clone();
+                let on_abort = Closure::<dyn FnMut()>::new(move || {
+                    let _ = raw_reader.cancel_with_reason(&abort_reason(&abort_signal));
+                });
+                signal
+                    .add_event_listener_with_callback("abort", on_abort.as_ref().unchecked_ref())?;
+
+                Some(on_abort)
+            }
+            None => None,
+        };
 
-        while let Some(row) = reader.read().await? {
-            let row = Uint8Array::from(row);
+        let result: Result<(), JsValue> = async {
+            let mut buffer = Vec::new();
 
-            unsafe {
-                let next_len = cursor + row.byte_length() as usize;
+            while let Some(chunk) = reader.read().await? {
+                if let Some(signal) = signal.as_ref().filter(|s| s.aborted()) {
+                    return Err(abort_reason(signal));
+                }
 
-                if next_len > buffer.capacity() {
-                    let delta = next_len - buffer.capacity();
-                    buffer.reserve(
-                        (delta / default_alloc + (delta % default_alloc).min(1)) * default_alloc,
-                    );
+                let chunk = Uint8Array::from(chunk);
+                buffer.resize(chunk.length() as usize, 0);
+                chunk.copy_to(&mut buffer);
+                decoder.push(&buffer);
+
+                if let Some(on_progress) = &on_progress {
+                    on_progress.call2(
+                        &JsValue::NULL,
+                        &JsValue::from(decoder.bytes() as f64),
+                        &JsValue::from(decoder.rows() as f64),
+                    )?;
                 }
+            }
 
-                buffer.set_len(next_len);
+            match signal.as_ref().filter(|s| s.aborted()) {
+                Some(signal) => Err(abort_reason(signal)),
+                None => Ok(()),
             }
+        }
+        .await;
 
-            row.copy_to(&mut buffer[cursor..(cursor + row.byte_length() as usize)]);
-            cursor += row.byte_length() as usize;
+        if let (Some(signal), Some(on_abort)) = (&signal, &on_abort) {
+            signal
+                .remove_event_listener_with_callback("abort", on_abort.as_ref().unchecked_ref())?;
         }
+        result?;
 
-        Ok(Self {
-            handles,
-            x_desc,
-            y_desc,
-            data: buffer,
-        })
+        Ok(Self { handles, decoder })
     }
 
     pub async fn from_array(
@@ -78,83 +126,275 @@ impl Bulkloader {
         x_type: String,
         y_type: String,
         array: Uint8Array,
-    ) -> Result<Bulkloader, JsValue> {
-        let x_desc = TYPE_SIZES.get(x_type.as_str()).unwrap();
-        let y_desc = TYPE_SIZES.get(y_type.as_str()).unwrap();
-
-        Ok(Self {
-            handles,
-            x_desc,
-            y_desc,
-            data: array.to_vec(),
-        })
-    }
+    ) -> Result<Bulkloader> {
+        let x_desc = type_desc(&x_type)?;
+        let y_desc = type_desc(&y_type)?;
 
-    pub fn apply(self) -> BundleRc {
-        let Bulkloader {
-            handles,
-            x_desc,
-            y_desc,
-            data,
-        } = self;
+        let mut decoder = RowDecoder::new(x_desc, y_desc, handles.len());
+        let mut buffer = vec![0u8; ARRAY_CHUNK_BYTES];
+        let len = array.length() as usize;
 
-        let row_bytes_len = x_desc.size + y_desc.size * handles.len();
+        for start in (0..len).step_by(ARRAY_CHUNK_BYTES) {
+            let end = (start + ARRAY_CHUNK_BYTES).min(len);
+            let chunk = &mut buffer[..end - start];
 
-        let point_count = data.len() / row_bytes_len;
-        let mut x = Vec::<i64>::with_capacity(point_count);
-        let mut y = vec![0.; point_count * handles.len()];
-
-        for (row_idx, row) in data.chunks_exact(row_bytes_len).enumerate() {
-            let cur = (x_desc.parser)(&row[0..x_desc.size]);
-            x.push(cur as i64);
-
-            for (col_idx, col) in row[x_desc.size..].chunks_exact(y_desc.size).enumerate() {
-                y[col_idx * point_count + row_idx] = (y_desc.parser)(col);
-            }
+            array.subarray(start as u32, end as u32).copy_to(chunk);
+            decoder.push(chunk);
         }
 
-        BundleRc::new(Batch::new(x, y, &handles))
+        Ok(Self { handles, decoder })
+    }
+
+    /// Builds the bundle. Timestamp x values are exposed as seconds since `epoch`,
+    /// given in the unit of the x type and defaulting to the first timestamp.
+    /// The bundle's `epoch` tells which one was used.
+    pub fn apply(self, epoch: Option<i64>) -> Result<BundleRc> {
+        self.decoder.finish(&self.handles, epoch)
     }
 
+    /// ### Loads an x column and one y column per trace handle
+    /// * timestamp x values are exposed as seconds since `epoch`, see `apply`
     pub fn from_columnar(
         handles: Vec<TraceHandle>,
         x_type: String,
         y_type: String,
         input_x: Uint8Array,
         input_ys: Vec<Uint8Array>,
-    ) -> BundleRc {
-        let [x_desc, y_desc] = [x_type, y_type].map(|t| TYPE_SIZES.get(t.as_str()).unwrap());
+        epoch: Option<i64>,
+    ) -> Result<BundleRc> {
+        let (x_desc, y_desc) = (type_desc(&x_type)?, type_desc(&y_type)?);
 
         let point_count = input_x.length() as usize / x_desc.size;
+        if point_count * x_desc.size != input_x.length() as usize {
+            return Err(ChartError::LengthMismatch {
+                what: "bytes of x values",
+                expected: point_count * x_desc.size,
+                actual: input_x.length() as usize,
+            });
+        }
+        if input_ys.len() != handles.len() {
+            return Err(ChartError::LengthMismatch {
+                what: "y columns",
+                expected: handles.len(),
+                actual: input_ys.len(),
+            });
+        }
+        // checked before decoding, so that nothing gets allocated for invalid input
+        for input_y in &input_ys {
+            if input_y.length() as usize != point_count * y_desc.size {
+                return Err(ChartError::LengthMismatch {
+                    what: "bytes of y values",
+                    expected: point_count * y_desc.size,
+                    actual: input_y.length() as usize,
+                });
+            }
+        }
 
-        let mut x = Vec::<i64>::with_capacity(point_count);
-        let mut y = Vec::<f64>::with_capacity(point_count * input_ys.len());
-
-        let input_x = input_x.to_vec();
-
-        for current_x in input_x.chunks_exact(x_desc.size) {
-            let cur = (x_desc.parser)(current_x);
-            x.push(cur as i64);
+        let mut x = XColumn::with_capacity(&x_desc, point_count);
+        for current_x in input_x.to_vec().chunks_exact(x_desc.size) {
+            x.push_raw(&x_desc, current_x);
         }
 
+        let mut y = YColumns::with_capacity(&y_desc, input_ys.len(), point_count);
         let mut buffer = vec![0u8; y_desc.size * point_count];
-
-        for input_y in input_ys.into_iter() {
+        for (column, input_y) in input_ys.into_iter().enumerate() {
             input_y.copy_to(&mut buffer);
+            for value in buffer.chunks_exact(y_desc.size) {
+                y.push_raw(column, &y_desc, value);
+            }
+        }
+
+        x.into_bundle(y, &handles, epoch)
+    }
 
-            for window in buffer.chunks_exact(y_desc.size) {
-                y.push((y_desc.parser)(window));
+    /// ### Loads traces that don't share their x values
+    /// * `input_xs` and `input_ys` contain one column per trace handle
+    /// * the x values of every trace are sorted if they aren't already
+    /// * timestamp x values are exposed as seconds since `epoch`, see `apply`
+    pub fn from_ragged(
+        handles: Vec<TraceHandle>,
+        x_type: String,
+        y_type: String,
+        input_xs: Vec<Uint8Array>,
+        input_ys: Vec<Uint8Array>,
+        epoch: Option<i64>,
+    ) -> Result<BundleRc> {
+        let (x_desc, y_desc) = (type_desc(&x_type)?, type_desc(&y_type)?);
+
+        for (what, columns) in [("x columns", &input_xs), ("y columns", &input_ys)] {
+            if columns.len() != handles.len() {
+                return Err(ChartError::LengthMismatch {
+                    what,
+                    expected: handles.len(),
+                    actual: columns.len(),
+                });
             }
         }
 
-        BundleRc::new(Batch::new(x, y, &handles))
+        let columns = handles
+            .into_iter()
+            .zip(input_xs.iter().zip(input_ys.iter()));
+
+        if x_desc.is_float() {
+            let columns = columns
+                .map(|(handle, (x, y))| {
+                    (handle, decode_column(&x_desc, x), decode_column(&y_desc, y))
+                })
+                .collect();
+
+            return Ok(BundleRc::new(RaggedBatch::new(columns)?.with_lod()));
+        }
+
+        let columns = columns.map(|(handle, (x, y))| {
+            let x = x
+                .to_vec()
+                .chunks_exact(x_desc.size)
+                .map(|value| x_desc.decode_int(value))
+                .collect::<Vec<_>>();
+
+            (handle, x, decode_column(&y_desc, y))
+        });
+
+        // u64 values above `i64::MAX` would wrap around, and they are never timestamps
+        if x_desc.storage == Storage::U64 {
+            let columns = columns
+                .map(|(handle, x, y)| (handle, x.into_iter().map(|v| v as u64).collect(), y))
+                .collect();
+
+            return Ok(BundleRc::new(
+                RaggedBatch::<u64, f64>::new(columns)?.with_lod(),
+            ));
+        }
+
+        let columns: Vec<(TraceHandle, Vec<i64>, Vec<f64>)> = columns
+            .map(|(handle, x, y)| (handle, x.into_iter().map(|v| v as i64).collect(), y))
+            .collect();
+        let first = columns
+            .iter()
+            .filter_map(|(_, x, _)| x.iter().min())
+            .min()
+            .copied();
+
+        let batch = RaggedBatch::new(columns)?.with_lod();
+        Ok(BundleRc::new(match x_desc.unit {
+            Some(unit) => {
+                let epoch = epoch.or(first).unwrap_or(0);
+                batch.with_time_axis(TimeAxis::new(unit, epoch))
+            }
+            None => batch,
+        }))
+    }
+
+    /// Loads a bundle previously saved using `BundleRc.to_bundle_file`.
+    /// The file describes its own handles and types.
+    pub fn from_bundle_file(array: Uint8Array) -> Result<BundleRc> {
+        read_bundle_file(&array.to_vec())
+    }
+
+    /// ### Loads an Arrow IPC file or stream
+    /// * `x_column` names the x column, the first column is used if not given
+    /// * `handles` are assigned to the remaining columns in schema order
+    /// * nulls are loaded as missing samples
+    #[cfg(feature = "arrow")]
+    pub fn from_arrow_ipc(
+        handles: Vec<TraceHandle>,
+        array: Uint8Array,
+        x_column: Option<String>,
+    ) -> Result<BundleRc> {
+        read_arrow_ipc(array.to_vec(), x_column.as_deref(), &handles)
+    }
+
+    /// ### Loads the given columns of a Parquet file
+    /// * `handles` are assigned to `y_columns` in the given order
+    /// * nulls are loaded as missing samples
+    #[cfg(feature = "parquet")]
+    pub fn from_parquet(
+        handles: Vec<TraceHandle>,
+        array: Uint8Array,
+        x_column: String,
+        y_columns: Vec<String>,
+    ) -> Result<BundleRc> {
+        read_parquet(JsFile(array), &x_column, &y_columns, &handles)
+    }
+
+    /// ### Parses a CSV or TSV file
+    /// * `handle_for` is called with the name of every loaded column and returns its trace handle
+    /// * malformed rows are left out and listed in `CsvImport.errors`
+    pub fn from_csv(
+        array: Uint8Array,
+        options: CsvOptions,
+        handle_for: &js_sys::Function,
+    ) -> Result<CsvImport> {
+        let text = String::from_utf8_lossy(&array.to_vec()).into_owned();
+        let mut error = None;
+
+        let import = read_csv(&text, &options, |name| {
+            let handle = handle_for
+                .call1(&JsValue::NULL, &JsValue::from_str(name))
+                .map_err(ChartError::from)
+                .and_then(|handle| {
+                    handle
+                        .as_f64()
+                        .filter(|&h| {
+                            h.fract() == 0. && (0. ..=TraceHandle::MAX as f64).contains(&h)
+                        })
+                        .map(|h| h as TraceHandle)
+                        .ok_or_else(|| CsvError::InvalidHandle(name.to_string()).into())
+                });
+
+            handle.unwrap_or_else(|e| {
+                error.get_or_insert(e);
+                0
+            })
+        });
+
+        if let Some(e) = error {
+            return Err(e);
+        }
+        import
     }
 
     // FIXME move this to a different file once it works :d
-    pub fn threshold_from_array(handles: Vec<TraceHandle>, input_ys: Float64Array) -> BundleRc {
+    pub fn threshold_from_array(
+        handles: Vec<TraceHandle>,
+        input_ys: Float64Array,
+    ) -> Result<BundleRc> {
         let ys_as_vec: Vec<f64> = input_ys.to_vec();
+        if ys_as_vec.len() != handles.len() {
+            return Err(ChartError::LengthMismatch {
+                what: "thresholds",
+                expected: handles.len(),
+                actual: ys_as_vec.len(),
+            });
+        }
         let ys = HashMap::from_iter(handles.iter().zip(ys_as_vec.iter()).map(|(h, y)| (*h, *y)));
 
-        BundleRc::new(ConstantBatch::new(ys))
+        Ok(BundleRc::new(ConstantBatch::new(ys)))
     }
 }
+
+/// The reason a signal was aborted with, older browsers don't provide any
+fn abort_reason(signal: &AbortSignal) -> JsValue {
+    js_sys::Reflect::get(signal, &JsValue::from_str("reason"))
+        .ok()
+        .filter(|reason| !reason.is_undefined())
+        .unwrap_or_else(|| js_sys::Error::new("the operation was aborted").into())
+}
+
+fn decode_column(desc: &TypeDescriptor, input: &Uint8Array) -> Vec<f64> {
+    input
+        .to_vec()
+        .chunks_exact(desc.size)
+        .map(|value| desc.decode(value))
+        .collect()
+}
+
+/// Builds a bundle from decoded columns, sorting the rows by x unless they are sorted
+/// already. Fails with [`ChartError::NoData`] if there are no rows.
+fn rows_into_bundle(mut x: XColumn, mut y: YColumns, handles: &[TraceHandle]) -> Result<BundleRc> {
+    if let Some(order) = x.sort() {
+        y.reorder(&order);
+    }
+
+    x.into_bundle(y, handles, None)
+}
@@ -0,0 +1,168 @@
+//! Import of Parquet files.
+//!
+//! Only the selected columns are decoded, one record batch at a time, rather than
+//! materializing the whole table first. Files held by JavaScript are read through
+//! [`JsFile`], which copies just the metadata and the selected column chunks into
+//! wasm memory. Column types are handled the same way as in Arrow IPC ingestion.
+
+use std::io::{self, Read};
+
+use arrow_array::RecordBatchReader;
+use bytes::Bytes;
+use js_sys::Uint8Array;
+use parquet::{
+    arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ProjectionMask},
+    errors::ParquetError,
+    file::reader::{ChunkReader, Length},
+};
+
+use crate::{data::TraceHandle, error::ChartError, trace::BundleRc};
+
+use super::{ArrowLoadError, RecordBatchSink};
+
+/// A Parquet file in a JavaScript array, read a range at a time
+pub struct JsFile(pub Uint8Array);
+
+// wasm is single-threaded, so the array never leaves the thread it was created on
+unsafe impl Send for JsFile {}
+unsafe impl Sync for JsFile {}
+
+impl Length for JsFile {
+    fn len(&self) -> u64 {
+        self.0.length() as u64
+    }
+}
+
+impl ChunkReader for JsFile {
+    type T = JsFileReader;
+
+    fn get_read(&self, start: u64) -> parquet::errors::Result<JsFileReader> {
+        Ok(JsFileReader {
+            array: self.0.clone(),
+            position: start.min(self.len()) as u32,
+        })
+    }
+
+    fn get_bytes(&self, start: u64, length: usize) -> parquet::errors::Result<Bytes> {
+        let end = start + length as u64;
+        if end > self.len() {
+            return Err(ParquetError::EOF(format!(
+                "expected {length} bytes at {start}, the file is {} bytes long",
+                self.len()
+            )));
+        }
+
+        Ok(self.0.subarray(start as u32, end as u32).to_vec().into())
+    }
+}
+
+/// Copies a [`JsFile`] into wasm memory as it is read
+pub struct JsFileReader {
+    array: Uint8Array,
+    position: u32,
+}
+
+impl Read for JsFileReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let end = (self.position as usize + buf.len()).min(self.array.length() as usize) as u32;
+        let len = (end - self.position) as usize;
+
+        self.array
+            .subarray(self.position, end)
+            .copy_to(&mut buf[..len]);
+        self.position = end;
+
+        Ok(len)
+    }
+}
+
+/// Reads the `x_column` and `y_columns` of a Parquet file, `handles` are assigned
+/// to `y_columns` in the given order.
+pub fn read_parquet<R: ChunkReader + 'static>(
+    data: R,
+    x_column: &str,
+    y_columns: &[String],
+    handles: &[TraceHandle],
+) -> Result<BundleRc, ChartError> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(data)?;
+
+    let schema = builder.schema();
+    let roots = std::iter::once(x_column)
+        .chain(y_columns.iter().map(String::as_str))
+        .map(|name| {
+            schema
+                .index_of(name)
+                .map_err(|_| ArrowLoadError::MissingColumn(name.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mask = ProjectionMask::roots(builder.parquet_schema(), roots);
+    let reader = builder.with_projection(mask).build()?;
+
+    let mut sink =
+        RecordBatchSink::new(&reader.schema(), Some(x_column), Some(y_columns), handles)?;
+    for batch in reader {
+        sink.push(&batch?)?;
+    }
+
+    sink.finish(handles)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Float64Array, Int64Array, RecordBatch, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+
+    use super::read_parquet;
+    use crate::types::NumericRange;
+
+    #[test]
+    fn selects_columns_across_row_groups() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("label", DataType::Utf8, false),
+            Field::new("cpu", DataType::Float64, true),
+            Field::new("time", DataType::Int64, false),
+            Field::new("mem", DataType::Float64, true),
+        ]));
+        let n = 100;
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from_iter_values((0..n).map(|i| i.to_string()))),
+                Arc::new(Float64Array::from_iter((0..n).map(|i| Some(i as f64)))),
+                Arc::new(Int64Array::from_iter_values(0..n)),
+                Arc::new(Float64Array::from_iter(
+                    (0..n).map(|i| (i % 10 != 0).then_some(-i as f64)),
+                )),
+            ],
+        )
+        .unwrap();
+
+        let props = WriterProperties::builder()
+            .set_max_row_group_size(16)
+            .set_compression(Compression::ZSTD(Default::default()))
+            .build();
+        let mut data = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut data, schema, Some(props)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let data = bytes::Bytes::from(data);
+        let columns = ["mem".to_string(), "cpu".to_string()];
+        let bundle = read_parquet(data.clone(), "time", &columns, &[7, 8]).unwrap();
+        assert_eq!(bundle.point_count(), n as usize);
+        assert_eq!(bundle.stored_types(), Some(("i64", "f64")));
+
+        let rows: Vec<_> = bundle
+            .iter_many_in_range_f64(vec![7, 8], NumericRange::new(40., 41.))
+            .collect();
+        assert!(rows[0][1].is_nan());
+        assert_eq!(rows[1], vec![41., -41., 41.]);
+
+        let label = ["label".to_string()];
+        assert!(read_parquet(data, "time", &label, &[1]).is_err());
+    }
+}
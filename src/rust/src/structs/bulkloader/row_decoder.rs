@@ -0,0 +1,140 @@
+use crate::{data::TraceHandle, error::Result, trace::BundleRc};
+
+use super::{
+    columns::{XColumn, YColumns},
+    data_types::TypeDescriptor,
+};
+
+/// Decodes rows of an x value followed by one y value per trace into columns,
+/// accepting the data in chunks of any size.
+pub(super) struct RowDecoder {
+    x_desc: TypeDescriptor,
+    y_desc: TypeDescriptor,
+
+    x: XColumn,
+    y: YColumns,
+
+    /// Beginning of a row split across chunks
+    partial: Vec<u8>,
+    bytes: usize,
+}
+
+impl RowDecoder {
+    pub fn new(x_desc: TypeDescriptor, y_desc: TypeDescriptor, columns: usize) -> Self {
+        Self {
+            x: XColumn::with_capacity(&x_desc, 0),
+            y: YColumns::with_capacity(&y_desc, columns, 0),
+            x_desc,
+            y_desc,
+            partial: Vec::new(),
+            bytes: 0,
+        }
+    }
+
+    fn row_len(&self) -> usize {
+        self.x_desc.size + self.y_desc.size * self.y.len()
+    }
+
+    /// Number of bytes received so far
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Number of complete rows decoded so far
+    pub fn rows(&self) -> usize {
+        self.x.len()
+    }
+
+    pub fn push(&mut self, mut chunk: &[u8]) {
+        let row_len = self.row_len();
+        self.bytes += chunk.len();
+
+        if !self.partial.is_empty() {
+            let take = (row_len - self.partial.len()).min(chunk.len());
+            self.partial.extend_from_slice(&chunk[..take]);
+            chunk = &chunk[take..];
+
+            if self.partial.len() < row_len {
+                return;
+            }
+
+            let row = std::mem::take(&mut self.partial);
+            self.decode_row(&row);
+            self.partial = row;
+            self.partial.clear();
+        }
+
+        let mut rows = chunk.chunks_exact(row_len);
+        for row in &mut rows {
+            self.decode_row(row);
+        }
+        self.partial.extend_from_slice(rows.remainder());
+    }
+
+    fn decode_row(&mut self, row: &[u8]) {
+        let (x, ys) = row.split_at(self.x_desc.size);
+
+        self.x.push_raw(&self.x_desc, x);
+        for (column, y) in ys.chunks_exact(self.y_desc.size).enumerate() {
+            self.y.push_raw(column, &self.y_desc, y);
+        }
+    }
+
+    /// Builds the bundle, an incomplete trailing row is dropped.
+    pub fn finish(self, handles: &[TraceHandle], epoch: Option<i64>) -> Result<BundleRc> {
+        self.x.into_bundle(self.y, handles, epoch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RowDecoder;
+    use crate::{structs::bulkloader::data_types::type_desc, types::NumericRange};
+
+    #[test]
+    fn joins_rows_split_across_chunks() {
+        let data: Vec<u8> = (0u32..10)
+            .flat_map(|i| [i, i * 10, i * 100])
+            .flat_map(u32::to_le_bytes)
+            .collect();
+
+        let u32_desc = type_desc("u32").unwrap();
+        let mut decoder = RowDecoder::new(u32_desc.clone(), u32_desc, 2);
+        for chunk in data.chunks(5) {
+            decoder.push(chunk);
+        }
+        assert_eq!(decoder.rows(), 10);
+        assert_eq!(decoder.bytes(), data.len());
+
+        let bundle = decoder.finish(&[1, 2], None).unwrap();
+        let rows: Vec<_> = bundle
+            .iter_many_in_range_f64(vec![1, 2], NumericRange::new(3., 4.))
+            .collect();
+        assert_eq!(rows, vec![vec![3., 30., 300.], vec![4., 40., 400.]]);
+    }
+
+    #[test]
+    fn exposes_timestamps_as_seconds_since_the_first() {
+        let start = 1_700_000_000_000i64;
+        let data: Vec<u8> = [(start, 1.), (start + 1500, 2.)]
+            .into_iter()
+            .flat_map(|(x, y): (i64, f64)| x.to_le_bytes().into_iter().chain(y.to_le_bytes()))
+            .collect();
+
+        let mut decoder = RowDecoder::new(
+            type_desc("DateTimeMs").unwrap(),
+            type_desc("f64").unwrap(),
+            1,
+        );
+        decoder.push(&data);
+
+        let bundle = decoder.finish(&[1], None).unwrap();
+        assert_eq!(bundle.epoch(), Some(start));
+        assert_eq!(
+            bundle
+                .iter_in_range_f64(1, NumericRange::new(0., 10.))
+                .collect::<Vec<_>>(),
+            vec![(0., 1.), (1.5, 2.)]
+        );
+    }
+}
@@ -2,6 +2,7 @@ use wasm_bindgen::prelude::wasm_bindgen;
 
 use crate::{
     data::TraceHandle,
+    error::{ChartError, Result},
     trace::BundleRc,
     types::{NumericRange, TraceMetas},
 };
@@ -36,6 +37,18 @@ impl MetaCounter {
         }
     }
 
+    fn check_column(&self, col: usize) -> Result<()> {
+        if col < self.sums.len() {
+            Ok(())
+        } else {
+            Err(ChartError::OutOfRange {
+                what: "column",
+                index: col,
+                len: self.sums.len(),
+            })
+        }
+    }
+
     pub fn iter_metas(&self) -> impl Iterator<Item = TraceMetas> + '_ {
         (0..self.sums.len()).map(|i| TraceMetas {
             handle: 0,
@@ -66,7 +79,15 @@ impl MetaCounter {
         }
     }
 
-    pub fn add_from_counter(&mut self, col: usize, other: &MetaCounter, other_col: usize) {
+    pub fn add_from_counter(
+        &mut self,
+        col: usize,
+        other: &MetaCounter,
+        other_col: usize,
+    ) -> Result<()> {
+        self.check_column(col)?;
+        other.check_column(other_col)?;
+
         self.sums[col] += other.sums[other_col];
         self.lens[col] += other.lens[other_col];
         self.nz_lens[col] += other.nz_lens[other_col];
@@ -75,6 +96,8 @@ impl MetaCounter {
         self.firsts[col] = self.firsts[col].min(other.firsts[other_col]);
         self.lasts[col] = self.lasts[col].max(other.lasts[other_col]);
         self.point_counts[col] += other.point_counts[other_col];
+
+        Ok(())
     }
 
     // ! TODO Ensure correct behavior for trace handles
@@ -85,7 +108,15 @@ impl MetaCounter {
         traces: &[TraceHandle],
         x_range: NumericRange,
         y_factor: f64,
-    ) {
+    ) -> Result<()> {
+        if traces.len() > self.sums.len() {
+            return Err(ChartError::OutOfRange {
+                what: "column",
+                index: traces.len() - 1,
+                len: self.sums.len(),
+            });
+        }
+
         for (i, trace_data) in traces
             .iter()
             .map(|&t| bundle.iter_in_range_f64(t, x_range))
@@ -95,12 +126,13 @@ impl MetaCounter {
                 self.add(i, y * y_factor);
             }
         }
+
+        Ok(())
     }
 
-    pub fn to_array(&self) -> js_sys::Array {
-        js_sys::Array::from_iter(
-            self.iter_metas()
-                .map(|m| serde_wasm_bindgen::to_value(&m).unwrap()),
-        )
+    pub fn to_array(&self) -> Result<js_sys::Array> {
+        self.iter_metas()
+            .map(|m| Ok(serde_wasm_bindgen::to_value(&m)?))
+            .collect()
     }
 }
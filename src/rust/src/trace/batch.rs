@@ -2,76 +2,256 @@ use std::collections::HashMap;
 
 use num_traits::{FromPrimitive, Num, ToPrimitive};
 
-use crate::{data::TraceHandle, types::NumericRange};
+use crate::{
+    data::TraceHandle,
+    error::{ChartError, Result},
+    types::NumericRange,
+};
 
-use super::{Bundle, BundleRange, InterpolationStrategy};
+use super::{Bundle, BundleRange, InterpolationStrategy, LodPyramid, TimeAxis, ValidityMask};
+
+pub trait N: Num + Clone + PartialOrd + ToPrimitive + FromPrimitive {
+    /// Name of the type as accepted by the bulkloader, e.g. `"u8"`
+    const NAME: &'static str;
 
-pub trait N: Num + Clone + ToPrimitive + FromPrimitive {
     fn as_f64(&self) -> f64 {
         self.to_f64().unwrap()
     }
 }
-impl<T: Num + Clone + ToPrimitive + FromPrimitive> N for T {}
+
+macro_rules! impl_n {
+    ( $($t:ty),+ ) => {
+        $(
+            impl N for $t {
+                const NAME: &'static str = stringify!($t);
+            }
+        )+
+    };
+}
+
+impl_n!(u8, i8, u16, i16, u32, i32, i64, u64, f32, f64);
 
 #[derive(Clone)]
 pub struct Batch<X: N, Y: N> {
     x: Vec<X>,
-    y: Vec<Y>,
+    /// One column of samples per trace, each as long as `x`
+    y: Vec<Vec<Y>>,
     y_idx: HashMap<TraceHandle, usize>,
+    lod: Option<LodPyramid>,
+
+    /// Marks missing samples of the concatenated columns, all samples are present if unset.
+    /// Missing samples are reported as NaN, same as NaNs stored in `y`.
+    validity: Option<ValidityMask>,
+
+    /// Set if `x` holds integer timestamps, which are exposed relative to its epoch
+    time_axis: Option<TimeAxis>,
 
     from: f64,
     to: f64,
 }
 
 impl<X: N, Y: N> Batch<X, Y> {
-    pub fn new(x: Vec<X>, y: Vec<Y>, handles: &[TraceHandle]) -> Self {
-        let from = x.first().unwrap().as_f64();
-        let to = x.last().unwrap().as_f64();
+    /// Creates the batch from `y` holding the columns of all the handles one after another.
+    pub fn new(x: Vec<X>, y: Vec<Y>, handles: &[TraceHandle]) -> Result<Self> {
+        if y.len() != x.len() * handles.len() {
+            return Err(ChartError::LengthMismatch {
+                what: "y values",
+                expected: x.len() * handles.len(),
+                actual: y.len(),
+            });
+        }
+
+        let columns = match x.len() {
+            0 => vec![Vec::new(); handles.len()],
+            len => y.chunks_exact(len).map(<[Y]>::to_vec).collect(),
+        };
+
+        Self::from_columns(x, columns, handles)
+    }
 
-        assert_eq!(
-            y.len(),
-            x.len() * handles.len(),
-            "length of y matches (length of x * number of handles)"
-        );
+    /// Creates the batch from one column per handle.
+    pub fn from_columns(x: Vec<X>, y: Vec<Vec<Y>>, handles: &[TraceHandle]) -> Result<Self> {
+        let (Some(first), Some(last)) = (x.first(), x.last()) else {
+            return Err(ChartError::NoData);
+        };
+        let (from, to) = (first.as_f64(), last.as_f64());
+
+        if y.len() != handles.len() {
+            return Err(ChartError::LengthMismatch {
+                what: "y columns",
+                expected: handles.len(),
+                actual: y.len(),
+            });
+        }
+        if let Some(column) = y.iter().find(|column| column.len() != x.len()) {
+            return Err(ChartError::LengthMismatch {
+                what: "y values per column",
+                expected: x.len(),
+                actual: column.len(),
+            });
+        }
 
         let y_idx = HashMap::from_iter(handles.iter().enumerate().map(|(i, handle)| (*handle, i)));
 
-        Self {
+        Ok(Self {
             x,
             y,
             y_idx,
+            lod: None,
+            validity: None,
+            time_axis: None,
             from,
             to,
+        })
+    }
+
+    /// Builds the level-of-detail pyramid used for decimated iteration and extents.
+    pub fn with_lod(mut self) -> Self {
+        let lod = LodPyramid::build(self.x.len(), self.y.len(), |col, i| self.y_at(col, i));
+
+        self.lod = Some(lod);
+        self
+    }
+
+    /// Marks samples as missing, the mask covers all the columns one after another.
+    pub fn with_validity(mut self, validity: ValidityMask) -> Result<Self> {
+        if validity.len() != self.x.len() * self.y.len() {
+            return Err(ChartError::LengthMismatch {
+                what: "validity flags",
+                expected: self.x.len() * self.y.len(),
+                actual: validity.len(),
+            });
         }
+
+        self.validity = validity.has_missing().then_some(validity);
+
+        if self.lod.is_some() {
+            self = self.with_lod();
+        }
+        Ok(self)
+    }
+
+    /// Interprets the x values as timestamps, which changes the range they are queried by.
+    pub fn with_time_axis(mut self, time_axis: TimeAxis) -> Self {
+        self.time_axis = Some(time_axis);
+        self.from = self.x_f64(&self.x[0]);
+        self.to = self.x_f64(&self.x[self.x.len() - 1]);
+        self
     }
 
     pub fn get_y_data_of(&self, trace: TraceHandle) -> Option<&[Y]> {
-        let window = self.x.len();
+        self.y_idx.get(&trace).map(|&idx| self.y[idx].as_slice())
+    }
 
-        self.y_idx.get(&trace).map(|idx| {
-            let offset = idx * window;
+    /// Index of the trace's column in `y`
+    fn column_of(&self, trace: TraceHandle) -> Option<usize> {
+        self.y_idx.get(&trace).copied()
+    }
 
-            &self.y[offset..(offset + window)]
-        })
+    /// The `i`-th sample of a column, NaN if it is missing
+    fn y_at(&self, column: usize, i: usize) -> f64 {
+        match &self.validity {
+            Some(validity) if !validity.is_valid(column * self.x.len() + i) => f64::NAN,
+            _ => self.y[column][i].as_f64(),
+        }
+    }
+
+    /// The x value as exposed by the bundle
+    fn x_f64(&self, x: &X) -> f64 {
+        match self.time_axis {
+            Some(axis) => x.to_i64().map_or(f64::NAN, |t| axis.to_x(t)),
+            None => x.as_f64(),
+        }
+    }
+
+    fn x_at(&self, i: usize) -> f64 {
+        self.x_f64(&self.x[i])
+    }
+
+    fn point_at(&self, column: usize, i: usize) -> (f64, f64) {
+        (self.x_at(i), self.y_at(column, i))
+    }
+
+    /// Binary searches the x values, ordering them by [`f64::total_cmp`] so that floating point
+    /// x values can be searched just like integers
+    fn search_x(&self, x: &[X], value: f64) -> Result<usize, usize> {
+        x.binary_search_by(|p| self.x_f64(p).total_cmp(&value))
+    }
+
+    /// Returns the span of indices visited by `iter_in_range_with_neighbors_f64`
+    fn neighbors_span(&self, x_range: NumericRange) -> Option<(usize, usize)> {
+        let from = match self.search_x(&self.x, x_range.from) {
+            Ok(i) => i,
+            Err(0) => 0,
+            Err(i) if i == self.x.len() => return None,
+            Err(i) => i - 1,
+        };
+
+        let take = match self.search_x(&self.x[from..], x_range.to) {
+            // x_range.to is before from
+            Err(0) => return None,
+            Ok(i) | Err(i) => i + 1,
+        };
+
+        Some((from, (from + take).min(self.x.len())))
     }
 }
 
-impl<X: N + Ord, Y: N> Bundle for Batch<X, Y> {
+impl<X: N, Y: N> Bundle for Batch<X, Y> {
     fn traces(&self) -> Vec<TraceHandle> {
         self.y_idx.keys().copied().collect()
     }
 
+    fn stored_types(&self) -> Option<(&'static str, &'static str)> {
+        Some((X::NAME, Y::NAME))
+    }
+
+    fn integer_column(&self, trace: Option<TraceHandle>) -> Option<Vec<i128>> {
+        fn exact<T: N>(values: &[T]) -> Option<Vec<i128>> {
+            if T::NAME.starts_with('f') {
+                return None;
+            }
+            values.iter().map(ToPrimitive::to_i128).collect()
+        }
+
+        match trace {
+            None => exact(&self.x),
+            Some(trace) => exact(&self.y[*self.y_idx.get(&trace)?]),
+        }
+    }
+
     fn contains_trace(&self, trace: TraceHandle) -> bool {
         self.y_idx.contains_key(&trace)
     }
 
     fn range(&self) -> BundleRange {
         BundleRange::Bounded {
-            from: self.from.as_f64(),
-            to: self.to.as_f64(),
+            from: self.from,
+            to: self.to,
         }
     }
 
+    fn time_axis(&self) -> Option<TimeAxis> {
+        self.time_axis
+    }
+
+    fn memory_footprint(&self) -> usize {
+        let columns: usize = self
+            .y
+            .iter()
+            .map(|column| column.capacity() * size_of::<Y>())
+            .sum();
+
+        self.x.capacity() * size_of::<X>()
+            + columns
+            + self.y_idx.capacity() * size_of::<(TraceHandle, usize)>()
+            + self.lod.as_ref().map_or(0, LodPyramid::memory_footprint)
+            + self
+                .validity
+                .as_ref()
+                .map_or(0, ValidityMask::memory_footprint)
+    }
+
     fn point_count(&self) -> usize {
         self.x.len()
     }
@@ -81,21 +261,18 @@ impl<X: N + Ord, Y: N> Bundle for Batch<X, Y> {
         trace: TraceHandle,
         x_range: NumericRange,
     ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
-        let Some(data) = self.get_y_data_of(trace) else {
+        let Some(column) = self.column_of(trace) else {
             return Box::new(std::iter::empty());
         };
 
-        let from = match self.x.binary_search(&X::from_f64(x_range.from).unwrap()) {
+        let from = match self.search_x(&self.x, x_range.from) {
             Ok(i) | Err(i) => i,
         };
 
         Box::new(
-            self.x
-                .iter()
-                .zip(data.iter())
-                .skip(from)
-                .take_while(move |(x, _)| x.as_f64() <= x_range.to)
-                .map(|(x, y)| (x.as_f64(), y.as_f64())),
+            (from..self.x.len())
+                .take_while(move |&i| self.x_at(i) <= x_range.to)
+                .map(move |i| self.point_at(column, i)),
         )
     }
 
@@ -104,46 +281,71 @@ impl<X: N + Ord, Y: N> Bundle for Batch<X, Y> {
         handle: TraceHandle,
         x_range: NumericRange,
     ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
-        let Some(data) = self.get_y_data_of(handle) else {
+        let Some(column) = self.column_of(handle) else {
             return Box::new(std::iter::empty());
         };
-
-        let from = match self.x.binary_search(&X::from_f64(x_range.from).unwrap()) {
-            Ok(i) => i,
-            Err(0) => 0,
-            Err(i) if i == self.x.len() => return Box::new(std::iter::empty()),
-            Err(i) => i - 1,
+        let Some((from, to)) = self.neighbors_span(x_range) else {
+            return Box::new(std::iter::empty());
         };
 
-        let take = match self.x[from..].binary_search(&X::from_f64(x_range.to).unwrap()) {
-            // x_range.to is before from
-            Err(0) => return Box::new(std::iter::empty()),
-            Ok(i) | Err(i) => i + 1,
+        Box::new((from..to).map(move |i| self.point_at(column, i)))
+    }
+
+    fn iter_in_range_decimated_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+        buckets: usize,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        let (Some(lod), Some(&column)) = (&self.lod, self.y_idx.get(&handle)) else {
+            return self.iter_in_range_with_neighbors_f64(handle, x_range);
+        };
+        let Some((from, to)) = self.neighbors_span(x_range) else {
+            return Box::new(std::iter::empty());
+        };
+        let Some(level) = lod.level_for(to - from, buckets) else {
+            return self.iter_in_range_with_neighbors_f64(handle, x_range);
         };
 
         Box::new(
-            self.x
-                .iter()
-                .zip(data.iter())
-                .skip(from)
-                .take(take)
-                .map(|(x, y)| (x.as_f64(), y.as_f64())),
+            lod.decimated_indices(level, column, from, to)
+                .map(move |i| self.point_at(column, i)),
         )
     }
 
+    fn extents_in_range_with_neighbors_f64(
+        &self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Option<(f64, f64)> {
+        let column = self.column_of(handle)?;
+        let (from, to) = self.neighbors_span(x_range)?;
+
+        match &self.lod {
+            Some(lod) => lod.extents(column, from, to, |i| self.y_at(column, i)),
+            None => (from..to)
+                .map(|i| self.y_at(column, i))
+                .filter(|y| !y.is_nan())
+                .fold(None, |acc, y| match acc {
+                    Some((min, max)) => Some((y.min(min), y.max(max))),
+                    None => Some((y, y)),
+                }),
+        }
+    }
+
     fn iter_many_in_range_f64<'a>(
         &'a self,
         traces: Vec<TraceHandle>,
         x_range: NumericRange,
     ) -> Box<dyn Iterator<Item = Vec<f64>> + 'a> {
-        let index = match self.x.binary_search(&X::from_f64(x_range.from).unwrap()) {
+        let index = match self.search_x(&self.x, x_range.from) {
             Ok(i) => i,
             Err(i) => i,
         };
 
         Box::new(BatchManyIterator {
             batch: self,
-            traces,
+            columns: traces.into_iter().map(|t| self.column_of(t)).collect(),
             index,
             to: x_range.to,
         })
@@ -159,36 +361,14 @@ impl<X: N + Ord, Y: N> Bundle for Batch<X, Y> {
             return None;
         }
 
-        let data = self.get_y_data_of(handle)?;
+        let column = self.column_of(handle)?;
 
-        match self.x.binary_search(&X::from_f64(x).unwrap()) {
+        match self.search_x(&self.x, x) {
             Err(0) => None,
             Err(i) if i == self.x.len() => None,
-            Ok(i) => Some((x, data[i].as_f64())),
+            Ok(i) => Some((x, self.y_at(column, i))).filter(|(_, y)| !y.is_nan()),
             Err(i) => {
-                let left_x = self.x[i - 1].as_f64();
-                let right_x = self.x[i].as_f64();
-
-                let left_y = data[i - 1].as_f64();
-                let right_y = data[i].as_f64();
-
-                match strategy {
-                    InterpolationStrategy::None => None,
-                    InterpolationStrategy::Previous => (left_x, left_y).into(),
-                    InterpolationStrategy::Next => (right_x, right_y).into(),
-                    InterpolationStrategy::Nearest => {
-                        if (x - left_x) < (right_x - x) {
-                            (left_x, left_y).into()
-                        } else {
-                            (right_x, right_y).into()
-                        }
-                    }
-                    InterpolationStrategy::Linear => {
-                        let frac = (x - left_x) / (right_x - left_x);
-
-                        Some((x, right_y * frac + left_y * (1.0 - frac)))
-                    }
-                }
+                strategy.interpolate(x, self.point_at(column, i - 1), self.point_at(column, i))
             }
         }
     }
@@ -196,7 +376,7 @@ impl<X: N + Ord, Y: N> Bundle for Batch<X, Y> {
 
 struct BatchManyIterator<'a, X: N, Y: N> {
     batch: &'a Batch<X, Y>,
-    traces: Vec<TraceHandle>,
+    columns: Vec<Option<usize>>,
     to: f64,
     index: usize,
 }
@@ -208,18 +388,20 @@ impl<X: N, Y: N> Iterator for BatchManyIterator<'_, X, Y> {
             return None;
         }
 
-        let xi = self.batch.x[self.index].as_f64();
+        let xi = self.batch.x_at(self.index);
         if xi > self.to {
             return None;
         }
 
-        let mut result = Vec::with_capacity(self.traces.len() + 1);
+        let mut result = Vec::with_capacity(self.columns.len() + 1);
         result.push(xi);
 
-        for t in &self.traces {
-            let y = self.batch.get_y_data_of(*t).unwrap();
-
-            result.push(y[self.index].as_f64());
+        for column in &self.columns {
+            result.push(match *column {
+                Some(column) => self.batch.y_at(column, self.index),
+                // traces missing from the bundle are missing at every x
+                None => f64::NAN,
+            });
         }
         self.index += 1;
 
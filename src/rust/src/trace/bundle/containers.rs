@@ -29,6 +29,14 @@ impl BundleRc {
         }
     }
 
+    /// Wraps a bundle that is shared with some other owner, e.g. a writer handle.
+    pub fn from_rc(bundle: Rc<dyn Bundle>) -> BundleRc {
+        BundleRc {
+            handle: BUNDLE_COUNTER.fetch_add(1, Ordering::Relaxed),
+            bundle,
+        }
+    }
+
     pub fn downgrade(&self) -> BundleWeak {
         BundleWeak {
             handle: self.handle,
@@ -92,6 +100,13 @@ impl BundleRc {
     pub fn point_count(&self) -> usize {
         self.bundle.point_count()
     }
+    pub fn version(&self) -> u64 {
+        self.bundle.version()
+    }
+    /// Approximate number of bytes taken by the bundle's data, see `Bundle::memory_footprint`
+    pub fn memory_footprint(&self) -> usize {
+        self.bundle.memory_footprint()
+    }
     pub fn contains_point(&self, point: f64) -> bool {
         self.bundle.contains_point(point)
     }
@@ -5,6 +5,8 @@ mod containers;
 mod vec;
 
 use crate::{data::TraceHandle, types::NumericRange};
+
+use super::{MergedPointsIterator, TimeAxis};
 use serde::{Deserialize, Serialize};
 use tsify::Tsify;
 use wasm_bindgen::prelude::wasm_bindgen;
@@ -23,6 +25,34 @@ pub enum InterpolationStrategy {
     Next,
 }
 
+impl InterpolationStrategy {
+    /// Computes the value at `x` lying strictly between the points `left` and `right`.
+    /// Returns `None` if the value depends on a missing sample.
+    pub fn interpolate(self, x: f64, left: (f64, f64), right: (f64, f64)) -> Option<(f64, f64)> {
+        let ((left_x, left_y), (right_x, right_y)) = (left, right);
+
+        let point = match self {
+            InterpolationStrategy::None => None,
+            InterpolationStrategy::Previous => left.into(),
+            InterpolationStrategy::Next => right.into(),
+            InterpolationStrategy::Nearest => {
+                if (x - left_x) < (right_x - x) {
+                    left.into()
+                } else {
+                    right.into()
+                }
+            }
+            InterpolationStrategy::Linear => {
+                let frac = (x - left_x) / (right_x - left_x);
+
+                Some((x, right_y * frac + left_y * (1.0 - frac)))
+            }
+        };
+
+        point.filter(|(_, y)| !y.is_nan())
+    }
+}
+
 #[derive(Debug, Clone, Tsify, Serialize, Deserialize)]
 #[tsify(into_wasm_abi, from_wasm_abi)]
 #[serde(tag = "type", content = "value")]
@@ -39,6 +69,14 @@ impl BundleRange {
             Everywhere => true,
         }
     }
+
+    /// The ends of the range, `Everywhere` spans from minus to plus infinity
+    pub fn bounds(&self) -> (f64, f64) {
+        match self {
+            BundleRange::Bounded { from, to } => (*from, *to),
+            BundleRange::Everywhere => (f64::NEG_INFINITY, f64::INFINITY),
+        }
+    }
 }
 
 impl From<NumericRange> for BundleRange {
@@ -50,6 +88,11 @@ impl From<NumericRange> for BundleRange {
     }
 }
 
+/// A collection of traces sharing a common x range.
+///
+/// Missing samples are represented by a NaN y. Iterators yield them as they are so that
+/// renderers can break lines at gaps, [`Bundle::value_at`] returns `None` instead of
+/// a value depending on a missing sample, and extents ignore them.
 pub trait Bundle {
     fn traces(&self) -> Vec<TraceHandle>;
     fn range(&self) -> BundleRange;
@@ -57,6 +100,35 @@ pub trait Bundle {
 
     fn contains_trace(&self, trace: TraceHandle) -> bool;
 
+    /// Monotonically increasing counter bumped on every mutation of the bundle's data.
+    /// Immutable bundles never change and can keep the default.
+    fn version(&self) -> u64 {
+        0
+    }
+
+    /// How the x values map to timestamps, if they were loaded from integer timestamps
+    fn time_axis(&self) -> Option<TimeAxis> {
+        None
+    }
+
+    /// Approximate number of bytes taken by the bundle's own data and caches,
+    /// not counting the bundles it is computed from
+    fn memory_footprint(&self) -> usize {
+        0
+    }
+
+    /// Names of the types the x and y values are stored as, see [`super::N::NAME`],
+    /// for bundles keeping the values they were loaded with
+    fn stored_types(&self) -> Option<(&'static str, &'static str)> {
+        None
+    }
+
+    /// Exact values of a column stored as integers, the x values if `trace` is `None`.
+    /// Lets bundles be saved without rounding integers too wide for an `f64`.
+    fn integer_column(&self, _trace: Option<TraceHandle>) -> Option<Vec<i128>> {
+        None
+    }
+
     fn contains_point(&self, point: f64) -> bool {
         match self.range() {
             BundleRange::Bounded { from, to } => from <= point && to >= point,
@@ -83,6 +155,34 @@ pub trait Bundle {
         x_range: NumericRange,
     ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a>;
 
+    /// Like [`Bundle::iter_in_range_with_neighbors_f64`], but may leave out points that would
+    /// be indistinguishable when `x_range` is split into `buckets` columns. The first, last,
+    /// smallest and largest point of every column are always kept.
+    fn iter_in_range_decimated_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+        _buckets: usize,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        self.iter_in_range_with_neighbors_f64(handle, x_range)
+    }
+
+    /// The smallest and largest y of the points returned by
+    /// [`Bundle::iter_in_range_with_neighbors_f64`], ignoring NaNs.
+    fn extents_in_range_with_neighbors_f64(
+        &self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Option<(f64, f64)> {
+        self.iter_in_range_with_neighbors_f64(handle, x_range)
+            .map(|(_, y)| y)
+            .filter(|y| !y.is_nan())
+            .fold(None, |acc, y| match acc {
+                Some((min, max)) => Some((y.min(min), y.max(max))),
+                None => Some((y, y)),
+            })
+    }
+
     fn iter_many_in_range_f64<'a>(
         &'a self,
         handles: Vec<TraceHandle>,
@@ -96,3 +196,81 @@ pub trait Bundle {
         interpolation_strategy: InterpolationStrategy,
     ) -> Option<(f64, f64)>;
 }
+
+/// Leaves out all but the last point before `x_range` and the first one after it
+pub(crate) fn trim_neighbors<'a>(
+    points: impl Iterator<Item = (f64, f64)> + 'a,
+    x_range: NumericRange,
+) -> impl Iterator<Item = (f64, f64)> + 'a {
+    let mut points = points.peekable();
+
+    let mut before = None;
+    while let Some(point) = points.next_if(|(x, _)| *x < x_range.from) {
+        before = Some(point);
+    }
+
+    before
+        .into_iter()
+        .chain(points.scan(false, move |done, point| {
+            if *done {
+                return None;
+            }
+            *done = point.0 > x_range.to;
+
+            Some(point)
+        }))
+}
+
+/// Finds the value at `x` among the points of a trace around it, as returned by
+/// [`Bundle::iter_in_range_with_neighbors_f64`] for the range `[x, x]`
+pub(crate) fn value_from_neighbors(
+    points: impl Iterator<Item = (f64, f64)>,
+    x: f64,
+    interpolation_strategy: InterpolationStrategy,
+) -> Option<(f64, f64)> {
+    let mut left = None;
+    for (xi, yi) in points {
+        if xi == x {
+            return Some((x, yi)).filter(|(_, y)| !y.is_nan());
+        }
+        if xi > x {
+            return interpolation_strategy.interpolate(x, left?, (xi, yi));
+        }
+        left = Some((xi, yi));
+    }
+
+    None
+}
+
+/// Rows of several traces merged from their points, for bundles which compute
+/// [`Bundle::iter_many_in_range_f64`] from [`Bundle::iter_in_range_f64`]
+pub(crate) fn merged_rows_in_range<'a>(
+    bundle: &'a (impl Bundle + ?Sized),
+    handles: Vec<TraceHandle>,
+    x_range: NumericRange,
+) -> Box<dyn Iterator<Item = Vec<f64>> + 'a> {
+    Box::new(MergedPointsIterator::new(
+        handles
+            .into_iter()
+            .map(|handle| bundle.iter_in_range_f64(handle, x_range))
+            .collect(),
+    ))
+}
+
+/// [`Bundle::value_at`] of bundles which compute it from the points around `x`
+pub(crate) fn value_at_from_neighbors(
+    bundle: &(impl Bundle + ?Sized),
+    trace: TraceHandle,
+    x: f64,
+    interpolation_strategy: InterpolationStrategy,
+) -> Option<(f64, f64)> {
+    if !bundle.contains_point(x) {
+        return None;
+    }
+
+    value_from_neighbors(
+        bundle.iter_in_range_with_neighbors_f64(trace, NumericRange::new(x, x)),
+        x,
+        interpolation_strategy,
+    )
+}
@@ -1,5 +1,7 @@
 use js_sys::wasm_bindgen::prelude::*;
 
+use crate::error::{ChartError, Result};
+
 use super::{BundleRc, BundleWeak};
 
 #[wasm_bindgen]
@@ -26,8 +28,12 @@ impl BundleVec {
 }
 
 impl BundleVec {
-    pub fn iter(&self) -> impl Iterator<Item = BundleRc> + '_ {
-        self.0.iter().map(|d| d.upgrade().unwrap())
+    /// Upgrades all the bundles, failing if any of them has already been freed
+    pub fn upgrade_all(&self) -> Result<Vec<BundleRc>> {
+        self.0
+            .iter()
+            .map(|d| d.upgrade().ok_or(ChartError::DroppedBundle))
+            .collect()
     }
 
     pub fn push_weak(&mut self, bundle: BundleWeak) {
@@ -0,0 +1,412 @@
+// https://github.com/madonoharu/tsify/issues/42
+#![allow(non_snake_case)]
+
+use std::iter::Peekable;
+
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    data::TraceHandle,
+    error::{ChartError, Result},
+    types::NumericRange,
+};
+
+use super::{
+    common_time_axis, merged_rows_in_range, trim_neighbors, value_at_from_neighbors, Bundle,
+    BundleRange, BundleRc, BundleVec, InterpolationStrategy, TimeAxis,
+};
+
+/// Decides whose points are used where the ranges of partitions overlap
+#[derive(Tsify, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverlapPolicy {
+    /// Within its range, a partition replaces the points of all the older ones
+    PreferNewest,
+    /// Within its range, a partition replaces the points of all the newer ones
+    PreferOldest,
+    /// The points of all the partitions are kept, the newest one wins where they share an x
+    Merge,
+}
+
+type Points<'a> = Box<dyn Iterator<Item = (f64, f64)> + 'a>;
+
+/// A bundle stitching several partitions together along x.
+///
+/// Partitions are ordered from the oldest to the newest. A trace doesn't have to be present
+/// in all of them, the partitions without it are skipped when it is queried.
+pub struct ChainedBundle {
+    partitions: Vec<BundleRc>,
+    overlap: OverlapPolicy,
+}
+
+impl ChainedBundle {
+    pub fn new(partitions: Vec<BundleRc>, overlap: OverlapPolicy) -> Self {
+        Self {
+            partitions,
+            overlap,
+        }
+    }
+
+    /// Whether the points of partition `a` take precedence over the ones of `b`
+    fn outranks(&self, a: usize, b: usize) -> bool {
+        match self.overlap {
+            OverlapPolicy::PreferNewest | OverlapPolicy::Merge => a > b,
+            OverlapPolicy::PreferOldest => a < b,
+        }
+    }
+
+    /// Indices and ranges of the partitions containing the trace
+    fn partitions_of(&self, trace: TraceHandle) -> Vec<(usize, (f64, f64))> {
+        self.partitions
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.contains_trace(trace))
+            .map(|(i, p)| (i, p.range().bounds()))
+            .collect()
+    }
+
+    /// Merges the points `query` yields for every partition containing the trace, leaving out
+    /// the points within the range of a partition that outranks theirs. With `neighbors`,
+    /// the closest partitions on either side of `x_range` are asked for their edge points too.
+    fn merged<'a>(
+        &'a self,
+        trace: TraceHandle,
+        x_range: NumericRange,
+        neighbors: bool,
+        query: impl Fn(&'a BundleRc, NumericRange) -> Points<'a>,
+    ) -> Points<'a> {
+        let parts = self.partitions_of(trace);
+
+        let mut queries: Vec<(usize, NumericRange)> = parts
+            .iter()
+            .filter(|(_, range)| intersects(*range, x_range.as_tuple()))
+            .map(|&(i, _)| (i, x_range))
+            .collect();
+
+        if neighbors {
+            let before = parts
+                .iter()
+                .filter(|(_, (_, to))| *to < x_range.from)
+                .max_by(|(_, a), (_, b)| a.1.total_cmp(&b.1));
+            let after = parts
+                .iter()
+                .filter(|(_, (from, _))| *from > x_range.to)
+                .min_by(|(_, a), (_, b)| a.0.total_cmp(&b.0));
+
+            queries.extend(before.map(|&(i, (_, to))| (i, NumericRange::new(to, to))));
+            queries.extend(after.map(|&(i, (from, _))| (i, NumericRange::new(from, from))));
+        }
+
+        // the merge prefers the sources that come first
+        queries.sort_by_key(|&(i, _)| match self.overlap {
+            OverlapPolicy::PreferOldest => i as isize,
+            _ => -(i as isize),
+        });
+
+        let sources = queries
+            .into_iter()
+            .map(|(i, range)| {
+                let own = self.partitions[i].range().bounds();
+                let shadows: Vec<(f64, f64)> = match self.overlap {
+                    OverlapPolicy::Merge => Vec::new(),
+                    _ => parts
+                        .iter()
+                        .filter(|&&(j, other)| self.outranks(j, i) && intersects(own, other))
+                        .map(|&(_, other)| other)
+                        .collect(),
+                };
+
+                let points = query(&self.partitions[i], range)
+                    .filter(move |(x, _)| !shadows.iter().any(|(from, to)| from <= x && x <= to));
+
+                (Box::new(points) as Points<'a>).peekable()
+            })
+            .collect();
+
+        let points = ChainIterator { sources };
+        if neighbors {
+            Box::new(trim_neighbors(points, x_range))
+        } else {
+            Box::new(points)
+        }
+    }
+}
+
+fn intersects(a: (f64, f64), b: (f64, f64)) -> bool {
+    a.0 <= b.1 && b.0 <= a.1
+}
+
+/// Merges sorted point iterators, which are ordered by precedence.
+/// Of the points sharing an x, only the one of the first iterator is kept.
+struct ChainIterator<'a> {
+    sources: Vec<Peekable<Points<'a>>>,
+}
+
+impl Iterator for ChainIterator<'_> {
+    type Item = (f64, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self
+            .sources
+            .iter_mut()
+            .filter_map(|s| s.peek().map(|(x, _)| *x))
+            .min_by(f64::total_cmp)?;
+
+        let mut point = None;
+        for source in self.sources.iter_mut() {
+            if let Some(p) = source.next_if(|(xi, _)| *xi == x) {
+                point.get_or_insert(p);
+            }
+        }
+
+        point
+    }
+}
+
+impl Bundle for ChainedBundle {
+    fn traces(&self) -> Vec<TraceHandle> {
+        let mut traces: Vec<_> = self.partitions.iter().flat_map(|p| p.traces()).collect();
+        traces.sort_unstable();
+        traces.dedup();
+
+        traces
+    }
+
+    fn range(&self) -> BundleRange {
+        let bounded = self.partitions.iter().filter_map(|p| match p.range() {
+            BundleRange::Bounded { from, to } => Some((from, to)),
+            BundleRange::Everywhere => None,
+        });
+
+        match bounded.reduce(|a, b| (a.0.min(b.0), a.1.max(b.1))) {
+            Some((from, to)) => BundleRange::Bounded { from, to },
+            None => BundleRange::Everywhere,
+        }
+    }
+
+    fn point_count(&self) -> usize {
+        self.partitions.iter().map(|p| p.point_count()).sum()
+    }
+
+    fn contains_trace(&self, trace: TraceHandle) -> bool {
+        self.partitions.iter().any(|p| p.contains_trace(trace))
+    }
+
+    fn version(&self) -> u64 {
+        self.partitions
+            .iter()
+            .fold(0, |acc, p| acc.wrapping_add(p.version()))
+    }
+
+    fn time_axis(&self) -> Option<TimeAxis> {
+        common_time_axis(&self.partitions)
+    }
+
+    /// Partitions are owned by the chain, so their data is counted
+    /// Only the list of partitions, which are shared with whoever else holds them
+    fn memory_footprint(&self) -> usize {
+        self.partitions.capacity() * size_of::<BundleRc>()
+    }
+
+    fn iter_in_range_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        self.merged(handle, x_range, false, move |p, range| {
+            p.iter_in_range_f64(handle, range)
+        })
+    }
+
+    fn iter_in_range_with_neighbors_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        self.merged(handle, x_range, true, move |p, range| {
+            p.iter_in_range_with_neighbors_f64(handle, range)
+        })
+    }
+
+    fn iter_in_range_decimated_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+        buckets: usize,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        self.merged(handle, x_range, true, move |p, range| {
+            p.iter_in_range_decimated_f64(handle, range, buckets)
+        })
+    }
+
+    fn extents_in_range_with_neighbors_f64(
+        &self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Option<(f64, f64)> {
+        let parts = self.partitions_of(handle);
+        let hits: Vec<_> = parts
+            .iter()
+            .filter(|(_, range)| intersects(*range, x_range.as_tuple()))
+            .collect();
+
+        // without overlaps, and with both ends of the range within some partition,
+        // the neighbors of each partition are the neighbors of the whole chain
+        let overlapping = hits.iter().any(|&&(i, range)| {
+            parts
+                .iter()
+                .any(|&(j, other)| i != j && intersects(range, other))
+        });
+        let covers_ends = hits.iter().any(|(_, (from, _))| *from <= x_range.from)
+            && hits.iter().any(|(_, (_, to))| *to >= x_range.to);
+
+        if !overlapping && covers_ends {
+            return hits
+                .iter()
+                .filter_map(|&&(i, _)| {
+                    self.partitions[i].extents_in_range_with_neighbors_f64(handle, x_range)
+                })
+                .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)));
+        }
+
+        self.iter_in_range_with_neighbors_f64(handle, x_range)
+            .map(|(_, y)| y)
+            .filter(|y| !y.is_nan())
+            .fold(None, |acc, y| match acc {
+                Some((min, max)) => Some((y.min(min), y.max(max))),
+                None => Some((y, y)),
+            })
+    }
+
+    fn iter_many_in_range_f64<'a>(
+        &'a self,
+        handles: Vec<TraceHandle>,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = Vec<f64>> + 'a> {
+        merged_rows_in_range(self, handles, x_range)
+    }
+
+    fn value_at(
+        &self,
+        trace: TraceHandle,
+        x: f64,
+        interpolation_strategy: InterpolationStrategy,
+    ) -> Option<(f64, f64)> {
+        value_at_from_neighbors(self, trace, x, interpolation_strategy)
+    }
+}
+
+/// ### Stitches partitions together along x into a single bundle
+/// * `partitions` are ordered from the oldest to the newest
+/// * `overlap` decides whose points are used where their ranges overlap
+#[wasm_bindgen]
+pub fn chain_bundles(partitions: &BundleVec, overlap: OverlapPolicy) -> Result<BundleRc> {
+    let partitions = partitions.upgrade_all()?;
+    if partitions.is_empty() {
+        return Err(ChartError::EmptyList("partitions"));
+    }
+
+    Ok(BundleRc::new(ChainedBundle::new(partitions, overlap)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChainedBundle, OverlapPolicy};
+    use crate::{
+        trace::{Batch, Bundle, BundleRc, InterpolationStrategy},
+        types::NumericRange,
+    };
+
+    fn partition(x: Vec<f64>, y: f64, handles: &[u32]) -> BundleRc {
+        let ys = vec![y; x.len() * handles.len()];
+        BundleRc::new(Batch::new(x, ys, handles).unwrap())
+    }
+
+    fn chain(overlap: OverlapPolicy) -> ChainedBundle {
+        ChainedBundle::new(
+            vec![
+                partition(vec![0., 1., 2., 3., 4.], 1., &[1, 2]),
+                partition(vec![2.5, 3.5, 4.5, 6.], 2., &[1]),
+            ],
+            overlap,
+        )
+    }
+
+    fn points(bundle: &ChainedBundle, from: f64, to: f64) -> Vec<(f64, f64)> {
+        bundle
+            .iter_in_range_with_neighbors_f64(1, NumericRange::new(from, to))
+            .collect()
+    }
+
+    #[test]
+    fn resolves_overlaps() {
+        let newest = chain(OverlapPolicy::PreferNewest);
+        assert_eq!(
+            points(&newest, 0.5, 5.),
+            vec![
+                (0., 1.),
+                (1., 1.),
+                (2., 1.),
+                (2.5, 2.),
+                (3.5, 2.),
+                (4.5, 2.),
+                (6., 2.)
+            ]
+        );
+
+        let oldest = chain(OverlapPolicy::PreferOldest);
+        assert_eq!(
+            points(&oldest, 0.5, 5.),
+            vec![
+                (0., 1.),
+                (1., 1.),
+                (2., 1.),
+                (3., 1.),
+                (4., 1.),
+                (4.5, 2.),
+                (6., 2.)
+            ]
+        );
+
+        let merged = chain(OverlapPolicy::Merge);
+        assert_eq!(
+            points(&merged, 2., 3.),
+            vec![(2., 1.), (2.5, 2.), (3., 1.), (3.5, 2.)]
+        );
+        assert_eq!(merged.point_count(), 9);
+
+        let partitions: usize = merged.partitions.iter().map(|p| p.memory_footprint()).sum();
+        assert!(merged.memory_footprint() < partitions);
+        assert_eq!(
+            merged.memory_footprint(),
+            merged.partitions.len() * size_of::<BundleRc>()
+        );
+    }
+
+    #[test]
+    fn traces_missing_from_some_partitions() {
+        let bundle = chain(OverlapPolicy::PreferNewest);
+
+        assert_eq!(
+            bundle
+                .iter_in_range_f64(2, NumericRange::new(3., 10.))
+                .collect::<Vec<_>>(),
+            vec![(3., 1.), (4., 1.)]
+        );
+        assert_eq!(
+            bundle.extents_in_range_with_neighbors_f64(1, NumericRange::new(0., 6.)),
+            Some((1., 2.))
+        );
+        assert_eq!(
+            bundle.value_at(1, 5., InterpolationStrategy::Previous),
+            Some((4.5, 2.))
+        );
+        assert_eq!(
+            bundle.value_at(2, 5., InterpolationStrategy::Previous),
+            None
+        );
+    }
+}
@@ -0,0 +1,423 @@
+use std::collections::HashSet;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    data::TraceHandle,
+    error::{ChartError, Result},
+    types::NumericRange,
+};
+
+use super::{common_time_axis, Batch, Bundle, BundleRange, BundleRc, TimeAxis, LOD_MIN_POINTS, N};
+
+/// Samples of some traces copied out of a bundle, one column per trace
+pub(super) struct Columns {
+    pub handles: Vec<TraceHandle>,
+    pub x: Vec<f64>,
+    pub y: Vec<Vec<f64>>,
+}
+
+impl Columns {
+    /// Copies the points of the traces within `x_range`, including the missing ones
+    fn collect(
+        bundle: &BundleRc,
+        handles: Vec<TraceHandle>,
+        x_range: NumericRange,
+    ) -> Result<Self> {
+        let mut x = Vec::new();
+        let mut y = vec![Vec::new(); handles.len()];
+
+        for row in bundle.iter_many_in_range_f64(handles.clone(), x_range) {
+            if row.len() != handles.len() + 1 {
+                return Err(ChartError::LengthMismatch {
+                    what: "values per row",
+                    expected: handles.len() + 1,
+                    actual: row.len(),
+                });
+            }
+
+            x.push(row[0]);
+            for (column, &value) in y.iter_mut().zip(&row[1..]) {
+                column.push(value);
+            }
+        }
+
+        Ok(Self { handles, x, y })
+    }
+
+    /// Copies the whole traces
+    pub fn collect_all(bundle: &BundleRc, handles: Vec<TraceHandle>) -> Result<Self> {
+        let BundleRange::Bounded { from, to } = bundle.range() else {
+            return Err(ChartError::InvalidOption(
+                "a bundle without a bounded range can't be copied whole".to_string(),
+            ));
+        };
+
+        Self::collect(bundle, handles, NumericRange::new(from, to))
+    }
+
+    /// Puts the traces of both side by side, with their x values merged.
+    /// Traces are missing at the x values only the other columns have.
+    fn merge(self, other: Columns) -> Columns {
+        let mut handles = self.handles;
+        handles.extend(other.handles);
+        let mut merged = Columns {
+            handles,
+            x: Vec::with_capacity(self.x.len().max(other.x.len())),
+            y: vec![Vec::new(); self.y.len() + other.y.len()],
+        };
+
+        let (mut i, mut j) = (0, 0);
+        while i < self.x.len() || j < other.x.len() {
+            let x = match (self.x.get(i), other.x.get(j)) {
+                (Some(&a), Some(&b)) => a.min(b),
+                (Some(&a), None) => a,
+                (None, Some(&b)) => b,
+                (None, None) => unreachable!(),
+            };
+            let ours = self.x.get(i) == Some(&x);
+            let theirs = other.x.get(j) == Some(&x);
+
+            merged.x.push(x);
+            let (left, right) = merged.y.split_at_mut(self.y.len());
+            for (target, column) in left.iter_mut().zip(&self.y) {
+                target.push(if ours { column[i] } else { f64::NAN });
+            }
+            for (target, column) in right.iter_mut().zip(&other.y) {
+                target.push(if theirs { column[j] } else { f64::NAN });
+            }
+
+            i += ours as usize;
+            j += theirs as usize;
+        }
+
+        merged
+    }
+
+    /// Appends the points of the other columns.
+    /// Traces only one of them has are missing in the other's part.
+    fn append(mut self, other: Columns) -> Columns {
+        for &handle in &other.handles {
+            if !self.handles.contains(&handle) {
+                self.handles.push(handle);
+                self.y.push(vec![f64::NAN; self.x.len()]);
+            }
+        }
+
+        for (handle, column) in self.handles.iter().zip(&mut self.y) {
+            match other.handles.iter().position(|h| h == handle) {
+                Some(i) => column.extend(&other.y[i]),
+                None => column.resize(column.len() + other.x.len(), f64::NAN),
+            }
+        }
+        self.x.extend(other.x);
+
+        self
+    }
+
+    /// Builds a batch of the columns, with integer timestamps for x if it has a time axis.
+    /// The values are stored as the types named by `types`, see [`Bundle::stored_types`],
+    /// and as `f64` if not given.
+    fn into_bundle(
+        self,
+        time_axis: Option<TimeAxis>,
+        types: Option<(&str, &str)>,
+    ) -> Result<BundleRc> {
+        if !self.x.is_sorted() {
+            return Err(ChartError::UnsortedX);
+        }
+
+        let (x_type, y_type) = types.unwrap_or(("f64", "f64"));
+
+        match time_axis {
+            Some(axis) => {
+                let x = self.x.iter().map(|&x| axis.to_timestamp(x)).collect();
+                with_y_type!(y_type, Y => self.build::<i64, Y>(x, Some(axis)))
+            }
+            None => with_x_type!(x_type, X => {
+                with_y_type!(y_type, Y => self.build::<X, Y>(convert(&self.x), None))
+            }),
+        }
+    }
+
+    fn build<X: N + 'static, Y: N + 'static>(
+        &self,
+        x: Vec<X>,
+        time_axis: Option<TimeAxis>,
+    ) -> Result<BundleRc> {
+        let y = self.y.iter().map(|column| convert(column)).collect();
+
+        let mut batch = Batch::<X, Y>::from_columns(x, y, &self.handles)?;
+        if let Some(axis) = time_axis {
+            batch = batch.with_time_axis(axis);
+        }
+
+        // integers can't hold a NaN, their missing samples are marked separately
+        let float = matches!(Y::NAME, "f32" | "f64");
+        if !float && self.y.iter().flatten().any(|y| y.is_nan()) {
+            let validity = self.y.iter().flatten().map(|y| !y.is_nan()).collect();
+            batch = batch.with_validity(validity)?;
+        }
+
+        if batch.point_count() >= LOD_MIN_POINTS {
+            batch = batch.with_lod();
+        }
+
+        Ok(BundleRc::new(batch))
+    }
+}
+
+/// Converts the values to `T`, values it can't hold become zero
+fn convert<T: N>(values: &[f64]) -> Vec<T> {
+    values
+        .iter()
+        .map(|&v| T::from_f64(v).unwrap_or_else(T::zero))
+        .collect()
+}
+
+/// Evaluates `$body` with `$t` standing for the x type named `$name`. Like the bulkloader,
+/// only a few x types are used, the other ones are widened to the closest of them.
+macro_rules! with_x_type {
+    ( $name:expr, $t:ident => $body:expr ) => {
+        match $name {
+            "u8" | "u16" | "u32" => {
+                type $t = u32;
+                $body
+            }
+            "i8" | "i16" | "i32" | "i64" => {
+                type $t = i64;
+                $body
+            }
+            "u64" => {
+                type $t = u64;
+                $body
+            }
+            "f32" => {
+                type $t = f32;
+                $body
+            }
+            _ => {
+                type $t = f64;
+                $body
+            }
+        }
+    };
+}
+
+/// Evaluates `$body` with `$t` standing for the y type named `$name`, `f64` for unknown names
+macro_rules! with_y_type {
+    ( $name:expr, $t:ident => $body:expr ) => {
+        match $name {
+            "u8" => {
+                type $t = u8;
+                $body
+            }
+            "i8" => {
+                type $t = i8;
+                $body
+            }
+            "u16" => {
+                type $t = u16;
+                $body
+            }
+            "i16" => {
+                type $t = i16;
+                $body
+            }
+            "u32" => {
+                type $t = u32;
+                $body
+            }
+            "i32" => {
+                type $t = i32;
+                $body
+            }
+            "i64" => {
+                type $t = i64;
+                $body
+            }
+            "u64" => {
+                type $t = u64;
+                $body
+            }
+            "f32" => {
+                type $t = f32;
+                $body
+            }
+            _ => {
+                type $t = f64;
+                $body
+            }
+        }
+    };
+}
+
+use {with_x_type, with_y_type};
+
+/// The types to store the columns of two bundles as, each kept if both bundles share it
+fn common_types(a: &BundleRc, b: &BundleRc) -> Option<(&'static str, &'static str)> {
+    let (a_x, a_y) = a.stored_types()?;
+    let (b_x, b_y) = b.stored_types()?;
+    let common = |a: &'static str, b| if a == b { a } else { "f64" };
+
+    Some((common(a_x, b_x), common(a_y, b_y)))
+}
+
+pub(super) fn sorted_traces(bundle: &BundleRc) -> Vec<TraceHandle> {
+    let mut traces = (**bundle).traces();
+    traces.sort_unstable();
+    traces
+}
+
+/// Operations copying the points of bundles into new compact batches,
+/// e.g. to free long histories or to export a subset of the data.
+#[wasm_bindgen]
+impl BundleRc {
+    /// Copies the points of this bundle within `x_range` into a new bundle
+    pub fn slice(&self, x_range: NumericRange) -> Result<BundleRc> {
+        Columns::collect(self, sorted_traces(self), x_range)?
+            .into_bundle(self.time_axis(), self.stored_types())
+    }
+
+    /// Copies the given traces of this bundle into a new bundle
+    pub fn select(&self, traces: &[TraceHandle]) -> Result<BundleRc> {
+        if traces.is_empty() {
+            return Err(ChartError::EmptyList("traces"));
+        }
+
+        let mut seen = HashSet::new();
+        for &trace in traces {
+            if !self.contains_trace(trace) {
+                return Err(ChartError::UnknownTrace(trace));
+            }
+            if !seen.insert(trace) {
+                return Err(ChartError::DuplicateTrace(trace));
+            }
+        }
+
+        Columns::collect_all(self, traces.to_vec())?
+            .into_bundle(self.time_axis(), self.stored_types())
+    }
+
+    /// Copies the traces of this bundle and `other` into one bundle, which mustn't share
+    /// any handles. Where only one of them has a sample, the other's traces are missing.
+    pub fn merge_traces(&self, other: &BundleRc) -> Result<BundleRc> {
+        if let Some(&trace) = other.traces().iter().find(|&&t| self.contains_trace(t)) {
+            return Err(ChartError::DuplicateTrace(trace));
+        }
+
+        let ours = Columns::collect_all(self, sorted_traces(self))?;
+        let theirs = Columns::collect_all(other, sorted_traces(other))?;
+
+        ours.merge(theirs).into_bundle(
+            common_time_axis(&[self.clone(), other.clone()]),
+            common_types(self, other),
+        )
+    }
+
+    /// Copies this bundle followed by `other` into one bundle, `other` has to start after
+    /// this one ends. Traces only one of them has are missing in the other's part.
+    pub fn concat(&self, other: &BundleRc) -> Result<BundleRc> {
+        let ours = Columns::collect_all(self, sorted_traces(self))?;
+        let theirs = Columns::collect_all(other, sorted_traces(other))?;
+
+        if let (Some(last), Some(first)) = (ours.x.last(), theirs.x.first()) {
+            if last >= first {
+                return Err(ChartError::UnsortedX);
+            }
+        }
+
+        ours.append(theirs).into_bundle(
+            common_time_axis(&[self.clone(), other.clone()]),
+            common_types(self, other),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::ChartError,
+        trace::{Batch, BundleRc},
+        types::NumericRange,
+    };
+
+    fn batch(x: Vec<f64>, y: Vec<f64>, handles: &[u32]) -> BundleRc {
+        BundleRc::new(Batch::new(x, y, handles).unwrap())
+    }
+
+    fn points(bundle: &BundleRc, trace: u32) -> Vec<(f64, f64)> {
+        bundle
+            .iter_in_range_f64(trace, NumericRange::new(f64::MIN, f64::MAX))
+            .collect()
+    }
+
+    #[test]
+    fn slices_and_selects() {
+        let bundle = batch(
+            vec![0., 1., 2., 3.],
+            (0..8).map(f64::from).collect(),
+            &[1, 2],
+        );
+
+        let slice = bundle.slice(NumericRange::new(0.5, 2.)).unwrap();
+        assert_eq!(slice.point_count(), 2);
+        assert_eq!(points(&slice, 2), [(1., 5.), (2., 6.)]);
+
+        let selected = bundle.select(&[2]).unwrap();
+        assert_eq!(*selected.traces(), [2]);
+        assert!(matches!(
+            bundle.select(&[2, 2]),
+            Err(ChartError::DuplicateTrace(2))
+        ));
+        assert!(matches!(
+            bundle.select(&[3]),
+            Err(ChartError::UnknownTrace(3))
+        ));
+    }
+
+    #[test]
+    fn merges_and_concatenates() {
+        let first = batch(vec![0., 1.], vec![1., 2.], &[1]);
+        let second = batch(vec![1., 2.], vec![3., 4.], &[2]);
+
+        let merged = first.merge_traces(&second).unwrap();
+        assert_eq!(merged.point_count(), 3);
+        assert_eq!(points(&merged, 1)[..2], [(0., 1.), (1., 2.)]);
+        assert!(points(&merged, 1)[2].1.is_nan());
+        assert!(matches!(
+            first.merge_traces(&first),
+            Err(ChartError::DuplicateTrace(1))
+        ));
+
+        let later = batch(vec![2., 3.], vec![5., 6.], &[1]);
+        let concatenated = first.concat(&later).unwrap();
+        assert_eq!(
+            points(&concatenated, 1),
+            [(0., 1.), (1., 2.), (2., 5.), (3., 6.)]
+        );
+        assert!(matches!(first.concat(&second), Err(ChartError::UnsortedX)));
+    }
+
+    #[test]
+    fn keeps_stored_types() {
+        let bundle =
+            BundleRc::new(Batch::<u32, u8>::new(vec![0, 1, 2], vec![5, 6, 7], &[1]).unwrap());
+        let other = BundleRc::new(Batch::<u32, u8>::new(vec![1, 3], vec![8, 9], &[2]).unwrap());
+
+        let slice = bundle.slice(NumericRange::new(0., 1.)).unwrap();
+        assert_eq!(slice.stored_types(), Some(("u32", "u8")));
+        assert_eq!(points(&slice, 1), [(0., 5.), (1., 6.)]);
+
+        // the samples only the other bundle has are missing
+        let merged = bundle.merge_traces(&other).unwrap();
+        assert_eq!(merged.stored_types(), Some(("u32", "u8")));
+        assert_eq!(points(&merged, 2)[1], (1., 8.));
+        assert!(points(&merged, 2)[0].1.is_nan());
+
+        let floats = batch(vec![5.], vec![1.5], &[1]);
+        let concatenated = bundle.concat(&floats).unwrap();
+        assert_eq!(concatenated.stored_types(), Some(("f64", "f64")));
+        assert_eq!(points(&concatenated, 1)[3], (5., 1.5));
+    }
+}
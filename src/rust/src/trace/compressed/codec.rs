@@ -0,0 +1,250 @@
+//! Bit-level encodings of the Gorilla time series database
+//! (Pelkonen et al., "Gorilla: A Fast, Scalable, In-Memory Time Series Database").
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    /// Number of bits written to the last byte
+    used: u32,
+}
+
+impl BitWriter {
+    fn push_bit(&mut self, bit: bool) {
+        if self.bytes.is_empty() || self.used == 8 {
+            self.bytes.push(0);
+            self.used = 0;
+        }
+        if bit {
+            *self.bytes.last_mut().unwrap() |= 0x80 >> self.used;
+        }
+        self.used += 1;
+    }
+
+    /// Writes the lowest `count` bits of `value`, most significant first
+    fn push_bits(&mut self, value: u64, count: u32) {
+        for i in (0..count).rev() {
+            self.push_bit(value >> i & 1 == 1);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.bytes.shrink_to_fit();
+        self.bytes
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn bit(&mut self) -> bool {
+        let bit = self.bytes[self.position / 8] & (0x80 >> (self.position % 8)) != 0;
+        self.position += 1;
+        bit
+    }
+
+    fn bits(&mut self, count: u32) -> u64 {
+        (0..count).fold(0, |value, _| value << 1 | self.bit() as u64)
+    }
+}
+
+/// `(prefix, prefix length, value bits)` of the delta-of-delta ranges, smallest first
+const DOD_RANGES: [(u64, u32, u32); 4] = [
+    (0b10, 2, 7),
+    (0b110, 3, 9),
+    (0b1110, 4, 12),
+    (0b1111, 4, 64),
+];
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+/// Encodes integers by the change of their differences, which is mostly zero
+/// for regularly sampled timestamps
+pub fn encode_delta_of_delta(values: &[i64]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    let Some(&first) = values.first() else {
+        return writer.finish();
+    };
+    writer.push_bits(first as u64, 64);
+
+    let (mut previous, mut delta) = (first, 0i64);
+    for &value in &values[1..] {
+        let next_delta = value.wrapping_sub(previous);
+        let dod = zigzag(next_delta.wrapping_sub(delta));
+
+        if dod == 0 {
+            writer.push_bit(false);
+        } else {
+            let &(prefix, prefix_len, bits) = DOD_RANGES
+                .iter()
+                .find(|(_, _, bits)| *bits == 64 || dod < 1 << bits)
+                .unwrap();
+            writer.push_bits(prefix, prefix_len);
+            writer.push_bits(dod, bits);
+        }
+
+        (previous, delta) = (value, next_delta);
+    }
+
+    writer.finish()
+}
+
+pub fn decode_delta_of_delta(bytes: &[u8], count: usize) -> Vec<i64> {
+    let mut values = Vec::with_capacity(count);
+    if count == 0 {
+        return values;
+    }
+
+    let mut reader = BitReader::new(bytes);
+    let (mut previous, mut delta) = (reader.bits(64) as i64, 0i64);
+    values.push(previous);
+
+    for _ in 1..count {
+        let dod = match reader.bit() {
+            true => {
+                // the prefix of the largest range has no terminating zero
+                let mut range = 0;
+                while range < DOD_RANGES.len() - 1 && reader.bit() {
+                    range += 1;
+                }
+                unzigzag(reader.bits(DOD_RANGES[range].2))
+            }
+            false => 0,
+        };
+
+        delta = delta.wrapping_add(dod);
+        previous = previous.wrapping_add(delta);
+        values.push(previous);
+    }
+
+    values
+}
+
+/// Encodes floats by XORing them with the previous value, storing only the bits
+/// that differ. Slowly changing or repeated values take just a few bits each.
+pub fn encode_xor(values: &[f64]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    let Some(&first) = values.first() else {
+        return writer.finish();
+    };
+    writer.push_bits(first.to_bits(), 64);
+
+    let mut previous = first.to_bits();
+    // leading and trailing zeros of the last stored difference
+    let mut window: Option<(u32, u32)> = None;
+
+    for value in &values[1..] {
+        let xor = value.to_bits() ^ previous;
+        previous = value.to_bits();
+
+        if xor == 0 {
+            writer.push_bit(false);
+            continue;
+        }
+        writer.push_bit(true);
+
+        let (leading, trailing) = (xor.leading_zeros().min(31), xor.trailing_zeros());
+        match window {
+            Some((l, t)) if leading >= l && trailing >= t => {
+                writer.push_bit(false);
+                writer.push_bits(xor >> t, 64 - l - t);
+            }
+            _ => {
+                let meaningful = 64 - leading - trailing;
+                writer.push_bit(true);
+                writer.push_bits(leading as u64, 5);
+                // 64 meaningful bits don't fit into 6 bits and are stored as 0
+                writer.push_bits(meaningful as u64 & 63, 6);
+                writer.push_bits(xor >> trailing, meaningful);
+                window = Some((leading, trailing));
+            }
+        }
+    }
+
+    writer.finish()
+}
+
+pub fn decode_xor(bytes: &[u8], count: usize) -> Vec<f64> {
+    let mut values = Vec::with_capacity(count);
+    if count == 0 {
+        return values;
+    }
+
+    let mut reader = BitReader::new(bytes);
+    let mut previous = reader.bits(64);
+    values.push(f64::from_bits(previous));
+    let (mut leading, mut trailing) = (0, 0);
+
+    for _ in 1..count {
+        if reader.bit() {
+            if reader.bit() {
+                leading = reader.bits(5) as u32;
+                let meaningful = match reader.bits(6) as u32 {
+                    0 => 64,
+                    meaningful => meaningful,
+                };
+                trailing = 64 - leading - meaningful;
+            }
+            previous ^= reader.bits(64 - leading - trailing) << trailing;
+        }
+        values.push(f64::from_bits(previous));
+    }
+
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_delta_of_delta, decode_xor, encode_delta_of_delta, encode_xor};
+
+    #[test]
+    fn roundtrips() {
+        let timestamps = [
+            1_700_000_000,
+            1_700_000_001,
+            1_700_000_002,
+            1_700_000_010,
+            1_699_000_000,
+            i64::MAX,
+            i64::MIN,
+        ];
+        let encoded = encode_delta_of_delta(&timestamps);
+        assert_eq!(
+            decode_delta_of_delta(&encoded, timestamps.len()),
+            timestamps
+        );
+
+        let regular: Vec<i64> = (0..1000).map(|i| 1_700_000_000 + i * 15).collect();
+        let encoded = encode_delta_of_delta(&regular);
+        assert!(encoded.len() < 8 + 1000 / 8 + 2);
+        assert_eq!(decode_delta_of_delta(&encoded, regular.len()), regular);
+
+        let values = [
+            1.5,
+            1.5,
+            1.25,
+            -3e300,
+            f64::NAN,
+            0.,
+            7.,
+            7.000001,
+            f64::INFINITY,
+        ];
+        let decoded = decode_xor(&encode_xor(&values), values.len());
+        for (decoded, value) in decoded.iter().zip(&values) {
+            assert_eq!(decoded.to_bits(), value.to_bits());
+        }
+    }
+}
@@ -0,0 +1,520 @@
+mod codec;
+
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    data::TraceHandle,
+    error::{ChartError, Result},
+    types::NumericRange,
+};
+
+use self::codec::{decode_delta_of_delta, decode_xor, encode_delta_of_delta, encode_xor};
+use super::{
+    compact::{sorted_traces, Columns},
+    Bundle, BundleRange, BundleRc, InterpolationStrategy, TimeAxis,
+};
+
+/// Number of points per block, which are always decoded together
+const BLOCK_SIZE: usize = 1024;
+
+/// Magnitude up to which every integer is exactly representable as `f64`
+const MAX_EXACT_INTEGER: f64 = (1u64 << 53) as f64;
+
+/// The smallest and largest of the values, ignoring NaNs
+fn extents(values: impl IntoIterator<Item = f64>) -> Option<(f64, f64)> {
+    values
+        .into_iter()
+        .filter(|y| !y.is_nan())
+        .fold(None, |acc, y| match acc {
+            Some((min, max)) => Some((y.min(min), y.max(max))),
+            None => Some((y, y)),
+        })
+}
+
+fn merge_extents(a: Option<(f64, f64)>, b: Option<(f64, f64)>) -> Option<(f64, f64)> {
+    [a, b]
+        .into_iter()
+        .flatten()
+        .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)))
+}
+
+/// A block of x values
+struct XBlock {
+    /// First and last x as exposed by the bundle
+    first: f64,
+    last: f64,
+    /// Set if the values are integers encoded by their delta of delta,
+    /// floats are XOR encoded instead
+    integers: bool,
+    bits: Vec<u8>,
+}
+
+impl XBlock {
+    fn new(x: &[f64], time_axis: Option<TimeAxis>) -> Self {
+        let integers: Option<Vec<i64>> = match time_axis {
+            Some(axis) => Some(x.iter().map(|&x| axis.to_timestamp(x)).collect()),
+            None => x
+                .iter()
+                .all(|x| x.fract() == 0. && x.abs() <= MAX_EXACT_INTEGER)
+                .then(|| x.iter().map(|&x| x as i64).collect()),
+        };
+
+        match integers {
+            Some(values) => Self {
+                first: exposed_x(values[0], time_axis),
+                last: exposed_x(values[values.len() - 1], time_axis),
+                integers: true,
+                bits: encode_delta_of_delta(&values),
+            },
+            None => Self {
+                first: x[0],
+                last: x[x.len() - 1],
+                integers: false,
+                bits: encode_xor(x),
+            },
+        }
+    }
+}
+
+fn exposed_x(value: i64, time_axis: Option<TimeAxis>) -> f64 {
+    match time_axis {
+        Some(axis) => axis.to_x(value),
+        None => value as f64,
+    }
+}
+
+/// A block of one trace's y values
+struct YBlock {
+    extents: Option<(f64, f64)>,
+    first: f64,
+    last: f64,
+    bits: Vec<u8>,
+}
+
+impl YBlock {
+    fn new(y: &[f64]) -> Self {
+        Self {
+            extents: extents(y.iter().copied()),
+            first: y[0],
+            last: y[y.len() - 1],
+            bits: encode_xor(y),
+        }
+    }
+}
+
+/// A batch storing its points compressed in blocks, with x values encoded by their
+/// delta of delta and y values by XOR with the previous one, as in Facebook's Gorilla.
+///
+/// Queries decode only the blocks they touch, and extents of the blocks fully inside
+/// the queried range are answered from their headers.
+pub struct CompressedBatch {
+    len: usize,
+    x: Vec<XBlock>,
+    /// Blocks of one column per trace
+    y: Vec<Vec<YBlock>>,
+    y_idx: HashMap<TraceHandle, usize>,
+
+    /// Set if x is stored as integer timestamps, which are exposed relative to its epoch
+    time_axis: Option<TimeAxis>,
+}
+
+impl CompressedBatch {
+    /// Compresses one column of y per handle. The x values are stored as timestamps
+    /// of `time_axis` if set, and are rounded to them.
+    pub fn from_columns(
+        x: &[f64],
+        y: &[Vec<f64>],
+        handles: &[TraceHandle],
+        time_axis: Option<TimeAxis>,
+    ) -> Result<Self> {
+        if x.is_empty() {
+            return Err(ChartError::NoData);
+        }
+        if y.len() != handles.len() {
+            return Err(ChartError::LengthMismatch {
+                what: "y columns",
+                expected: handles.len(),
+                actual: y.len(),
+            });
+        }
+        if let Some(column) = y.iter().find(|column| column.len() != x.len()) {
+            return Err(ChartError::LengthMismatch {
+                what: "y values per column",
+                expected: x.len(),
+                actual: column.len(),
+            });
+        }
+        if !x.is_sorted() {
+            return Err(ChartError::UnsortedX);
+        }
+
+        Ok(Self {
+            len: x.len(),
+            x: x.chunks(BLOCK_SIZE)
+                .map(|chunk| XBlock::new(chunk, time_axis))
+                .collect(),
+            y: y.iter()
+                .map(|column| column.chunks(BLOCK_SIZE).map(YBlock::new).collect())
+                .collect(),
+            y_idx: HashMap::from_iter(handles.iter().enumerate().map(|(i, handle)| (*handle, i))),
+            time_axis,
+        })
+    }
+
+    fn column_of(&self, trace: TraceHandle) -> Option<usize> {
+        self.y_idx.get(&trace).copied()
+    }
+
+    fn block_len(&self, block: usize) -> usize {
+        BLOCK_SIZE.min(self.len - block * BLOCK_SIZE)
+    }
+
+    /// Indices of the blocks containing the points `from..to`
+    fn blocks_of(from: usize, to: usize) -> std::ops::Range<usize> {
+        match from < to {
+            true => from / BLOCK_SIZE..to.div_ceil(BLOCK_SIZE),
+            false => 0..0,
+        }
+    }
+
+    fn decode_x(&self, block: usize) -> Vec<f64> {
+        let XBlock { integers, bits, .. } = &self.x[block];
+        let count = self.block_len(block);
+
+        match integers {
+            true => decode_delta_of_delta(bits, count)
+                .into_iter()
+                .map(|x| exposed_x(x, self.time_axis))
+                .collect(),
+            false => decode_xor(bits, count),
+        }
+    }
+
+    fn decode_y(&self, column: usize, block: usize) -> Vec<f64> {
+        decode_xor(&self.y[column][block].bits, self.block_len(block))
+    }
+
+    /// The `i`-th point of a column, taken from the block headers if it's at an edge of its block
+    fn point_at(&self, column: usize, i: usize) -> (f64, f64) {
+        let (block, index) = (i / BLOCK_SIZE, i % BLOCK_SIZE);
+        let (x, y) = (&self.x[block], &self.y[column][block]);
+
+        if index == 0 {
+            (x.first, y.first)
+        } else if index == self.block_len(block) - 1 {
+            (x.last, y.last)
+        } else {
+            (
+                self.decode_x(block)[index],
+                self.decode_y(column, block)[index],
+            )
+        }
+    }
+
+    /// Points of a column at the indices `from..to`, decoding a block at a time
+    fn points(
+        &self,
+        column: usize,
+        from: usize,
+        to: usize,
+    ) -> impl Iterator<Item = (f64, f64)> + '_ {
+        Self::blocks_of(from, to).flat_map(move |block| {
+            let offset = block * BLOCK_SIZE;
+            let start = from.saturating_sub(offset);
+            let end = (to - offset).min(self.block_len(block));
+
+            self.decode_x(block)
+                .into_iter()
+                .zip(self.decode_y(column, block))
+                .take(end)
+                .skip(start)
+        })
+    }
+
+    /// Binary searches the x values like [`Batch`](super::Batch) does, decoding only
+    /// the block found by the headers
+    fn search_x(&self, value: f64) -> Result<usize, usize> {
+        let block = self
+            .x
+            .partition_point(|block| block.last.total_cmp(&value).is_lt());
+        if block == self.x.len() {
+            return Err(self.len);
+        }
+
+        let offset = block * BLOCK_SIZE;
+        match self
+            .decode_x(block)
+            .binary_search_by(|x| x.total_cmp(&value))
+        {
+            Ok(i) => Ok(offset + i),
+            Err(i) => Err(offset + i),
+        }
+    }
+
+    /// Returns the span of indices visited by `iter_in_range_with_neighbors_f64`
+    fn neighbors_span(&self, x_range: NumericRange) -> Option<(usize, usize)> {
+        let from = match self.search_x(x_range.from) {
+            Ok(i) => i,
+            Err(0) => 0,
+            Err(i) if i == self.len => return None,
+            Err(i) => i - 1,
+        };
+
+        let to = match self.search_x(x_range.to) {
+            Ok(i) if i >= from => i + 1,
+            // the point after x_range.to is the neighbor
+            Err(i) if i > from => i + 1,
+            _ => return None,
+        };
+
+        Some((from, to.min(self.len)))
+    }
+}
+
+impl Bundle for CompressedBatch {
+    fn traces(&self) -> Vec<TraceHandle> {
+        self.y_idx.keys().copied().collect()
+    }
+
+    fn contains_trace(&self, trace: TraceHandle) -> bool {
+        self.y_idx.contains_key(&trace)
+    }
+
+    fn range(&self) -> BundleRange {
+        BundleRange::Bounded {
+            from: self.x[0].first,
+            to: self.x[self.x.len() - 1].last,
+        }
+    }
+
+    fn time_axis(&self) -> Option<TimeAxis> {
+        self.time_axis
+    }
+
+    fn memory_footprint(&self) -> usize {
+        let x: usize = self.x.iter().map(|block| block.bits.capacity()).sum();
+        let y: usize = self
+            .y
+            .iter()
+            .map(|column| {
+                column.capacity() * size_of::<YBlock>()
+                    + column
+                        .iter()
+                        .map(|block| block.bits.capacity())
+                        .sum::<usize>()
+            })
+            .sum();
+
+        self.x.capacity() * size_of::<XBlock>()
+            + x
+            + self.y.capacity() * size_of::<Vec<YBlock>>()
+            + y
+            + self.y_idx.capacity() * size_of::<(TraceHandle, usize)>()
+    }
+
+    fn point_count(&self) -> usize {
+        self.len
+    }
+
+    fn iter_in_range_f64<'a>(
+        &'a self,
+        trace: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        let Some(column) = self.column_of(trace) else {
+            return Box::new(std::iter::empty());
+        };
+
+        let from = match self.search_x(x_range.from) {
+            Ok(i) | Err(i) => i,
+        };
+
+        Box::new(
+            self.points(column, from, self.len)
+                .take_while(move |&(x, _)| x <= x_range.to),
+        )
+    }
+
+    fn iter_in_range_with_neighbors_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        let Some(column) = self.column_of(handle) else {
+            return Box::new(std::iter::empty());
+        };
+        let Some((from, to)) = self.neighbors_span(x_range) else {
+            return Box::new(std::iter::empty());
+        };
+
+        Box::new(self.points(column, from, to))
+    }
+
+    fn extents_in_range_with_neighbors_f64(
+        &self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Option<(f64, f64)> {
+        let column = self.column_of(handle)?;
+        let (from, to) = self.neighbors_span(x_range)?;
+
+        Self::blocks_of(from, to).fold(None, |acc, block| {
+            let offset = block * BLOCK_SIZE;
+            let len = self.block_len(block);
+
+            let block_extents = match from <= offset && offset + len <= to {
+                true => self.y[column][block].extents,
+                false => {
+                    let y = self.decode_y(column, block);
+                    extents(
+                        y[from.saturating_sub(offset)..(to - offset).min(len)]
+                            .iter()
+                            .copied(),
+                    )
+                }
+            };
+
+            merge_extents(acc, block_extents)
+        })
+    }
+
+    fn iter_many_in_range_f64<'a>(
+        &'a self,
+        traces: Vec<TraceHandle>,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = Vec<f64>> + 'a> {
+        let columns: Vec<_> = traces.into_iter().map(|t| self.column_of(t)).collect();
+        let from = match self.search_x(x_range.from) {
+            Ok(i) | Err(i) => i,
+        };
+
+        let rows = Self::blocks_of(from, self.len).flat_map(move |block| {
+            let x = self.decode_x(block);
+            // traces missing from the bundle are missing at every x
+            let y: Vec<_> = columns
+                .iter()
+                .map(|column| column.map(|column| self.decode_y(column, block)))
+                .collect();
+
+            (from.saturating_sub(block * BLOCK_SIZE)..x.len()).map(move |i| {
+                let mut row = Vec::with_capacity(y.len() + 1);
+                row.push(x[i]);
+                row.extend(y.iter().map(|y| y.as_ref().map_or(f64::NAN, |y| y[i])));
+                row
+            })
+        });
+
+        Box::new(rows.take_while(move |row| row[0] <= x_range.to))
+    }
+
+    fn value_at(
+        &self,
+        handle: TraceHandle,
+        x: f64,
+        strategy: InterpolationStrategy,
+    ) -> Option<(f64, f64)> {
+        if !self.contains_point(x) {
+            return None;
+        }
+
+        let column = self.column_of(handle)?;
+
+        match self.search_x(x) {
+            Err(0) => None,
+            Err(i) if i == self.len => None,
+            Ok(i) => Some((x, self.point_at(column, i).1)).filter(|(_, y)| !y.is_nan()),
+            Err(i) => {
+                strategy.interpolate(x, self.point_at(column, i - 1), self.point_at(column, i))
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl BundleRc {
+    /// Copies this bundle into a compressed one, which takes less memory
+    /// but has to decode the points whenever they're queried
+    pub fn compress(&self) -> Result<BundleRc> {
+        let columns = Columns::collect_all(self, sorted_traces(self))?;
+
+        Ok(BundleRc::new(CompressedBatch::from_columns(
+            &columns.x,
+            &columns.y,
+            &columns.handles,
+            self.time_axis(),
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CompressedBatch, BLOCK_SIZE};
+    use crate::{
+        trace::{Batch, Bundle, InterpolationStrategy},
+        types::NumericRange,
+    };
+
+    #[test]
+    fn matches_batch() {
+        let len = 3 * BLOCK_SIZE + 100;
+        let y: Vec<Vec<f64>> = [1., 2.]
+            .map(|scale| {
+                (0..len)
+                    .map(|i| match i % 700 {
+                        13 => f64::NAN,
+                        _ => ((i as f64 / 50.).sin() * 100.).round() * scale,
+                    })
+                    .collect()
+            })
+            .to_vec();
+
+        for step in [15., 0.25] {
+            let x: Vec<f64> = (0..len).map(|i| 1000. + i as f64 * step).collect();
+            let batch = Batch::from_columns(x.clone(), y.clone(), &[1, 2]).unwrap();
+            let compressed = CompressedBatch::from_columns(&x, &y, &[1, 2], None).unwrap();
+            assert!(compressed.memory_footprint() < batch.memory_footprint() / 2);
+
+            for (from, to) in [
+                (0., 1e9),
+                (x[5], x[5]),
+                (x[1023] + step / 2., x[2050]),
+                (x[9], x[9] - 1.),
+            ] {
+                let range = NumericRange::new(from, to);
+                let points = |bundle: &dyn Bundle| {
+                    let mut points: Vec<_> = bundle.iter_in_range_f64(2, range).collect();
+                    points.extend(bundle.iter_in_range_with_neighbors_f64(2, range));
+                    format!("{points:?}")
+                };
+                assert_eq!(points(&compressed), points(&batch));
+                assert_eq!(
+                    compressed.extents_in_range_with_neighbors_f64(1, range),
+                    batch.extents_in_range_with_neighbors_f64(1, range)
+                );
+                assert_eq!(
+                    format!(
+                        "{:?}",
+                        compressed
+                            .iter_many_in_range_f64(vec![2, 3], range)
+                            .collect::<Vec<_>>()
+                    ),
+                    format!(
+                        "{:?}",
+                        batch
+                            .iter_many_in_range_f64(vec![2, 3], range)
+                            .collect::<Vec<_>>()
+                    )
+                );
+            }
+
+            for at in [x[0], x[1024], x[2000] + step / 3.] {
+                assert_eq!(
+                    compressed.value_at(1, at, InterpolationStrategy::Linear),
+                    batch.value_at(1, at, InterpolationStrategy::Linear)
+                );
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, vec};
+use std::collections::HashMap;
 
 use crate::{data::TraceHandle, types::NumericRange};
 
@@ -32,6 +32,15 @@ impl<Y: N> Bundle for ConstantBatch<Y> {
         2
     }
 
+    fn memory_footprint(&self) -> usize {
+        self.ys.capacity() * size_of::<(TraceHandle, Y)>()
+    }
+
+    fn stored_types(&self) -> Option<(&'static str, &'static str)> {
+        // there are no x values to store
+        Some(("f64", Y::NAME))
+    }
+
     fn iter_in_range_f64<'a>(
         &'a self,
         handle: crate::data::TraceHandle,
@@ -61,16 +70,13 @@ impl<Y: N> Bundle for ConstantBatch<Y> {
         handles: Vec<crate::data::TraceHandle>,
         x_range: NumericRange,
     ) -> Box<dyn Iterator<Item = Vec<f64>> + 'a> {
-        let mut ys: Vec<f64> = handles
+        // traces missing from the bundle are missing at every x
+        let ys = handles
             .into_iter()
-            .map(|handle| self.ys.get(&handle))
-            .filter(|y| y.is_some())
-            .map(|y| y.unwrap().as_f64())
-            .collect();
-        let mut from = vec![x_range.from];
-        from.append(&mut ys.clone());
-        let mut to = vec![x_range.to];
-        to.append(&mut ys);
+            .map(|handle| self.ys.get(&handle).map_or(f64::NAN, N::as_f64));
+        let from = std::iter::once(x_range.from).chain(ys.clone()).collect();
+        let to = std::iter::once(x_range.to).chain(ys).collect();
+
         Box::new([from, to].into_iter())
     }
 
@@ -83,3 +89,23 @@ impl<Y: N> Bundle for ConstantBatch<Y> {
         self.ys.get(&trace).map(|y| (x, y.as_f64()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::ConstantBatch;
+    use crate::{trace::Bundle, types::NumericRange};
+
+    #[test]
+    fn rows_have_a_value_per_handle() {
+        let batch = ConstantBatch::new(HashMap::from([(1, 5.)]));
+        let rows: Vec<_> = batch
+            .iter_many_in_range_f64(vec![2, 1], NumericRange::new(0., 1.))
+            .collect();
+
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0][1].is_nan());
+        assert_eq!((rows[1][0], rows[1][2]), (1., 5.));
+    }
+}
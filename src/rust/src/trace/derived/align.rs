@@ -0,0 +1,63 @@
+use std::iter::Peekable;
+
+use crate::trace::InterpolationStrategy;
+
+pub(crate) type Points<'a> = Box<dyn Iterator<Item = (f64, f64)> + 'a>;
+
+struct Input<'a> {
+    points: Peekable<Points<'a>>,
+    /// The last point taken from `points`
+    left: Option<(f64, f64)>,
+}
+
+/// Walks the points of several traces at once, stopping at every x where one of them
+/// has a sample. The others are interpolated there, and are NaN where they can't be.
+pub(crate) struct AlignedPoints<'a> {
+    inputs: Vec<Input<'a>>,
+    interpolation: InterpolationStrategy,
+}
+
+impl<'a> AlignedPoints<'a> {
+    pub fn new(
+        inputs: impl IntoIterator<Item = Points<'a>>,
+        interpolation: InterpolationStrategy,
+    ) -> Self {
+        Self {
+            inputs: inputs
+                .into_iter()
+                .map(|points| Input {
+                    points: points.peekable(),
+                    left: None,
+                })
+                .collect(),
+            interpolation,
+        }
+    }
+
+    /// Advances to the next x, writing the values of the traces there into `values`
+    pub fn next_into(&mut self, values: &mut [f64]) -> Option<f64> {
+        let x = self
+            .inputs
+            .iter_mut()
+            .filter_map(|input| input.points.peek().map(|(x, _)| *x))
+            .min_by(f64::total_cmp)?;
+
+        for (input, value) in self.inputs.iter_mut().zip(values.iter_mut()) {
+            *value = match input.points.next_if(|(xi, _)| *xi == x) {
+                Some(point) => {
+                    input.left = Some(point);
+                    point.1
+                }
+                None => match (input.left, input.points.peek()) {
+                    (Some(left), Some(&right)) => self
+                        .interpolation
+                        .interpolate(x, left, right)
+                        .map_or(f64::NAN, |(_, y)| y),
+                    _ => f64::NAN,
+                },
+            };
+        }
+
+        Some(x)
+    }
+}
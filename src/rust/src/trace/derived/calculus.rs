@@ -0,0 +1,374 @@
+// https://github.com/madonoharu/tsify/issues/42
+#![allow(non_snake_case)]
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    data::TraceHandle,
+    error::{ChartError, Result},
+    trace::{
+        merged_rows_in_range, value_at_from_neighbors, Bundle, BundleRange, BundleRc,
+        InterpolationStrategy, TimeAxis,
+    },
+    types::NumericRange,
+};
+
+#[derive(Tsify, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "kebab-case")]
+pub enum CalculusKind {
+    /// Increase of a monotonic counter per `unit` of x, ignoring its resets
+    Rate,
+    /// Change of the value per `unit` of x
+    Derivative,
+    /// Sum of all the values up to the point
+    CumulativeSum,
+    /// Trapezoidal integral from the start of the trace, in `unit`s of x
+    Integral,
+}
+
+#[derive(Tsify, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct CalculusSpec {
+    pub kind: CalculusKind,
+    /// Span of x the rates are given per, e.g. 60 for per minute when x is in seconds
+    #[serde(default)]
+    pub unit: Option<f64>,
+    /// Value at which a counter wraps around to zero, e.g. 2^32 for 32-bit counters.
+    /// Without it, every decrease of a counter is taken as a reset.
+    #[serde(default)]
+    pub wrap_at: Option<f64>,
+}
+
+/// Running totals of a whole trace
+struct Accumulated {
+    version: u64,
+    x: Vec<f64>,
+    y: Vec<f64>,
+}
+
+/// A bundle of the rates, derivatives or running totals of another bundle's traces.
+///
+/// Rates and derivatives are placed at the end of the interval they are computed over, so
+/// the first sample of a trace has none. Running totals are computed for the whole trace
+/// when it is first queried and cached until the source changes.
+pub struct CalculusBundle {
+    source: BundleRc,
+    spec: CalculusSpec,
+    accumulated: RefCell<HashMap<TraceHandle, Rc<Accumulated>>>,
+}
+
+impl CalculusBundle {
+    pub fn new(source: BundleRc, spec: CalculusSpec) -> Result<Self> {
+        if let Some(unit) = spec.unit {
+            if !(unit.is_finite() && unit > 0.) {
+                return Err(ChartError::InvalidOption(format!(
+                    "unit {unit} is not positive"
+                )));
+            }
+        }
+        if let Some(wrap_at) = spec.wrap_at {
+            if !(wrap_at.is_finite() && wrap_at > 0.) {
+                return Err(ChartError::InvalidOption(format!(
+                    "wraparound value {wrap_at} is not positive"
+                )));
+            }
+        }
+
+        Ok(Self {
+            source,
+            spec,
+            accumulated: RefCell::new(HashMap::new()),
+        })
+    }
+
+    fn unit(&self) -> f64 {
+        self.spec.unit.unwrap_or(1.)
+    }
+
+    /// Increase of a counter from `before` to `after`.
+    ///
+    /// A decrease is a wraparound if the counter went from the upper half of its range
+    /// to the lower one, and a reset to zero otherwise.
+    fn counter_increase(&self, before: f64, after: f64) -> f64 {
+        if after >= before {
+            return after - before;
+        }
+
+        match self.spec.wrap_at {
+            Some(wrap_at) if before >= wrap_at / 2. && after < wrap_at / 2. => {
+                wrap_at - before + after
+            }
+            _ => after,
+        }
+    }
+
+    /// Rates or derivatives at the points of the source from `a` to `b`,
+    /// missing at a missing sample and at the one after it
+    fn differentiate(&self, trace: TraceHandle, a: f64, b: f64) -> Vec<(f64, f64)> {
+        let before_a = a.next_down();
+        let start = self
+            .source
+            .iter_in_range_with_neighbors_f64(trace, NumericRange::new(before_a, before_a))
+            .next()
+            .map_or(a, |(x, _)| x.min(a));
+
+        let points: Vec<_> = self
+            .source
+            .iter_in_range_f64(trace, NumericRange::new(start, b))
+            .collect();
+
+        points
+            .windows(2)
+            .filter(|pair| pair[1].0 >= a)
+            .map(|pair| {
+                let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+                if y0.is_nan() || y1.is_nan() {
+                    return (x1, f64::NAN);
+                }
+
+                let change = match self.spec.kind {
+                    CalculusKind::Rate => self.counter_increase(y0, y1),
+                    _ => y1 - y0,
+                };
+
+                (x1, change / (x1 - x0) * self.unit())
+            })
+            .collect()
+    }
+
+    /// Running totals of the whole trace, computed when the source has changed
+    fn accumulated(&self, trace: TraceHandle) -> Rc<Accumulated> {
+        let version = self.source.version();
+        if let Some(cached) = self.accumulated.borrow().get(&trace) {
+            if cached.version == version {
+                return cached.clone();
+            }
+        }
+
+        let (from, to) = self.source.range().bounds();
+        let points = self
+            .source
+            .iter_in_range_f64(trace, NumericRange::new(from, to));
+
+        let (mut x, mut y) = (Vec::new(), Vec::new());
+        let mut total = 0.;
+        let mut last: Option<(f64, f64)> = None;
+
+        for (xi, yi) in points {
+            if !yi.is_nan() {
+                total += match (self.spec.kind, last) {
+                    (CalculusKind::Integral, Some((x0, y0))) if !y0.is_nan() => {
+                        (yi + y0) / 2. * (xi - x0) / self.unit()
+                    }
+                    (CalculusKind::Integral, _) => 0.,
+                    _ => yi,
+                };
+            }
+            last = Some((xi, yi));
+
+            x.push(xi);
+            y.push(if yi.is_nan() { f64::NAN } else { total });
+        }
+
+        let accumulated = Rc::new(Accumulated { version, x, y });
+        self.accumulated
+            .borrow_mut()
+            .insert(trace, accumulated.clone());
+
+        accumulated
+    }
+
+    /// Transformed points of the source returned by its `iter_in_range_with_neighbors_f64`
+    fn transformed(&self, trace: TraceHandle, x_range: NumericRange) -> Vec<(f64, f64)> {
+        if !self.source.contains_trace(trace) {
+            return Vec::new();
+        }
+
+        match self.spec.kind {
+            CalculusKind::Rate | CalculusKind::Derivative => {
+                let edge = |x: f64| {
+                    self.source
+                        .iter_in_range_with_neighbors_f64(trace, NumericRange::new(x, x))
+                        .map(|(x, _)| x)
+                };
+                match (edge(x_range.from).next(), edge(x_range.to).last()) {
+                    (Some(a), Some(b)) => self.differentiate(trace, a, b),
+                    _ => Vec::new(),
+                }
+            }
+            CalculusKind::CumulativeSum | CalculusKind::Integral => {
+                let accumulated = self.accumulated(trace);
+                let x = &accumulated.x;
+
+                let mut first = x.partition_point(|x| *x < x_range.from);
+                if x.get(first) != Some(&x_range.from) {
+                    first = first.saturating_sub(1);
+                }
+                let mut last = x.partition_point(|x| *x <= x_range.to);
+                if last == 0 || x[last - 1] != x_range.to {
+                    last = (last + 1).min(x.len());
+                }
+
+                (first..last.max(first))
+                    .map(|i| (x[i], accumulated.y[i]))
+                    .collect()
+            }
+        }
+    }
+}
+
+impl Bundle for CalculusBundle {
+    fn traces(&self) -> Vec<TraceHandle> {
+        (*self.source).traces()
+    }
+
+    fn range(&self) -> BundleRange {
+        self.source.range()
+    }
+
+    fn point_count(&self) -> usize {
+        self.source.point_count()
+    }
+
+    fn contains_trace(&self, trace: TraceHandle) -> bool {
+        self.source.contains_trace(trace)
+    }
+
+    fn version(&self) -> u64 {
+        self.source.version()
+    }
+
+    fn time_axis(&self) -> Option<TimeAxis> {
+        self.source.time_axis()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        let accumulated = self.accumulated.borrow();
+        let totals: usize = accumulated
+            .values()
+            .map(|a| (a.x.capacity() + a.y.capacity()) * size_of::<f64>())
+            .sum();
+
+        totals + accumulated.capacity() * size_of::<(TraceHandle, Rc<Accumulated>)>()
+    }
+
+    fn iter_in_range_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        Box::new(
+            self.transformed(handle, x_range)
+                .into_iter()
+                .filter(move |(x, _)| x_range.from <= *x && *x <= x_range.to),
+        )
+    }
+
+    fn iter_in_range_with_neighbors_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        Box::new(self.transformed(handle, x_range).into_iter())
+    }
+
+    fn iter_many_in_range_f64<'a>(
+        &'a self,
+        handles: Vec<TraceHandle>,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = Vec<f64>> + 'a> {
+        merged_rows_in_range(self, handles, x_range)
+    }
+
+    fn value_at(
+        &self,
+        trace: TraceHandle,
+        x: f64,
+        interpolation_strategy: InterpolationStrategy,
+    ) -> Option<(f64, f64)> {
+        value_at_from_neighbors(self, trace, x, interpolation_strategy)
+    }
+}
+
+#[wasm_bindgen]
+impl BundleRc {
+    /// Creates a bundle with the rates, derivatives or running totals of this one's traces
+    pub fn calculus(&self, spec: CalculusSpec) -> Result<BundleRc> {
+        Ok(BundleRc::new(CalculusBundle::new(self.clone(), spec)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CalculusBundle, CalculusKind, CalculusSpec};
+    use crate::{
+        trace::{Batch, Bundle, BundleRc, InterpolationStrategy},
+        types::NumericRange,
+    };
+
+    fn transform(kind: CalculusKind, y: Vec<f64>, wrap_at: Option<f64>) -> CalculusBundle {
+        let x = (0..y.len()).map(|i| i as f64 * 10.).collect();
+        let source = BundleRc::new(Batch::new(x, y, &[1]).unwrap());
+
+        let spec = CalculusSpec {
+            kind,
+            unit: Some(60.),
+            wrap_at,
+        };
+        CalculusBundle::new(source, spec).unwrap()
+    }
+
+    fn values(bundle: &CalculusBundle, from: f64, to: f64) -> Vec<f64> {
+        bundle
+            .iter_in_range_with_neighbors_f64(1, NumericRange::new(from, to))
+            .map(|(_, y)| y)
+            .collect()
+    }
+
+    #[test]
+    fn counter_resets_and_wraparounds() {
+        let counter = vec![100., 110., 130., 5., 250., 10.];
+
+        let rate = transform(CalculusKind::Rate, counter.clone(), None);
+        assert_eq!(values(&rate, 0., 50.), [60., 120., 30., 1470., 60.]);
+        assert_eq!(values(&rate, 25., 35.), [120., 30., 1470.]);
+
+        let wrapping = transform(CalculusKind::Rate, counter.clone(), Some(256.));
+        assert_eq!(values(&wrapping, 45., 50.), [1470., 96.]);
+
+        let derivative = transform(CalculusKind::Derivative, counter, None);
+        assert_eq!(values(&derivative, 30., 30.), [-750.]);
+
+        // a gap isn't a reset
+        let gap = transform(CalculusKind::Rate, vec![100., f64::NAN, 110., 130.], None);
+        let rates = values(&gap, 0., 30.);
+        assert!(rates[0].is_nan() && rates[1].is_nan());
+        assert_eq!(rates[2], 120.);
+    }
+
+    #[test]
+    fn running_totals_start_at_the_trace() {
+        let y = vec![1., 2., f64::NAN, 4., 3.];
+
+        let sum = transform(CalculusKind::CumulativeSum, y.clone(), None);
+        assert_eq!(sum.memory_footprint(), 0);
+        assert_eq!(values(&sum, 5., 10.), [1., 3.]);
+        // the running totals of the whole trace are cached
+        assert!(sum.memory_footprint() >= 2 * 5 * size_of::<f64>());
+        assert_eq!(values(&sum, 30., 40.), [7., 10.]);
+        assert!(values(&sum, 20., 20.)[0].is_nan());
+
+        let integral = transform(CalculusKind::Integral, y, None);
+        assert_eq!(values(&integral, 30., 30.), [0.25]);
+        let (_, area) = integral
+            .value_at(1, 40., InterpolationStrategy::None)
+            .unwrap();
+        assert!((area - 0.25 - 35. / 60.).abs() < 1e-12);
+    }
+}
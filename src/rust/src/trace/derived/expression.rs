@@ -0,0 +1,389 @@
+use crate::error::{ChartError, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Abs,
+    Sqrt,
+    Exp,
+    Ln,
+    Log10,
+    Min,
+    Max,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "abs" => Function::Abs,
+            "sqrt" => Function::Sqrt,
+            "exp" => Function::Exp,
+            "ln" => Function::Ln,
+            "log10" => Function::Log10,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            _ => return None,
+        })
+    }
+
+    fn takes(self, args: usize) -> bool {
+        match self {
+            Function::Min | Function::Max => args >= 1,
+            _ => args == 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f64),
+    /// Index into [`Expression::variables`]
+    Variable(usize),
+    Negate(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    /// Left associative operators applied in order, e.g. `a - b + c`. Kept flat rather
+    /// than nested, so that long chains don't make evaluating and dropping recurse deeply.
+    Chain(Box<Node>, Vec<(BinaryOp, Node)>),
+    Call(Function, Vec<Node>),
+}
+
+impl BinaryOp {
+    fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            BinaryOp::Add => a + b,
+            BinaryOp::Sub => a - b,
+            BinaryOp::Mul => a * b,
+            BinaryOp::Div => a / b,
+            BinaryOp::Rem => a % b,
+            BinaryOp::Pow => a.powf(b),
+        }
+    }
+}
+
+impl Node {
+    fn eval(&self, values: &[f64]) -> f64 {
+        match self {
+            Node::Number(n) => *n,
+            Node::Variable(i) => values[*i],
+            Node::Negate(node) => -node.eval(values),
+            Node::Binary(op, a, b) => op.apply(a.eval(values), b.eval(values)),
+            Node::Chain(first, rest) => rest
+                .iter()
+                .fold(first.eval(values), |a, (op, b)| op.apply(a, b.eval(values))),
+            Node::Call(function, args) => {
+                let mut args = args.iter().map(|arg| arg.eval(values));
+                match function {
+                    Function::Abs => args.next().unwrap().abs(),
+                    Function::Sqrt => args.next().unwrap().sqrt(),
+                    Function::Exp => args.next().unwrap().exp(),
+                    Function::Ln => args.next().unwrap().ln(),
+                    Function::Log10 => args.next().unwrap().log10(),
+                    // f64::min and f64::max would skip missing values instead of propagating them
+                    Function::Min => args
+                        .reduce(|a, b| if a.is_nan() || a < b { a } else { b })
+                        .unwrap(),
+                    Function::Max => args
+                        .reduce(|a, b| if a.is_nan() || a > b { a } else { b })
+                        .unwrap(),
+                }
+            }
+        }
+    }
+}
+
+/// An arithmetic expression over named variables, e.g. `errors / requests * 100`.
+///
+/// Supports numbers, `+ - * / % ^`, parentheses and the functions
+/// `abs`, `sqrt`, `exp`, `ln`, `log10`, `min` and `max`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    root: Node,
+    variables: Vec<String>,
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut parser = Parser {
+            text,
+            pos: 0,
+            depth: 0,
+            variables: Vec::new(),
+        };
+
+        let root = parser.sum()?;
+        if parser.peek().is_some() {
+            return Err(parser.error("expected an operator"));
+        }
+
+        Ok(Self {
+            root,
+            variables: parser.variables,
+        })
+    }
+
+    /// Names of the variables in the order of their first occurrence
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    /// Evaluates the expression with `values` of its [`Expression::variables`]
+    pub fn eval(&self, values: &[f64]) -> f64 {
+        self.root.eval(values)
+    }
+}
+
+fn chain(first: Node, rest: Vec<(BinaryOp, Node)>) -> Node {
+    if rest.is_empty() {
+        first
+    } else {
+        Node::Chain(Box::new(first), rest)
+    }
+}
+
+/// Nesting deeper than this is rejected rather than overflowing the stack
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    /// Number of nested parentheses, calls, signs and exponents being parsed
+    depth: usize,
+    variables: Vec<String>,
+}
+
+impl Parser<'_> {
+    fn error(&self, what: &str) -> ChartError {
+        ChartError::Expression(format!(
+            "{what} at position {} of `{}`",
+            self.pos, self.text
+        ))
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+
+        self.text[self.pos..].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += c.len_utf8();
+        }
+
+        found
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &str {
+        let start = self.pos;
+        let rest = &self.text[start..];
+        self.pos += rest.find(|c| !f(c)).unwrap_or(rest.len());
+
+        &self.text[start..self.pos]
+    }
+
+    fn sum(&mut self) -> Result<Node> {
+        let first = self.product()?;
+        let mut rest = Vec::new();
+        loop {
+            let op = if self.eat('+') {
+                BinaryOp::Add
+            } else if self.eat('-') {
+                BinaryOp::Sub
+            } else {
+                return Ok(chain(first, rest));
+            };
+            rest.push((op, self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Node> {
+        let first = self.unary()?;
+        let mut rest = Vec::new();
+        loop {
+            let op = if self.eat('*') {
+                BinaryOp::Mul
+            } else if self.eat('/') {
+                BinaryOp::Div
+            } else if self.eat('%') {
+                BinaryOp::Rem
+            } else {
+                return Ok(chain(first, rest));
+            };
+            rest.push((op, self.unary()?));
+        }
+    }
+
+    /// Every nested part of the expression is parsed through here, so this is where
+    /// the depth is limited
+    fn unary(&mut self) -> Result<Node> {
+        if self.depth == MAX_DEPTH {
+            return Err(ChartError::Expression(
+                "expression is nested too deeply".into(),
+            ));
+        }
+        self.depth += 1;
+
+        let node = if self.eat('-') {
+            self.unary().map(|node| Node::Negate(Box::new(node)))
+        } else if self.eat('+') {
+            self.unary()
+        } else {
+            self.power()
+        };
+
+        self.depth -= 1;
+        node
+    }
+
+    /// Exponentiation is right associative and binds tighter than the unary minus on its left
+    fn power(&mut self) -> Result<Node> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            Ok(Node::Binary(
+                BinaryOp::Pow,
+                Box::new(base),
+                Box::new(self.unary()?),
+            ))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn atom(&mut self) -> Result<Node> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let node = self.sum()?;
+                if !self.eat(')') {
+                    return Err(self.error("expected `)`"));
+                }
+                Ok(node)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some(c) if c.is_alphabetic() || c == '_' => self.identifier(),
+            Some(c) => Err(self.error(&format!("unexpected `{c}`"))),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Node> {
+        let start = self.pos;
+        self.take_while(|c| c.is_ascii_digit() || c == '.');
+
+        let rest = &self.text[self.pos..];
+        let exponent = rest
+            .strip_prefix(['e', 'E'])
+            .map(|e| e.strip_prefix(['+', '-']).unwrap_or(e));
+        if exponent.is_some_and(|e| e.starts_with(|c: char| c.is_ascii_digit())) {
+            self.pos += rest.len() - exponent.unwrap().len();
+            self.take_while(|c| c.is_ascii_digit());
+        }
+
+        match self.text[start..self.pos].parse() {
+            Ok(n) => Ok(Node::Number(n)),
+            Err(_) => {
+                self.pos = start;
+                Err(self.error("invalid number"))
+            }
+        }
+    }
+
+    fn identifier(&mut self) -> Result<Node> {
+        let start = self.pos;
+        let name = self
+            .take_while(|c| c.is_alphanumeric() || c == '_')
+            .to_string();
+
+        if !self.eat('(') {
+            let index = match self.variables.iter().position(|v| *v == name) {
+                Some(index) => index,
+                None => {
+                    self.variables.push(name);
+                    self.variables.len() - 1
+                }
+            };
+            return Ok(Node::Variable(index));
+        }
+
+        let Some(function) = Function::from_name(&name) else {
+            self.pos = start;
+            return Err(self.error(&format!("unknown function `{name}`")));
+        };
+
+        let mut args = Vec::new();
+        if !self.eat(')') {
+            loop {
+                args.push(self.sum()?);
+                if self.eat(')') {
+                    break;
+                }
+                if !self.eat(',') {
+                    return Err(self.error("expected `,` or `)`"));
+                }
+            }
+        }
+
+        if !function.takes(args.len()) {
+            self.pos = start;
+            return Err(self.error(&format!("`{name}` doesn't take {} arguments", args.len())));
+        }
+
+        Ok(Node::Call(function, args))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Expression;
+
+    #[test]
+    fn evaluates_expressions() {
+        let eval = |text: &str, values: &[f64]| Expression::parse(text).unwrap().eval(values);
+
+        assert_eq!(eval("errors / requests * 100", &[5., 50.]), 10.);
+        assert_eq!(eval("-2 ^ 2 + 1.5e1", &[]), 11.);
+        assert_eq!(eval("2 ^ 3 ^ 2", &[]), 512.);
+        assert_eq!(eval("max(a, b - 1, 0) % 4", &[-3., 7.]), 2.);
+        assert_eq!(eval("abs(rx - (tx + rx))", &[2., 3.]), 3.);
+        assert!(eval("min(a, 1)", &[f64::NAN]).is_nan());
+
+        let expression = Expression::parse("cpu - baseline + cpu").unwrap();
+        assert_eq!(expression.variables(), ["cpu", "baseline"]);
+
+        for invalid in [
+            "",
+            "a +",
+            "(a",
+            "a b",
+            "foo(a)",
+            "sqrt(a, b)",
+            "1..2",
+            "a $",
+        ] {
+            assert!(Expression::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let nested = |depth: usize| format!("{}a{}", "(".repeat(depth), ")".repeat(depth));
+
+        assert!(Expression::parse(&nested(32)).is_ok());
+        assert!(Expression::parse(&nested(500)).is_err());
+        assert!(Expression::parse(&"-".repeat(500)).is_err());
+
+        let long = vec!["a * 2"; 100_000].join(" + ");
+        assert_eq!(Expression::parse(&long).unwrap().eval(&[1.5]), 300_000.);
+    }
+}
@@ -0,0 +1,339 @@
+// https://github.com/madonoharu/tsify/issues/42
+#![allow(non_snake_case)]
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    data::TraceHandle,
+    error::{ChartError, Result},
+    trace::{
+        common_time_axis, merged_rows_in_range, trim_neighbors, value_from_neighbors, Bundle,
+        BundleRange, BundleRc, BundleVec, InterpolationStrategy, TimeAxis,
+    },
+    types::NumericRange,
+};
+
+use super::{
+    align::{AlignedPoints, Points},
+    Expression,
+};
+
+#[derive(Tsify, Serialize, Deserialize, Clone)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct DerivedTraceSpec {
+    /// Handle of the new trace
+    pub handle: TraceHandle,
+    /// e.g. `errors / requests * 100`, see [`Expression`]
+    pub expression: String,
+    /// Handles of the traces the expression's variables stand for
+    pub variables: HashMap<String, TraceHandle>,
+}
+
+#[derive(Tsify, Serialize, Deserialize, Clone)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct DerivedBundleSpec {
+    pub traces: Vec<DerivedTraceSpec>,
+    /// How the value of a variable is found at an x where its trace has no sample
+    pub interpolation: InterpolationStrategy,
+}
+
+struct DerivedTrace {
+    handle: TraceHandle,
+    expression: Expression,
+    /// Index of the source bundle and handle of the trace for each variable
+    inputs: Vec<(usize, TraceHandle)>,
+}
+
+/// A bundle of traces computed from traces of other bundles when they are queried.
+///
+/// A derived trace has a point at every x where one of its inputs has a sample. The other
+/// inputs are interpolated there, and the result is missing where any of them is.
+pub struct ExpressionBundle {
+    sources: Vec<BundleRc>,
+    traces: Vec<DerivedTrace>,
+    interpolation: InterpolationStrategy,
+}
+
+impl ExpressionBundle {
+    pub fn new(sources: Vec<BundleRc>, spec: DerivedBundleSpec) -> Result<Self> {
+        let mut seen = HashSet::new();
+        if let Some(trace) = spec.traces.iter().find(|t| !seen.insert(t.handle)) {
+            return Err(ChartError::DuplicateTrace(trace.handle));
+        }
+
+        let traces = spec
+            .traces
+            .into_iter()
+            .map(|trace| {
+                let expression = Expression::parse(&trace.expression)?;
+                if expression.variables().is_empty() {
+                    return Err(ChartError::Expression(format!(
+                        "`{}` doesn't refer to any trace",
+                        trace.expression
+                    )));
+                }
+
+                let inputs = expression
+                    .variables()
+                    .iter()
+                    .map(|name| {
+                        let handle = *trace.variables.get(name).ok_or_else(|| {
+                            ChartError::Expression(format!("unknown variable `{name}`"))
+                        })?;
+                        let source = sources
+                            .iter()
+                            .position(|s| s.contains_trace(handle))
+                            .ok_or(ChartError::UnknownTrace(handle))?;
+
+                        Ok((source, handle))
+                    })
+                    .collect::<Result<_>>()?;
+
+                Ok(DerivedTrace {
+                    handle: trace.handle,
+                    expression,
+                    inputs,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            sources,
+            traces,
+            interpolation: spec.interpolation,
+        })
+    }
+
+    fn trace(&self, handle: TraceHandle) -> Option<&DerivedTrace> {
+        self.traces.iter().find(|t| t.handle == handle)
+    }
+
+    /// Where all the inputs of the trace have data
+    fn trace_range(&self, trace: &DerivedTrace) -> Option<(f64, f64)> {
+        trace
+            .inputs
+            .iter()
+            .map(|&(source, _)| self.sources[source].range().bounds())
+            .reduce(|a, b| (a.0.max(b.0), a.1.min(b.1)))
+            .filter(|(from, to)| from <= to)
+    }
+
+    /// Evaluates the trace at the neighbors of `x_range` and every x in it
+    /// where one of its inputs has a sample
+    fn evaluate<'a>(&'a self, handle: TraceHandle, x_range: NumericRange) -> Points<'a> {
+        let Some(trace) = self.trace(handle) else {
+            return Box::new(std::iter::empty());
+        };
+
+        let inputs = trace.inputs.iter().map(|&(source, handle)| {
+            self.sources[source].iter_in_range_with_neighbors_f64(handle, x_range)
+        });
+
+        Box::new(trim_neighbors(
+            ExpressionIterator {
+                points: AlignedPoints::new(inputs, self.interpolation),
+                values: vec![f64::NAN; trace.inputs.len()],
+                expression: &trace.expression,
+            },
+            x_range,
+        ))
+    }
+}
+
+struct ExpressionIterator<'a> {
+    points: AlignedPoints<'a>,
+    values: Vec<f64>,
+    expression: &'a Expression,
+}
+
+impl Iterator for ExpressionIterator<'_> {
+    type Item = (f64, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.points.next_into(&mut self.values)?;
+
+        // e.g. division by zero is treated as a missing value rather than breaking extents
+        let y = self.expression.eval(&self.values);
+        Some((x, if y.is_finite() { y } else { f64::NAN }))
+    }
+}
+
+impl Bundle for ExpressionBundle {
+    fn traces(&self) -> Vec<TraceHandle> {
+        self.traces.iter().map(|t| t.handle).collect()
+    }
+
+    fn range(&self) -> BundleRange {
+        let bounded = self
+            .traces
+            .iter()
+            .filter_map(|t| self.trace_range(t))
+            .filter(|(from, to)| from.is_finite() && to.is_finite());
+
+        match bounded.reduce(|a, b| (a.0.min(b.0), a.1.max(b.1))) {
+            Some((from, to)) => BundleRange::Bounded { from, to },
+            None => BundleRange::Everywhere,
+        }
+    }
+
+    /// The number of points in the source bundles
+    fn point_count(&self) -> usize {
+        self.sources.iter().map(|s| s.point_count()).sum()
+    }
+
+    fn contains_trace(&self, trace: TraceHandle) -> bool {
+        self.trace(trace).is_some()
+    }
+
+    fn version(&self) -> u64 {
+        self.sources
+            .iter()
+            .fold(0, |acc, s| acc.wrapping_add(s.version()))
+    }
+
+    fn time_axis(&self) -> Option<TimeAxis> {
+        common_time_axis(&self.sources)
+    }
+
+    fn iter_in_range_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        Box::new(
+            self.evaluate(handle, x_range)
+                .skip_while(move |(x, _)| *x < x_range.from)
+                .take_while(move |(x, _)| *x <= x_range.to),
+        )
+    }
+
+    fn iter_in_range_with_neighbors_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        self.evaluate(handle, x_range)
+    }
+
+    fn iter_many_in_range_f64<'a>(
+        &'a self,
+        handles: Vec<TraceHandle>,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = Vec<f64>> + 'a> {
+        merged_rows_in_range(self, handles, x_range)
+    }
+
+    fn value_at(
+        &self,
+        trace: TraceHandle,
+        x: f64,
+        interpolation_strategy: InterpolationStrategy,
+    ) -> Option<(f64, f64)> {
+        let (from, to) = self.trace_range(self.trace(trace)?)?;
+        if x < from || x > to {
+            return None;
+        }
+
+        value_from_neighbors(
+            self.evaluate(trace, NumericRange::new(x, x)),
+            x,
+            interpolation_strategy,
+        )
+    }
+}
+
+/// ### Creates a bundle of traces computed from traces of other bundles
+/// * `sources` are searched for the traces in order, the first bundle containing a trace is used
+/// * the derived traces are evaluated lazily whenever they are queried
+#[wasm_bindgen]
+pub fn derive_bundle(sources: &BundleVec, spec: DerivedBundleSpec) -> Result<BundleRc> {
+    let sources = sources.upgrade_all()?;
+
+    Ok(BundleRc::new(ExpressionBundle::new(sources, spec)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{DerivedBundleSpec, DerivedTraceSpec, ExpressionBundle};
+    use crate::{
+        error::ChartError,
+        trace::{Batch, Bundle, BundleRc, InterpolationStrategy},
+        types::NumericRange,
+    };
+
+    fn derived(expression: &str, interpolation: InterpolationStrategy) -> ExpressionBundle {
+        let rx = Batch::new(vec![0., 2., 4., 6.], vec![1., 2., 3., 4.], &[1]).unwrap();
+        let tx = Batch::new(vec![1., 2., 3., 4.], vec![10., 0., 30., 40.], &[2]).unwrap();
+
+        let spec = DerivedBundleSpec {
+            traces: vec![DerivedTraceSpec {
+                handle: 3,
+                expression: expression.to_string(),
+                variables: HashMap::from([("rx".into(), 1), ("tx".into(), 2)]),
+            }],
+            interpolation,
+        };
+
+        ExpressionBundle::new(vec![BundleRc::new(rx), BundleRc::new(tx)], spec).unwrap()
+    }
+
+    #[test]
+    fn aligns_inputs() {
+        let bundle = derived("rx + tx", InterpolationStrategy::Linear);
+        let points: Vec<_> = bundle
+            .iter_in_range_f64(3, NumericRange::new(0., 10.))
+            .collect();
+
+        assert_eq!(points[1..5], [(1., 11.5), (2., 2.), (3., 32.5), (4., 43.)]);
+        assert!(points[0].1.is_nan() && points[5].1.is_nan());
+        assert_eq!(
+            bundle.extents_in_range_with_neighbors_f64(3, NumericRange::new(1.5, 2.5)),
+            Some((2., 32.5))
+        );
+        assert_eq!(
+            bundle.value_at(3, 2.5, InterpolationStrategy::Linear),
+            Some((2.5, 17.25))
+        );
+        assert_eq!(bundle.value_at(3, 5., InterpolationStrategy::Linear), None);
+
+        let ratio = derived("rx / tx * 100", InterpolationStrategy::Previous);
+        assert!(ratio.value_at(3, 2., InterpolationStrategy::None).is_none());
+        assert_eq!(
+            ratio.value_at(3, 3., InterpolationStrategy::None),
+            Some((3., 2. / 30. * 100.))
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_inputs() {
+        let spec = |expression: &str| DerivedBundleSpec {
+            traces: vec![DerivedTraceSpec {
+                handle: 2,
+                expression: expression.to_string(),
+                variables: HashMap::from([("a".into(), 1), ("b".into(), 7)]),
+            }],
+            interpolation: InterpolationStrategy::None,
+        };
+        let source = || vec![BundleRc::new(Batch::new(vec![0.], vec![1.], &[1]).unwrap())];
+
+        assert!(ExpressionBundle::new(source(), spec("a * 2")).is_ok());
+        assert!(ExpressionBundle::new(source(), spec("a + c")).is_err());
+        assert!(ExpressionBundle::new(source(), spec("a + b")).is_err());
+        assert!(ExpressionBundle::new(source(), spec("1 + 2")).is_err());
+
+        let mut repeated = spec("a * 2");
+        repeated.traces.push(repeated.traces[0].clone());
+        assert!(matches!(
+            ExpressionBundle::new(source(), repeated),
+            Err(ChartError::DuplicateTrace(2))
+        ));
+    }
+}
@@ -0,0 +1,325 @@
+// https://github.com/madonoharu/tsify/issues/42
+#![allow(non_snake_case)]
+
+use std::{cell::RefCell, collections::HashMap};
+
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    data::TraceHandle,
+    error::{ChartError, Result},
+    trace::{
+        merged_rows_in_range, trim_neighbors, value_at_from_neighbors, Bundle, BundleRange,
+        BundleRc, InterpolationStrategy, TimeAxis,
+    },
+    types::NumericRange,
+};
+
+use super::percentile_of_sorted;
+
+#[derive(Tsify, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(tag = "type", content = "value", rename_all = "kebab-case")]
+pub enum GapThreshold {
+    /// Spacing of samples larger than this many median steps of the trace
+    MedianStep(f64),
+    /// Spacing of samples larger than this
+    MaxGap(f64),
+}
+
+#[derive(Tsify, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "kebab-case")]
+pub enum GapFill {
+    /// Breaks the line with a missing sample in the middle of the gap
+    Missing,
+    /// Drops to zero for the duration of the gap
+    Zero,
+    /// Keeps the last value before the gap
+    ForwardFill,
+    /// Takes the first value after the gap
+    BackFill,
+    /// Interpolates linearly across the gap
+    Linear,
+}
+
+#[derive(Tsify, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct GapSpec {
+    pub threshold: GapThreshold,
+    pub fill: GapFill,
+}
+
+/// Gap detection parameters of a trace
+#[derive(Clone, Copy)]
+struct TraceGaps {
+    version: u64,
+    /// Median spacing of the samples, if there are at least two
+    step: Option<f64>,
+    /// Smallest spacing considered a gap
+    threshold: f64,
+}
+
+/// A bundle with the outages of another one's traces detected and filled explicitly,
+/// so that interpolating across them (e.g. when stacking) doesn't hide them.
+///
+/// Filled gaps get points one median step after their start and before their end,
+/// and their shape between those is left to the interpolation.
+pub struct GapFilledBundle {
+    source: BundleRc,
+    spec: GapSpec,
+    gaps: RefCell<HashMap<TraceHandle, TraceGaps>>,
+}
+
+impl GapFilledBundle {
+    pub fn new(source: BundleRc, spec: GapSpec) -> Result<Self> {
+        let (GapThreshold::MedianStep(value) | GapThreshold::MaxGap(value)) = spec.threshold;
+        if !(value.is_finite() && value > 0.) {
+            return Err(ChartError::InvalidOption(format!(
+                "gap threshold {value} is not positive"
+            )));
+        }
+
+        Ok(Self {
+            source,
+            spec,
+            gaps: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// Gap detection parameters of the whole trace, computed when the source has changed
+    fn gaps(&self, trace: TraceHandle) -> TraceGaps {
+        let version = self.source.version();
+        if let Some(gaps) = self.gaps.borrow().get(&trace) {
+            if gaps.version == version {
+                return *gaps;
+            }
+        }
+
+        let (from, to) = self.source.range().bounds();
+        let points: Vec<_> = self
+            .source
+            .iter_in_range_f64(trace, NumericRange::new(from, to))
+            .map(|(x, _)| x)
+            .collect();
+        let mut steps: Vec<_> = points.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        steps.sort_unstable_by(f64::total_cmp);
+
+        let step = (!steps.is_empty()).then(|| percentile_of_sorted(&steps, 50.));
+        let threshold = match self.spec.threshold {
+            GapThreshold::MedianStep(factor) => step.map_or(f64::INFINITY, |step| step * factor),
+            GapThreshold::MaxGap(max) => max,
+        };
+
+        let gaps = TraceGaps {
+            version,
+            step,
+            threshold,
+        };
+        self.gaps.borrow_mut().insert(trace, gaps);
+
+        gaps
+    }
+
+    /// Points filling the gap between two samples
+    fn fill(
+        &self,
+        step: Option<f64>,
+        (x0, y0): (f64, f64),
+        (x1, y1): (f64, f64),
+    ) -> Vec<(f64, f64)> {
+        let gap = x1 - x0;
+        if self.spec.fill == GapFill::Missing {
+            return vec![(x0 + gap / 2., f64::NAN)];
+        }
+
+        let inset = step.map_or(gap / 2., |step| step.min(gap / 2.));
+        let edges = match inset < gap / 2. {
+            true => vec![x0 + inset, x1 - inset],
+            false => vec![x0 + inset],
+        };
+
+        edges
+            .into_iter()
+            .map(|x| match self.spec.fill {
+                GapFill::Zero => (x, 0.),
+                GapFill::ForwardFill => (x, y0),
+                GapFill::BackFill => (x, y1),
+                GapFill::Linear => (x, y0 + (y1 - y0) * (x - x0) / gap),
+                GapFill::Missing => unreachable!(),
+            })
+            .collect()
+    }
+
+    /// Points returned by the source's `iter_in_range_with_neighbors_f64`, with the gaps filled
+    fn filled(&self, trace: TraceHandle, x_range: NumericRange) -> Vec<(f64, f64)> {
+        if !self.source.contains_trace(trace) {
+            return Vec::new();
+        }
+        let gaps = self.gaps(trace);
+
+        let mut points = Vec::new();
+        let mut last: Option<(f64, f64)> = None;
+        for point in self.source.iter_in_range_with_neighbors_f64(trace, x_range) {
+            if let Some(last) = last {
+                if point.0 - last.0 > gaps.threshold {
+                    points.extend(self.fill(gaps.step, last, point));
+                }
+            }
+            points.push(point);
+            last = Some(point);
+        }
+
+        trim_neighbors(points.into_iter(), x_range).collect()
+    }
+}
+
+impl Bundle for GapFilledBundle {
+    fn traces(&self) -> Vec<TraceHandle> {
+        (*self.source).traces()
+    }
+
+    fn range(&self) -> BundleRange {
+        self.source.range()
+    }
+
+    fn point_count(&self) -> usize {
+        self.source.point_count()
+    }
+
+    fn contains_trace(&self, trace: TraceHandle) -> bool {
+        self.source.contains_trace(trace)
+    }
+
+    fn version(&self) -> u64 {
+        self.source.version()
+    }
+
+    fn time_axis(&self) -> Option<TimeAxis> {
+        self.source.time_axis()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.gaps.borrow().capacity() * size_of::<(TraceHandle, TraceGaps)>()
+    }
+
+    fn iter_in_range_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        Box::new(
+            self.filled(handle, x_range)
+                .into_iter()
+                .filter(move |(x, _)| x_range.from <= *x && *x <= x_range.to),
+        )
+    }
+
+    fn iter_in_range_with_neighbors_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        Box::new(self.filled(handle, x_range).into_iter())
+    }
+
+    fn iter_many_in_range_f64<'a>(
+        &'a self,
+        handles: Vec<TraceHandle>,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = Vec<f64>> + 'a> {
+        merged_rows_in_range(self, handles, x_range)
+    }
+
+    fn value_at(
+        &self,
+        trace: TraceHandle,
+        x: f64,
+        interpolation_strategy: InterpolationStrategy,
+    ) -> Option<(f64, f64)> {
+        value_at_from_neighbors(self, trace, x, interpolation_strategy)
+    }
+}
+
+#[wasm_bindgen]
+impl BundleRc {
+    /// Creates a bundle with the gaps in this one's traces filled according to `spec`
+    pub fn fill_gaps(&self, spec: GapSpec) -> Result<BundleRc> {
+        Ok(BundleRc::new(GapFilledBundle::new(self.clone(), spec)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GapFill, GapFilledBundle, GapSpec, GapThreshold};
+    use crate::{
+        trace::{Batch, Bundle, BundleRc, InterpolationStrategy},
+        types::NumericRange,
+    };
+
+    fn filled(threshold: GapThreshold, fill: GapFill) -> Vec<(f64, f64)> {
+        let source = BundleRc::new(
+            Batch::new(vec![0., 1., 2., 10., 11.], vec![1., 2., 4., 8., 9.], &[1]).unwrap(),
+        );
+        let bundle = GapFilledBundle::new(source, GapSpec { threshold, fill }).unwrap();
+
+        bundle
+            .iter_in_range_f64(1, NumericRange::new(1.5, 10.))
+            .collect()
+    }
+
+    #[test]
+    fn fills_gaps_by_strategy() {
+        let median = GapThreshold::MedianStep(2.);
+
+        let missing = filled(median, GapFill::Missing);
+        assert_eq!(missing.len(), 3);
+        assert!(missing[1].0 == 6. && missing[1].1.is_nan());
+
+        assert_eq!(
+            filled(median, GapFill::Zero),
+            [(2., 4.), (3., 0.), (9., 0.), (10., 8.)]
+        );
+        assert_eq!(
+            filled(median, GapFill::ForwardFill),
+            [(2., 4.), (3., 4.), (9., 4.), (10., 8.)]
+        );
+        assert_eq!(
+            filled(median, GapFill::BackFill),
+            [(2., 4.), (3., 8.), (9., 8.), (10., 8.)]
+        );
+        assert_eq!(
+            filled(median, GapFill::Linear),
+            [(2., 4.), (3., 4.5), (9., 7.5), (10., 8.)]
+        );
+        assert_eq!(
+            filled(GapThreshold::MaxGap(10.), GapFill::Zero),
+            [(2., 4.), (10., 8.)]
+        );
+    }
+
+    #[test]
+    fn neighbors_inside_gaps() {
+        let source =
+            BundleRc::new(Batch::new(vec![0., 1., 2., 10.], vec![1., 1., 1., 3.], &[1]).unwrap());
+        let spec = GapSpec {
+            threshold: GapThreshold::MaxGap(2.),
+            fill: GapFill::Zero,
+        };
+        let bundle = GapFilledBundle::new(source, spec).unwrap();
+
+        assert_eq!(
+            bundle
+                .iter_in_range_with_neighbors_f64(1, NumericRange::new(5., 5.))
+                .collect::<Vec<_>>(),
+            [(3., 0.), (9., 0.)]
+        );
+        assert_eq!(
+            bundle.value_at(1, 5., InterpolationStrategy::Linear),
+            Some((5., 0.))
+        );
+    }
+}
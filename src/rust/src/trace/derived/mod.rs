@@ -0,0 +1,20 @@
+mod align;
+mod calculus;
+mod expression;
+mod expression_bundle;
+mod gaps;
+mod normalize;
+mod percentiles;
+mod resample;
+mod shift;
+mod smoothing;
+
+pub use calculus::*;
+pub use expression::*;
+pub use expression_bundle::*;
+pub use gaps::*;
+pub use normalize::*;
+pub use percentiles::*;
+pub use resample::*;
+pub use shift::*;
+pub use smoothing::*;
@@ -0,0 +1,340 @@
+// https://github.com/madonoharu/tsify/issues/42
+#![allow(non_snake_case)]
+
+use std::{cell::RefCell, collections::HashMap};
+
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    data::TraceHandle,
+    error::{ChartError, Result},
+    trace::{
+        merged_rows_in_range, trim_neighbors, value_at_from_neighbors, Bundle, BundleRange,
+        BundleRc, InterpolationStrategy, TimeAxis,
+    },
+    types::NumericRange,
+};
+
+use super::align::AlignedPoints;
+
+#[derive(Tsify, Serialize, Deserialize, Clone, Copy)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(tag = "type", content = "value", rename_all = "kebab-case")]
+pub enum Normalization {
+    /// Scales the traces so that their first value in the range is 100
+    Rebase(NumericRange),
+    /// Subtracts the mean of the values in the range and divides by their standard deviation
+    ZScore(NumericRange),
+    /// Maps the smallest value in the range to 0 and the largest to 1
+    MinMax(NumericRange),
+    /// Divides the traces by a trace of the same bundle, interpolated where it has no sample
+    DivideBy {
+        trace: TraceHandle,
+        interpolation: InterpolationStrategy,
+    },
+}
+
+/// Values subtracted from and then dividing the values of a trace
+#[derive(Clone, Copy)]
+struct Scaling {
+    version: u64,
+    offset: f64,
+    divisor: f64,
+}
+
+impl Scaling {
+    fn apply(&self, y: f64) -> f64 {
+        let y = (y - self.offset) / self.divisor;
+        // e.g. a flat reference range is treated as missing values rather than breaking extents
+        if y.is_finite() {
+            y
+        } else {
+            f64::NAN
+        }
+    }
+}
+
+/// A bundle with the traces of another one normalized so that traces of different
+/// magnitudes can be compared.
+///
+/// Scalings computed over a reference range are kept until the source changes. To re-anchor
+/// the normalization e.g. when panning, create a new bundle with a different range.
+pub struct NormalizedBundle {
+    source: BundleRc,
+    normalization: Normalization,
+    scalings: RefCell<HashMap<TraceHandle, Scaling>>,
+}
+
+impl NormalizedBundle {
+    pub fn new(source: BundleRc, normalization: Normalization) -> Result<Self> {
+        match normalization {
+            Normalization::Rebase(range)
+            | Normalization::ZScore(range)
+            | Normalization::MinMax(range) => {
+                if range.from.is_nan() || range.to.is_nan() || range.from > range.to {
+                    return Err(ChartError::InvalidOption(format!(
+                        "reference range from {} to {} is empty",
+                        range.from, range.to
+                    )));
+                }
+            }
+            Normalization::DivideBy { trace, .. } => {
+                if !source.contains_trace(trace) {
+                    return Err(ChartError::UnknownTrace(trace));
+                }
+            }
+        }
+
+        Ok(Self {
+            source,
+            normalization,
+            scalings: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// Scaling of the trace, `None` if it is divided by another trace instead
+    fn scaling(&self, trace: TraceHandle) -> Option<Scaling> {
+        let version = self.source.version();
+        if let Some(scaling) = self.scalings.borrow().get(&trace) {
+            if scaling.version == version {
+                return Some(*scaling);
+            }
+        }
+
+        let (Normalization::Rebase(range)
+        | Normalization::ZScore(range)
+        | Normalization::MinMax(range)) = self.normalization
+        else {
+            return None;
+        };
+        let values: Vec<_> = self
+            .source
+            .iter_in_range_f64(trace, range)
+            .map(|(_, y)| y)
+            .filter(|y| !y.is_nan())
+            .collect();
+
+        let (offset, divisor) = match self.normalization {
+            Normalization::Rebase(_) => (0., values.first().map_or(f64::NAN, |y| y / 100.)),
+            Normalization::ZScore(_) => {
+                let count = values.len() as f64;
+                let mean = values.iter().sum::<f64>() / count;
+                let variance = values.iter().map(|y| (y - mean).powi(2)).sum::<f64>() / count;
+
+                (mean, variance.sqrt())
+            }
+            _ => {
+                let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+                let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+                (min, max - min)
+            }
+        };
+
+        let scaling = Scaling {
+            version,
+            offset,
+            divisor,
+        };
+        self.scalings.borrow_mut().insert(trace, scaling);
+
+        Some(scaling)
+    }
+
+    /// The trace divided by another one at the neighbors of `x_range` and every x in it
+    /// where one of them has a sample
+    fn divided(&self, handle: TraceHandle, x_range: NumericRange) -> Vec<(f64, f64)> {
+        let Normalization::DivideBy {
+            trace,
+            interpolation,
+        } = self.normalization
+        else {
+            return Vec::new();
+        };
+        if !self.source.contains_trace(handle) {
+            return Vec::new();
+        }
+
+        let mut points = AlignedPoints::new(
+            [handle, trace].map(|h| self.source.iter_in_range_with_neighbors_f64(h, x_range)),
+            interpolation,
+        );
+        let mut values = [f64::NAN; 2];
+        let divided = std::iter::from_fn(|| {
+            let x = points.next_into(&mut values)?;
+            let y = values[0] / values[1];
+
+            Some((x, if y.is_finite() { y } else { f64::NAN }))
+        });
+
+        trim_neighbors(divided, x_range).collect()
+    }
+}
+
+impl Bundle for NormalizedBundle {
+    fn traces(&self) -> Vec<TraceHandle> {
+        (*self.source).traces()
+    }
+
+    fn range(&self) -> BundleRange {
+        self.source.range()
+    }
+
+    fn point_count(&self) -> usize {
+        self.source.point_count()
+    }
+
+    fn contains_trace(&self, trace: TraceHandle) -> bool {
+        self.source.contains_trace(trace)
+    }
+
+    fn version(&self) -> u64 {
+        self.source.version()
+    }
+
+    fn time_axis(&self) -> Option<TimeAxis> {
+        self.source.time_axis()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.scalings.borrow().capacity() * size_of::<(TraceHandle, Scaling)>()
+    }
+
+    fn iter_in_range_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        match self.scaling(handle) {
+            Some(scaling) => Box::new(
+                self.source
+                    .iter_in_range_f64(handle, x_range)
+                    .map(move |(x, y)| (x, scaling.apply(y))),
+            ),
+            None => Box::new(
+                self.divided(handle, x_range)
+                    .into_iter()
+                    .filter(move |(x, _)| x_range.from <= *x && *x <= x_range.to),
+            ),
+        }
+    }
+
+    fn iter_in_range_with_neighbors_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        match self.scaling(handle) {
+            Some(scaling) => Box::new(
+                self.source
+                    .iter_in_range_with_neighbors_f64(handle, x_range)
+                    .map(move |(x, y)| (x, scaling.apply(y))),
+            ),
+            None => Box::new(self.divided(handle, x_range).into_iter()),
+        }
+    }
+
+    fn iter_in_range_decimated_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+        buckets: usize,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        match self.scaling(handle) {
+            Some(scaling) => Box::new(
+                self.source
+                    .iter_in_range_decimated_f64(handle, x_range, buckets)
+                    .map(move |(x, y)| (x, scaling.apply(y))),
+            ),
+            None => self.iter_in_range_with_neighbors_f64(handle, x_range),
+        }
+    }
+
+    fn iter_many_in_range_f64<'a>(
+        &'a self,
+        handles: Vec<TraceHandle>,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = Vec<f64>> + 'a> {
+        merged_rows_in_range(self, handles, x_range)
+    }
+
+    fn value_at(
+        &self,
+        trace: TraceHandle,
+        x: f64,
+        interpolation_strategy: InterpolationStrategy,
+    ) -> Option<(f64, f64)> {
+        match self.scaling(trace) {
+            Some(scaling) => self
+                .source
+                .value_at(trace, x, interpolation_strategy)
+                .map(|(x, y)| (x, scaling.apply(y))),
+            None => value_at_from_neighbors(self, trace, x, interpolation_strategy),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl BundleRc {
+    /// Creates a bundle with the traces of this one normalized
+    pub fn normalize(&self, normalization: Normalization) -> Result<BundleRc> {
+        Ok(BundleRc::new(NormalizedBundle::new(
+            self.clone(),
+            normalization,
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Normalization, NormalizedBundle};
+    use crate::{
+        trace::{Batch, Bundle, BundleRc, InterpolationStrategy},
+        types::NumericRange,
+    };
+
+    fn normalized(normalization: Normalization) -> Vec<f64> {
+        let source = BundleRc::new(
+            Batch::new(
+                vec![0., 1., 2., 3.],
+                vec![f64::NAN, 2., 4., 8., 8., 2., 2., 2.],
+                &[1, 2],
+            )
+            .unwrap(),
+        );
+        let bundle = NormalizedBundle::new(source, normalization).unwrap();
+
+        bundle
+            .iter_in_range_f64(1, NumericRange::new(0., 3.))
+            .map(|(_, y)| y)
+            .collect()
+    }
+
+    #[test]
+    fn normalizes_over_reference_range() {
+        let rebased = normalized(Normalization::Rebase(NumericRange::new(0., 3.)));
+        assert!(rebased[0].is_nan());
+        assert_eq!(rebased[1..], [100., 200., 400.]);
+
+        let z = normalized(Normalization::ZScore(NumericRange::new(1., 2.)));
+        assert_eq!(z[1..], [-1., 1., 5.]);
+
+        let min_max = normalized(Normalization::MinMax(NumericRange::new(1., 3.)));
+        assert_eq!(min_max[1..], [0., 1. / 3., 1.]);
+
+        let flat = normalized(Normalization::MinMax(NumericRange::new(2., 2.)));
+        assert!(flat.iter().all(|y| y.is_nan()));
+    }
+
+    #[test]
+    fn divides_by_trace() {
+        let divided = normalized(Normalization::DivideBy {
+            trace: 2,
+            interpolation: InterpolationStrategy::Linear,
+        });
+        assert!(divided[0].is_nan());
+        assert_eq!(divided[1..], [1., 2., 4.]);
+    }
+}
@@ -0,0 +1,363 @@
+// https://github.com/madonoharu/tsify/issues/42
+#![allow(non_snake_case)]
+
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    data::TraceHandle,
+    error::{ChartError, Result},
+    trace::{
+        common_time_axis, merged_rows_in_range, value_at_from_neighbors, Bundle, BundleRange,
+        BundleRc, BundleVec, InterpolationStrategy, TimeAxis,
+    },
+    types::NumericRange,
+};
+
+use super::{align::AlignedPoints, percentile_of_sorted};
+
+#[derive(Tsify, Serialize, Deserialize, Clone)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct PercentileSpec {
+    /// Percentiles between 0 and 100, e.g. `[5, 25, 50, 75, 95]`
+    pub percentiles: Vec<f64>,
+    /// Handles of the resulting traces, one for each percentile
+    pub handles: Vec<TraceHandle>,
+    /// How the value of a trace is found at an x where it has no sample
+    pub interpolation: InterpolationStrategy,
+}
+
+/// Percentiles of the last queried range
+struct CachedBands {
+    version: u64,
+    x_range: (f64, f64),
+    x: Vec<f64>,
+    /// One column per percentile
+    y: Vec<Vec<f64>>,
+}
+
+/// A bundle of percentiles across many traces, e.g. the median and p5–p95 band of a fleet.
+///
+/// Percentiles are computed at every x where one of the traces has a sample, from the traces
+/// having a value there. All of them are computed at once and kept for the last queried range,
+/// as the traces of a band are usually queried one after another.
+pub struct PercentileBundle {
+    sources: Vec<BundleRc>,
+    /// Index of the source bundle and handle of each input trace
+    inputs: Vec<(usize, TraceHandle)>,
+    percentiles: Vec<(TraceHandle, f64)>,
+    interpolation: InterpolationStrategy,
+    cache: RefCell<Option<Rc<CachedBands>>>,
+}
+
+impl PercentileBundle {
+    /// Computes percentiles of `traces` of the sources, or of all their traces if `None`.
+    /// A trace contained in several sources is taken from the first of them.
+    pub fn new(
+        sources: Vec<BundleRc>,
+        traces: Option<&[TraceHandle]>,
+        spec: PercentileSpec,
+    ) -> Result<Self> {
+        if spec.percentiles.is_empty() {
+            return Err(ChartError::EmptyList("percentiles"));
+        }
+        if spec.handles.len() != spec.percentiles.len() {
+            return Err(ChartError::LengthMismatch {
+                what: "handles",
+                expected: spec.percentiles.len(),
+                actual: spec.handles.len(),
+            });
+        }
+        let mut seen = HashSet::new();
+        if let Some(&handle) = spec.handles.iter().find(|&&handle| !seen.insert(handle)) {
+            return Err(ChartError::DuplicateTrace(handle));
+        }
+        if let Some(p) = spec.percentiles.iter().find(|p| !(0. ..=100.).contains(*p)) {
+            return Err(ChartError::InvalidOption(format!(
+                "percentile {p} is not between 0 and 100"
+            )));
+        }
+
+        let source_of = |handle: TraceHandle| {
+            sources
+                .iter()
+                .position(|s| s.contains_trace(handle))
+                .map(|source| (source, handle))
+        };
+        let inputs: Vec<_> = match traces {
+            Some(traces) => traces
+                .iter()
+                .map(|&handle| source_of(handle).ok_or(ChartError::UnknownTrace(handle)))
+                .collect::<Result<_>>()?,
+            None => {
+                let mut handles: Vec<_> = sources.iter().flat_map(|s| (**s).traces()).collect();
+                handles.sort_unstable();
+                handles.dedup();
+                handles.into_iter().filter_map(source_of).collect()
+            }
+        };
+        if inputs.is_empty() {
+            return Err(ChartError::EmptyList("traces"));
+        }
+
+        Ok(Self {
+            sources,
+            inputs,
+            percentiles: spec.handles.into_iter().zip(spec.percentiles).collect(),
+            interpolation: spec.interpolation,
+            cache: RefCell::new(None),
+        })
+    }
+
+    fn column_of(&self, handle: TraceHandle) -> Option<usize> {
+        self.percentiles.iter().position(|(h, _)| *h == handle)
+    }
+
+    /// Percentiles at the points within `x_range` and its neighbors
+    fn bands(&self, x_range: NumericRange) -> Rc<CachedBands> {
+        let version = self.version();
+        if let Some(cached) = self.cache.borrow().as_ref() {
+            if cached.version == version && cached.x_range == x_range.as_tuple() {
+                return cached.clone();
+            }
+        }
+
+        let mut points = AlignedPoints::new(
+            self.inputs.iter().map(|&(source, handle)| {
+                self.sources[source].iter_in_range_with_neighbors_f64(handle, x_range)
+            }),
+            self.interpolation,
+        );
+
+        let mut x = Vec::new();
+        let mut y = vec![Vec::new(); self.percentiles.len()];
+        let mut values = vec![f64::NAN; self.inputs.len()];
+        let mut sorted = Vec::with_capacity(self.inputs.len());
+
+        while let Some(xi) = points.next_into(&mut values) {
+            sorted.clear();
+            sorted.extend(values.iter().copied().filter(|v| !v.is_nan()));
+            sorted.sort_unstable_by(f64::total_cmp);
+
+            x.push(xi);
+            for (column, &(_, p)) in y.iter_mut().zip(&self.percentiles) {
+                column.push(match sorted.is_empty() {
+                    true => f64::NAN,
+                    false => percentile_of_sorted(&sorted, p),
+                });
+            }
+        }
+
+        // only the closest of the neighbors of the traces are neighbors of the bands
+        let first = x.partition_point(|x| *x < x_range.from).saturating_sub(1);
+        let last = (x.partition_point(|x| *x <= x_range.to) + 1).min(x.len());
+        let bands = Rc::new(CachedBands {
+            version,
+            x_range: x_range.as_tuple(),
+            x: x[first..last].to_vec(),
+            y: y.into_iter()
+                .map(|column| column[first..last].to_vec())
+                .collect(),
+        });
+        *self.cache.borrow_mut() = Some(bands.clone());
+
+        bands
+    }
+
+    fn points(&self, handle: TraceHandle, x_range: NumericRange) -> Vec<(f64, f64)> {
+        let Some(column) = self.column_of(handle) else {
+            return Vec::new();
+        };
+        let bands = self.bands(x_range);
+
+        bands
+            .x
+            .iter()
+            .copied()
+            .zip(bands.y[column].iter().copied())
+            .collect()
+    }
+}
+
+impl Bundle for PercentileBundle {
+    fn traces(&self) -> Vec<TraceHandle> {
+        self.percentiles.iter().map(|(h, _)| *h).collect()
+    }
+
+    fn range(&self) -> BundleRange {
+        let bounded = self.sources.iter().filter_map(|s| match s.range() {
+            BundleRange::Bounded { from, to } => Some((from, to)),
+            BundleRange::Everywhere => None,
+        });
+
+        match bounded.reduce(|a, b| (a.0.min(b.0), a.1.max(b.1))) {
+            Some((from, to)) => BundleRange::Bounded { from, to },
+            None => BundleRange::Everywhere,
+        }
+    }
+
+    /// The number of points in the source bundles
+    fn point_count(&self) -> usize {
+        self.sources.iter().map(|s| s.point_count()).sum()
+    }
+
+    fn contains_trace(&self, trace: TraceHandle) -> bool {
+        self.column_of(trace).is_some()
+    }
+
+    fn version(&self) -> u64 {
+        self.sources
+            .iter()
+            .fold(0, |acc, s| acc.wrapping_add(s.version()))
+    }
+
+    fn time_axis(&self) -> Option<TimeAxis> {
+        common_time_axis(&self.sources)
+    }
+
+    fn memory_footprint(&self) -> usize {
+        let Some(bands) = &*self.cache.borrow() else {
+            return 0;
+        };
+        let columns: usize = bands.y.iter().map(|column| column.capacity()).sum();
+
+        (bands.x.capacity() + columns) * size_of::<f64>()
+            + bands.y.capacity() * size_of::<Vec<f64>>()
+    }
+
+    fn iter_in_range_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        Box::new(
+            self.points(handle, x_range)
+                .into_iter()
+                .filter(move |(x, _)| x_range.from <= *x && *x <= x_range.to),
+        )
+    }
+
+    fn iter_in_range_with_neighbors_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        Box::new(self.points(handle, x_range).into_iter())
+    }
+
+    fn iter_many_in_range_f64<'a>(
+        &'a self,
+        handles: Vec<TraceHandle>,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = Vec<f64>> + 'a> {
+        merged_rows_in_range(self, handles, x_range)
+    }
+
+    fn value_at(
+        &self,
+        trace: TraceHandle,
+        x: f64,
+        interpolation_strategy: InterpolationStrategy,
+    ) -> Option<(f64, f64)> {
+        value_at_from_neighbors(self, trace, x, interpolation_strategy)
+    }
+}
+
+#[wasm_bindgen]
+impl BundleRc {
+    /// ### Creates a bundle of percentiles across traces of this bundle
+    /// * `traces` to compute the percentiles of, all of them by default
+    /// * `spec` lists the percentiles and the handles of the resulting traces
+    pub fn percentile_bands(
+        &self,
+        traces: Option<Box<[TraceHandle]>>,
+        spec: PercentileSpec,
+    ) -> Result<BundleRc> {
+        let bundle = PercentileBundle::new(vec![self.clone()], traces.as_deref(), spec)?;
+
+        Ok(BundleRc::new(bundle))
+    }
+}
+
+#[wasm_bindgen]
+impl BundleVec {
+    /// ### Creates a bundle of percentiles across traces of all the bundles
+    /// * `traces` to compute the percentiles of, all of them by default
+    /// * `spec` lists the percentiles and the handles of the resulting traces
+    pub fn percentile_bands(
+        &self,
+        traces: Option<Box<[TraceHandle]>>,
+        spec: PercentileSpec,
+    ) -> Result<BundleRc> {
+        let bundle = PercentileBundle::new(self.upgrade_all()?, traces.as_deref(), spec)?;
+
+        Ok(BundleRc::new(bundle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PercentileBundle, PercentileSpec};
+    use crate::{
+        error::ChartError,
+        trace::{Batch, Bundle, BundleRc, InterpolationStrategy},
+        types::NumericRange,
+    };
+
+    fn spec(percentiles: Vec<f64>) -> PercentileSpec {
+        PercentileSpec {
+            handles: (100..100 + percentiles.len() as u32).collect(),
+            percentiles,
+            interpolation: InterpolationStrategy::Linear,
+        }
+    }
+
+    #[test]
+    fn percentiles_across_traces() {
+        let hosts = Batch::new(
+            vec![0., 1., 2.],
+            vec![1., 2., 3., 5., 6., 7., 9., 10., f64::NAN, 0., 0., 0.],
+            &[1, 2, 3, 4],
+        )
+        .unwrap();
+        let late = Batch::new(vec![1.5, 2.], vec![100., 100.], &[5]).unwrap();
+        let sources = vec![BundleRc::new(hosts), BundleRc::new(late)];
+
+        let bundle = PercentileBundle::new(sources, None, spec(vec![0., 50., 100.])).unwrap();
+        let band = |handle| -> Vec<_> {
+            bundle
+                .iter_in_range_f64(handle, NumericRange::new(0., 2.))
+                .collect()
+        };
+
+        assert_eq!(band(100), [(0., 0.), (1., 0.), (1.5, 0.), (2., 0.)]);
+        assert_eq!(band(101), [(0., 3.), (1., 4.), (1.5, 4.5), (2., 5.)]);
+        assert_eq!(band(102), [(0., 9.), (1., 10.), (1.5, 100.), (2., 100.)]);
+        assert_eq!(
+            bundle.value_at(101, 0.5, InterpolationStrategy::Linear),
+            Some((0.5, 3.5))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_specs() {
+        let sources = || vec![BundleRc::new(Batch::new(vec![0.], vec![1.], &[1]).unwrap())];
+
+        assert!(PercentileBundle::new(sources(), None, spec(vec![])).is_err());
+        assert!(PercentileBundle::new(sources(), None, spec(vec![101.])).is_err());
+        assert!(PercentileBundle::new(sources(), Some(&[2]), spec(vec![50.])).is_err());
+
+        let repeated = PercentileSpec {
+            handles: vec![100, 100],
+            ..spec(vec![25., 75.])
+        };
+        assert!(matches!(
+            PercentileBundle::new(sources(), None, repeated),
+            Err(ChartError::DuplicateTrace(100))
+        ));
+    }
+}
@@ -0,0 +1,580 @@
+// https://github.com/madonoharu/tsify/issues/42
+#![allow(non_snake_case)]
+
+use std::{cell::RefCell, collections::HashMap};
+
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    data::TraceHandle,
+    error::{ChartError, Result},
+    trace::{
+        merged_rows_in_range, value_at_from_neighbors, Bundle, BundleRange, BundleRc,
+        InterpolationStrategy, TimeAxis,
+    },
+    types::NumericRange,
+    utils::calendar::{civil_from_days, days_from_civil, SECONDS_PER_DAY},
+};
+
+/// Calendar periods in UTC, weeks start on Monday
+#[derive(Tsify, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "kebab-case")]
+pub enum CalendarUnit {
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl CalendarUnit {
+    /// Index of the period containing the given second since the Unix epoch
+    pub fn index_of(self, seconds: i64) -> i64 {
+        let days = seconds.div_euclid(SECONDS_PER_DAY);
+        let months = || {
+            let (year, month, _) = civil_from_days(days);
+            year * 12 + month as i64 - 1
+        };
+
+        match self {
+            CalendarUnit::Minute => seconds.div_euclid(60),
+            CalendarUnit::Hour => seconds.div_euclid(3600),
+            CalendarUnit::Day => days,
+            // 1970-01-01 was a Thursday
+            CalendarUnit::Week => (days + 3).div_euclid(7),
+            CalendarUnit::Month => months(),
+            CalendarUnit::Quarter => months().div_euclid(3),
+            CalendarUnit::Year => civil_from_days(days).0,
+        }
+    }
+
+    /// The first second of the period with the given index
+    pub fn start_of(self, index: i64) -> i64 {
+        let month_start = |months: i64| {
+            days_from_civil(months.div_euclid(12), months.rem_euclid(12) as u32 + 1, 1)
+                * SECONDS_PER_DAY
+        };
+
+        match self {
+            CalendarUnit::Minute => index * 60,
+            CalendarUnit::Hour => index * 3600,
+            CalendarUnit::Day => index * SECONDS_PER_DAY,
+            CalendarUnit::Week => (index * 7 - 3) * SECONDS_PER_DAY,
+            CalendarUnit::Month => month_start(index),
+            CalendarUnit::Quarter => month_start(index * 3),
+            CalendarUnit::Year => days_from_civil(index, 1, 1) * SECONDS_PER_DAY,
+        }
+    }
+}
+
+#[derive(Tsify, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(tag = "type", content = "value", rename_all = "kebab-case")]
+pub enum BucketWidth {
+    /// Buckets of a fixed width in x, aligned to multiples of it
+    Fixed(f64),
+    /// Buckets spanning calendar periods, x being seconds since the Unix epoch
+    Calendar(CalendarUnit),
+}
+
+#[derive(Tsify, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(tag = "type", content = "value", rename_all = "kebab-case")]
+pub enum Aggregation {
+    Mean,
+    Sum,
+    Min,
+    Max,
+    Count,
+    First,
+    Last,
+    /// Linearly interpolated percentile, between 0 and 100
+    Percentile(f64),
+}
+
+impl Aggregation {
+    /// Aggregates the values of a bucket, empty buckets sum and count to zero
+    /// and are missing otherwise
+    pub fn apply(self, values: &mut [f64]) -> f64 {
+        let (Some(&first), Some(&last)) = (values.first(), values.last()) else {
+            return match self {
+                Aggregation::Sum | Aggregation::Count => 0.,
+                _ => f64::NAN,
+            };
+        };
+
+        match self {
+            Aggregation::Mean => values.iter().sum::<f64>() / values.len() as f64,
+            Aggregation::Sum => values.iter().sum(),
+            Aggregation::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+            Aggregation::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Aggregation::Count => values.len() as f64,
+            Aggregation::First => first,
+            Aggregation::Last => last,
+            Aggregation::Percentile(p) => {
+                values.sort_unstable_by(f64::total_cmp);
+                percentile_of_sorted(values, p)
+            }
+        }
+    }
+}
+
+/// Linearly interpolated percentile of sorted, non-empty values
+pub(crate) fn percentile_of_sorted(values: &[f64], percentile: f64) -> f64 {
+    let rank = percentile / 100. * (values.len() - 1) as f64;
+    let (below, above) = (rank.floor() as usize, rank.ceil() as usize);
+    let frac = rank - below as f64;
+
+    values[below] * (1. - frac) + values[above] * frac
+}
+
+/// Most buckets a resampled bundle spans, wider buckets are needed for longer sources
+const MAX_BUCKETS: i64 = 1 << 20;
+
+struct CachedBuckets {
+    version: u64,
+    first: i64,
+    values: Vec<f64>,
+}
+
+/// A bundle aggregating the points of another one into buckets along x.
+///
+/// Every bucket within the source's range has a point at its start. Buckets are aggregated
+/// when they are first queried and cached until the source changes. A query returns at most
+/// [`MAX_BUCKETS`] buckets, starting with its first one, and so does the cache hold.
+pub struct ResampledBundle {
+    source: BundleRc,
+    width: BucketWidth,
+    aggregation: Aggregation,
+    /// Timestamps of the buckets are computed exactly when the source has a time axis
+    axis: Option<TimeAxis>,
+    cache: RefCell<HashMap<TraceHandle, CachedBuckets>>,
+}
+
+impl ResampledBundle {
+    pub fn new(source: BundleRc, width: BucketWidth, aggregation: Aggregation) -> Result<Self> {
+        if let BucketWidth::Fixed(width) = width {
+            if !(width.is_finite() && width > 0.) {
+                return Err(ChartError::InvalidOption(format!(
+                    "bucket width {width} is not positive"
+                )));
+            }
+        }
+        if let Aggregation::Percentile(p) = aggregation {
+            if !(0. ..=100.).contains(&p) {
+                return Err(ChartError::InvalidOption(format!(
+                    "percentile {p} is not between 0 and 100"
+                )));
+            }
+        }
+
+        if !matches!(source.range(), BundleRange::Bounded { .. }) {
+            return Err(ChartError::InvalidOption(
+                "only bundles with a bounded range can be resampled".into(),
+            ));
+        }
+
+        let bundle = Self {
+            axis: source.time_axis(),
+            source,
+            width,
+            aggregation,
+            cache: RefCell::new(HashMap::new()),
+        };
+
+        if let Some((first, last)) = bundle.source_buckets() {
+            if last.saturating_sub(first) >= MAX_BUCKETS {
+                return Err(ChartError::InvalidOption(format!(
+                    "the buckets are too narrow, the bundle would span more than {MAX_BUCKETS} of them"
+                )));
+            }
+        }
+
+        Ok(bundle)
+    }
+
+    fn ticks_per_second(axis: TimeAxis) -> i64 {
+        axis.unit.per_second() as i64
+    }
+
+    /// Width of the fixed buckets in ticks of the time axis
+    fn width_ticks(width: f64, axis: TimeAxis) -> i64 {
+        (width * axis.unit.per_second()).round().max(1.) as i64
+    }
+
+    /// Index of the bucket containing `x`
+    fn index_of(&self, x: f64) -> i64 {
+        match (self.width, self.axis) {
+            (BucketWidth::Fixed(width), None) => (x / width).floor() as i64,
+            (BucketWidth::Fixed(width), Some(axis)) => axis
+                .to_timestamp(x)
+                .div_euclid(Self::width_ticks(width, axis)),
+            (BucketWidth::Calendar(unit), None) => unit.index_of(x.floor() as i64),
+            (BucketWidth::Calendar(unit), Some(axis)) => unit.index_of(
+                axis.to_timestamp(x)
+                    .div_euclid(Self::ticks_per_second(axis)),
+            ),
+        }
+    }
+
+    /// The x where the bucket with the given index starts
+    fn start_of(&self, index: i64) -> f64 {
+        match (self.width, self.axis) {
+            (BucketWidth::Fixed(width), None) => index as f64 * width,
+            (BucketWidth::Fixed(width), Some(axis)) => {
+                axis.to_x(index * Self::width_ticks(width, axis))
+            }
+            (BucketWidth::Calendar(unit), None) => unit.start_of(index) as f64,
+            (BucketWidth::Calendar(unit), Some(axis)) => {
+                axis.to_x(unit.start_of(index) * Self::ticks_per_second(axis))
+            }
+        }
+    }
+
+    /// Indices of the first and last bucket containing points of the source,
+    /// `None` if it has no points
+    fn source_buckets(&self) -> Option<(i64, i64)> {
+        let (from, to) = self.source.range().bounds();
+
+        (from.is_finite() && to.is_finite() && from <= to)
+            .then(|| (self.index_of(from), self.index_of(to)))
+    }
+
+    /// Index of the bucket containing `x`, or of the one just outside the source's buckets
+    /// if `x` lies beyond the source's range, where it might not be representable
+    fn index_near(&self, x: f64, (first, last): (i64, i64)) -> i64 {
+        let (from, to) = self.source.range().bounds();
+
+        if x < from {
+            first - 1
+        } else if x > to {
+            last + 1
+        } else {
+            self.index_of(x)
+        }
+    }
+
+    /// Clamps the bucket indices to the ones within the source's range
+    /// and to at most [`MAX_BUCKETS`] of them
+    fn clamp(&self, first: i64, last: i64) -> Option<(i64, i64)> {
+        let (source_first, source_last) = self.source_buckets()?;
+        let first = first.max(source_first);
+        let last = last.min(source_last).min(first + MAX_BUCKETS - 1);
+
+        (first <= last).then_some((first, last))
+    }
+
+    fn aggregate(&self, trace: TraceHandle, first: i64, last: i64) -> Vec<f64> {
+        let x_range = NumericRange::new(self.start_of(first), self.start_of(last + 1));
+
+        let mut values = Vec::with_capacity((last - first + 1) as usize);
+        let mut bucket = Vec::new();
+        let mut current = first;
+
+        for (x, y) in self.source.iter_in_range_f64(trace, x_range) {
+            let index = self.index_of(x);
+            if index > last {
+                break;
+            }
+            while current < index {
+                values.push(self.aggregation.apply(&mut bucket));
+                bucket.clear();
+                current += 1;
+            }
+            if !y.is_nan() {
+                bucket.push(y);
+            }
+        }
+        while current <= last {
+            values.push(self.aggregation.apply(&mut bucket));
+            bucket.clear();
+            current += 1;
+        }
+
+        values
+    }
+
+    /// Values of the buckets from `first` to `last`, aggregating those not cached yet
+    fn values(&self, trace: TraceHandle, first: i64, last: i64) -> Vec<f64> {
+        let version = self.source.version();
+        let mut cache = self.cache.borrow_mut();
+
+        let cached = match cache.get_mut(&trace) {
+            // extend the cached buckets if they are adjacent to the requested ones
+            Some(cached)
+                if cached.version == version
+                    && first <= cached.first + cached.values.len() as i64
+                    && last >= cached.first - 1
+                    && last.max(cached.first + cached.values.len() as i64 - 1)
+                        - first.min(cached.first)
+                        < MAX_BUCKETS =>
+            {
+                if first < cached.first {
+                    let mut values = self.aggregate(trace, first, cached.first - 1);
+                    values.append(&mut cached.values);
+                    cached.values = values;
+                    cached.first = first;
+                }
+
+                let cached_last = cached.first + cached.values.len() as i64 - 1;
+                if last > cached_last {
+                    let values = self.aggregate(trace, cached_last + 1, last);
+                    cached.values.extend(values);
+                }
+
+                cached
+            }
+            _ => {
+                let values = self.aggregate(trace, first, last);
+                cache.insert(
+                    trace,
+                    CachedBuckets {
+                        version,
+                        first,
+                        values,
+                    },
+                );
+                cache.get_mut(&trace).unwrap()
+            }
+        };
+
+        let offset = (first - cached.first) as usize;
+        cached.values[offset..offset + (last - first + 1) as usize].to_vec()
+    }
+
+    fn points(&self, trace: TraceHandle, first: i64, last: i64) -> std::vec::IntoIter<(f64, f64)> {
+        let Some((first, last)) = self
+            .clamp(first, last)
+            .filter(|_| self.source.contains_trace(trace))
+        else {
+            return Vec::new().into_iter();
+        };
+
+        (first..=last)
+            .map(|i| self.start_of(i))
+            .zip(self.values(trace, first, last))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+impl Bundle for ResampledBundle {
+    fn traces(&self) -> Vec<TraceHandle> {
+        (*self.source).traces()
+    }
+
+    fn range(&self) -> BundleRange {
+        match self.source_buckets() {
+            Some((first, last)) => BundleRange::Bounded {
+                from: self.start_of(first),
+                to: self.start_of(last),
+            },
+            // an empty source doesn't contain nor intersect anything
+            None => BundleRange::Bounded {
+                from: f64::INFINITY,
+                to: f64::NEG_INFINITY,
+            },
+        }
+    }
+
+    /// The number of buckets of all the traces
+    fn point_count(&self) -> usize {
+        match self.source_buckets() {
+            Some((first, last)) => (last - first + 1) as usize * self.traces().len(),
+            None => 0,
+        }
+    }
+
+    fn contains_trace(&self, trace: TraceHandle) -> bool {
+        self.source.contains_trace(trace)
+    }
+
+    fn version(&self) -> u64 {
+        self.source.version()
+    }
+
+    fn time_axis(&self) -> Option<TimeAxis> {
+        self.axis
+    }
+
+    fn memory_footprint(&self) -> usize {
+        let cache = self.cache.borrow();
+        let buckets: usize = cache
+            .values()
+            .map(|c| c.values.capacity() * size_of::<f64>())
+            .sum();
+
+        buckets + cache.capacity() * size_of::<(TraceHandle, CachedBuckets)>()
+    }
+
+    fn iter_in_range_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        let Some(buckets) = self.source_buckets() else {
+            return Box::new(std::iter::empty());
+        };
+
+        let mut first = self.index_near(x_range.from, buckets);
+        if self.start_of(first) < x_range.from {
+            first += 1;
+        }
+        let last = self.index_near(x_range.to, buckets);
+
+        Box::new(self.points(handle, first, last))
+    }
+
+    fn iter_in_range_with_neighbors_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        let Some(buckets) = self.source_buckets() else {
+            return Box::new(std::iter::empty());
+        };
+
+        let first = self.index_near(x_range.from, buckets);
+        let mut last = self.index_near(x_range.to, buckets);
+        if self.start_of(last) < x_range.to {
+            last += 1;
+        }
+
+        Box::new(self.points(handle, first, last))
+    }
+
+    fn iter_many_in_range_f64<'a>(
+        &'a self,
+        handles: Vec<TraceHandle>,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = Vec<f64>> + 'a> {
+        merged_rows_in_range(self, handles, x_range)
+    }
+
+    fn value_at(
+        &self,
+        trace: TraceHandle,
+        x: f64,
+        interpolation_strategy: InterpolationStrategy,
+    ) -> Option<(f64, f64)> {
+        value_at_from_neighbors(self, trace, x, interpolation_strategy)
+    }
+}
+
+#[wasm_bindgen]
+impl BundleRc {
+    /// ### Creates a bundle aggregating the points of this one into buckets along x
+    /// * `width` of the buckets, calendar units require x to be seconds since the Unix epoch
+    /// * `aggregation` of the points within each bucket
+    pub fn resample(&self, width: BucketWidth, aggregation: Aggregation) -> Result<BundleRc> {
+        Ok(BundleRc::new(ResampledBundle::new(
+            self.clone(),
+            width,
+            aggregation,
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{Aggregation, BucketWidth, CalendarUnit, ResampledBundle};
+    use crate::{
+        trace::{
+            Batch, Bundle, BundleRc, ConstantBatch, EpochUnit, LiveBatch, RetentionPolicy, TimeAxis,
+        },
+        types::NumericRange,
+        utils::calendar::{days_from_civil, SECONDS_PER_DAY},
+    };
+
+    #[test]
+    fn aggregates_fixed_buckets() {
+        let x: Vec<f64> = (0..10).map(f64::from).collect();
+        let y = vec![1., 5., 2., f64::NAN, 4., 4., 0., 8., 3., 7.];
+        let source = BundleRc::new(Batch::new(x, y, &[1]).unwrap());
+
+        let resample = |aggregation| {
+            ResampledBundle::new(source.clone(), BucketWidth::Fixed(4.), aggregation).unwrap()
+        };
+        let values = |bundle: &ResampledBundle, from: f64, to: f64| -> Vec<f64> {
+            bundle
+                .iter_in_range_f64(1, NumericRange::new(from, to))
+                .map(|(_, y)| y)
+                .collect()
+        };
+
+        let mean = resample(Aggregation::Mean);
+        assert_eq!(values(&mean, 4., 100.), [4., 5.]);
+        assert_eq!(values(&mean, 0., 3.), [8. / 3.]);
+        assert_eq!(values(&mean, -10., 100.), [8. / 3., 4., 5.]);
+
+        assert_eq!(values(&resample(Aggregation::Count), 0., 9.), [3., 4., 2.]);
+        assert_eq!(values(&resample(Aggregation::Max), 0., 9.), [5., 8., 7.]);
+        assert_eq!(values(&resample(Aggregation::Last), 0., 9.), [2., 8., 7.]);
+        assert_eq!(
+            values(&resample(Aggregation::Percentile(50.)), 0., 9.),
+            [2., 4., 5.]
+        );
+
+        assert_eq!(
+            mean.iter_in_range_with_neighbors_f64(1, NumericRange::new(5., 6.))
+                .collect::<Vec<_>>(),
+            [(4., 4.), (8., 5.)]
+        );
+        assert!(
+            ResampledBundle::new(source.clone(), BucketWidth::Fixed(0.), Aggregation::Sum).is_err()
+        );
+        assert!(ResampledBundle::new(source, BucketWidth::Fixed(1e-9), Aggregation::Sum).is_err());
+    }
+
+    #[test]
+    fn rejects_unbounded_sources() {
+        let constant = BundleRc::new(ConstantBatch::new(HashMap::from([(1, 5.)])));
+        assert!(ResampledBundle::new(constant, BucketWidth::Fixed(1.), Aggregation::Mean).is_err());
+
+        let live = BundleRc::new(LiveBatch::new(&[1], RetentionPolicy::default()));
+        let bundle = ResampledBundle::new(live, BucketWidth::Fixed(1.), Aggregation::Mean).unwrap();
+        assert_eq!(
+            bundle
+                .iter_in_range_f64(1, NumericRange::new(0., 1e12))
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn buckets_by_calendar_months() {
+        let epoch = days_from_civil(2024, 1, 1) * SECONDS_PER_DAY;
+        let days = [0, 30, 31, 59, 60, 400];
+        let x: Vec<i64> = days
+            .iter()
+            .map(|d| (epoch + d * SECONDS_PER_DAY) * 1000)
+            .collect();
+        let source = Batch::new(x, vec![1u8; days.len()], &[1])
+            .unwrap()
+            .with_time_axis(TimeAxis::new(EpochUnit::Ms, epoch * 1000));
+
+        let bundle = ResampledBundle::new(
+            BundleRc::new(source),
+            BucketWidth::Calendar(CalendarUnit::Month),
+            Aggregation::Sum,
+        )
+        .unwrap();
+
+        let points: Vec<_> = bundle
+            .iter_in_range_f64(1, NumericRange::new(0., 100. * SECONDS_PER_DAY as f64))
+            .collect();
+        let day = |d: i64| (d * SECONDS_PER_DAY) as f64;
+        assert_eq!(
+            points,
+            [(0., 2.), (day(31), 2.), (day(60), 1.), (day(91), 0.)]
+        );
+        assert_eq!(bundle.point_count(), 14);
+    }
+}
@@ -0,0 +1,402 @@
+// https://github.com/madonoharu/tsify/issues/42
+#![allow(non_snake_case)]
+
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    data::TraceHandle,
+    error::{ChartError, Result},
+    trace::{Bundle, BundleRange, BundleRc, InterpolationStrategy, TimeAxis},
+    types::NumericRange,
+};
+
+use super::CalendarUnit;
+
+#[derive(Tsify, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(tag = "type", content = "value", rename_all = "kebab-case")]
+pub enum XShift {
+    /// Moves the points by this much along x
+    Constant(f64),
+    /// Moves every point by `count` calendar periods, x being seconds since the Unix epoch.
+    /// Points keep their offset into the period, except that those past the end of a shorter
+    /// period are placed at its last second, e.g. January 31st moves to February's last day.
+    Calendar { unit: CalendarUnit, count: i32 },
+}
+
+#[derive(Tsify, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct ShiftSpec {
+    pub x_shift: XShift,
+    /// Factor the y values get multiplied by, 1 by default
+    #[serde(default)]
+    pub y_scale: Option<f64>,
+    /// Added to the y values after scaling them, 0 by default
+    #[serde(default)]
+    pub y_offset: Option<f64>,
+}
+
+/// How the x values of a [`ShiftedBundle`] are moved
+enum Shift {
+    Constant(f64),
+    Calendar {
+        unit: CalendarUnit,
+        count: i64,
+        /// Periods are found exactly when the source has a time axis
+        axis: Option<TimeAxis>,
+    },
+}
+
+impl Shift {
+    /// Moves `x` of the source to this bundle
+    fn forward(&self, x: f64) -> f64 {
+        match *self {
+            Shift::Constant(shift) => x + shift,
+            Shift::Calendar { unit, count, axis } => move_by_periods(unit, axis, x, count, false),
+        }
+    }
+
+    /// Moves `x` of this bundle back to the source. The last moment of a period moved to
+    /// a longer one becomes its last moment, as all the points it was shortened to end there.
+    fn backward(&self, x: f64, end: bool) -> f64 {
+        match *self {
+            Shift::Constant(shift) => x - shift,
+            Shift::Calendar { unit, count, axis } => move_by_periods(unit, axis, x, -count, end),
+        }
+    }
+}
+
+/// Start and length of the period containing `seconds` and of the one `count` periods away
+fn periods(unit: CalendarUnit, seconds: i64, count: i64) -> ((i64, i64), (i64, i64)) {
+    let period = unit.index_of(seconds);
+    let span = |index: i64| {
+        let start = unit.start_of(index);
+        (start, unit.start_of(index + 1) - start)
+    };
+
+    (span(period), span(period + count))
+}
+
+/// Moves `x` by `count` calendar periods keeping its offset into the period,
+/// limited to the last tick of the target period. With `end`, the last tick
+/// of the period is moved to the last tick of the target period.
+fn move_by_periods(
+    unit: CalendarUnit,
+    axis: Option<TimeAxis>,
+    x: f64,
+    count: i64,
+    end: bool,
+) -> f64 {
+    if !x.is_finite() {
+        return x;
+    }
+
+    match axis {
+        Some(axis) => {
+            let per_second = axis.unit.per_second() as i64;
+            let ticks = axis.to_timestamp(x);
+            let ((from, from_len), (to, to_len)) =
+                periods(unit, ticks.div_euclid(per_second), count);
+            let (from, from_len) = (from * per_second, from_len * per_second);
+            let (to, to_len) = (to * per_second, to_len * per_second);
+
+            let offset = match ticks - from {
+                offset if end && offset == from_len - 1 => to_len - 1,
+                offset => offset.min(to_len - 1),
+            };
+            axis.to_x(to + offset)
+        }
+        None => {
+            let ((from, from_len), (to, to_len)) = periods(unit, x.floor() as i64, count);
+            let (from_len, to_len) = (from_len as f64, to_len as f64);
+
+            let offset = match x - from as f64 {
+                offset if end && offset >= from_len - 1. => to_len - 1.,
+                offset => offset.min(to_len - 1.),
+            };
+            to as f64 + offset
+        }
+    }
+}
+
+/// A bundle with the points of another one moved along x and transformed along y,
+/// e.g. to overlay last week's data on this week's.
+pub struct ShiftedBundle {
+    source: BundleRc,
+    shift: Shift,
+    scale: f64,
+    offset: f64,
+}
+
+impl ShiftedBundle {
+    pub fn new(source: BundleRc, spec: ShiftSpec) -> Result<Self> {
+        let shift = match spec.x_shift {
+            XShift::Constant(shift) => Shift::Constant(shift),
+            XShift::Calendar { unit, count } => Shift::Calendar {
+                unit,
+                count: count as i64,
+                axis: source.time_axis(),
+            },
+        };
+        let (scale, offset) = (spec.y_scale.unwrap_or(1.), spec.y_offset.unwrap_or(0.));
+
+        let finite_shift = match shift {
+            Shift::Constant(shift) => shift.is_finite(),
+            Shift::Calendar { .. } => true,
+        };
+        if !(finite_shift && scale.is_finite() && offset.is_finite()) {
+            return Err(ChartError::InvalidOption(
+                "the shift and the y transform have to be finite".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            source,
+            shift,
+            scale,
+            offset,
+        })
+    }
+
+    /// The range of the source corresponding to a range of this bundle
+    fn source_range(&self, x_range: NumericRange) -> NumericRange {
+        NumericRange::new(
+            self.shift.backward(x_range.from, false),
+            self.shift.backward(x_range.to, true),
+        )
+    }
+
+    fn transform(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (self.shift.forward(x), y * self.scale + self.offset)
+    }
+}
+
+impl Bundle for ShiftedBundle {
+    fn traces(&self) -> Vec<TraceHandle> {
+        (*self.source).traces()
+    }
+
+    fn range(&self) -> BundleRange {
+        match self.source.range() {
+            BundleRange::Bounded { from, to } => BundleRange::Bounded {
+                from: self.shift.forward(from),
+                to: self.shift.forward(to),
+            },
+            BundleRange::Everywhere => BundleRange::Everywhere,
+        }
+    }
+
+    fn point_count(&self) -> usize {
+        self.source.point_count()
+    }
+
+    fn contains_trace(&self, trace: TraceHandle) -> bool {
+        self.source.contains_trace(trace)
+    }
+
+    fn version(&self) -> u64 {
+        self.source.version()
+    }
+
+    fn time_axis(&self) -> Option<TimeAxis> {
+        self.source.time_axis()
+    }
+
+    fn iter_in_range_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        Box::new(
+            self.source
+                .iter_in_range_f64(handle, self.source_range(x_range))
+                .map(|point| self.transform(point)),
+        )
+    }
+
+    fn iter_in_range_with_neighbors_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        Box::new(
+            self.source
+                .iter_in_range_with_neighbors_f64(handle, self.source_range(x_range))
+                .map(|point| self.transform(point)),
+        )
+    }
+
+    fn iter_in_range_decimated_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+        buckets: usize,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        Box::new(
+            self.source
+                .iter_in_range_decimated_f64(handle, self.source_range(x_range), buckets)
+                .map(|point| self.transform(point)),
+        )
+    }
+
+    fn extents_in_range_with_neighbors_f64(
+        &self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Option<(f64, f64)> {
+        let (min, max) = self
+            .source
+            .extents_in_range_with_neighbors_f64(handle, self.source_range(x_range))?;
+        let (min, max) = (
+            min * self.scale + self.offset,
+            max * self.scale + self.offset,
+        );
+
+        Some((min.min(max), min.max(max)))
+    }
+
+    fn iter_many_in_range_f64<'a>(
+        &'a self,
+        handles: Vec<TraceHandle>,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = Vec<f64>> + 'a> {
+        Box::new(
+            self.source
+                .iter_many_in_range_f64(handles, self.source_range(x_range))
+                .map(|mut row| {
+                    row[0] = self.shift.forward(row[0]);
+                    for y in &mut row[1..] {
+                        *y = *y * self.scale + self.offset;
+                    }
+                    row
+                }),
+        )
+    }
+
+    fn value_at(
+        &self,
+        trace: TraceHandle,
+        x: f64,
+        interpolation_strategy: InterpolationStrategy,
+    ) -> Option<(f64, f64)> {
+        self.source
+            .value_at(trace, self.shift.backward(x, false), interpolation_strategy)
+            .map(|(_, y)| (x, y * self.scale + self.offset))
+    }
+}
+
+#[wasm_bindgen]
+impl BundleRc {
+    /// Creates a bundle with the points of this one moved along x and transformed along y
+    pub fn shift(&self, spec: ShiftSpec) -> Result<BundleRc> {
+        Ok(BundleRc::new(ShiftedBundle::new(self.clone(), spec)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ShiftSpec, ShiftedBundle, XShift};
+    use crate::{
+        trace::{Batch, Bundle, BundleRange, BundleRc, CalendarUnit, InterpolationStrategy},
+        types::NumericRange,
+        utils::calendar::{days_from_civil, SECONDS_PER_DAY},
+    };
+
+    #[test]
+    fn shifts_and_scales() {
+        let source = BundleRc::new(Batch::new(vec![0., 1., 2.], vec![1., 3., 2.], &[1]).unwrap());
+        let bundle = ShiftedBundle::new(
+            source,
+            ShiftSpec {
+                x_shift: XShift::Constant(10.),
+                y_scale: Some(-2.),
+                y_offset: Some(1.),
+            },
+        )
+        .unwrap();
+
+        assert!(matches!(
+            bundle.range(),
+            BundleRange::Bounded { from, to } if from == 10. && to == 12.
+        ));
+        assert_eq!(
+            bundle
+                .iter_in_range_f64(1, NumericRange::new(10.5, 12.))
+                .collect::<Vec<_>>(),
+            [(11., -5.), (12., -3.)]
+        );
+        assert_eq!(
+            bundle.extents_in_range_with_neighbors_f64(1, NumericRange::new(10., 12.)),
+            Some((-5., -1.))
+        );
+        assert_eq!(
+            bundle.value_at(1, 10.5, InterpolationStrategy::Linear),
+            Some((10.5, -3.))
+        );
+    }
+
+    #[test]
+    fn shifts_by_calendar_months() {
+        let february = (days_from_civil(2024, 2, 1) * SECONDS_PER_DAY) as f64;
+        let march = (days_from_civil(2024, 3, 1) * SECONDS_PER_DAY) as f64;
+        let source = BundleRc::new(Batch::new(vec![february + 5.], vec![1.], &[1]).unwrap());
+
+        let bundle = ShiftedBundle::new(
+            source,
+            ShiftSpec {
+                x_shift: XShift::Calendar {
+                    unit: CalendarUnit::Month,
+                    count: 1,
+                },
+                y_scale: None,
+                y_offset: None,
+            },
+        )
+        .unwrap();
+
+        assert!(bundle.contains_point(march + 5.));
+    }
+
+    #[test]
+    fn shifts_every_point_by_its_own_period() {
+        let day = |month: u32, day: i64| {
+            ((days_from_civil(2024, month, 1) + day - 1) * SECONDS_PER_DAY) as f64
+        };
+        let source = BundleRc::new(
+            Batch::new(
+                vec![day(1, 31), day(2, 15), day(3, 15)],
+                vec![1., 2., 3.],
+                &[1],
+            )
+            .unwrap(),
+        );
+
+        let bundle = ShiftedBundle::new(
+            source,
+            ShiftSpec {
+                x_shift: XShift::Calendar {
+                    unit: CalendarUnit::Month,
+                    count: 1,
+                },
+                y_scale: None,
+                y_offset: None,
+            },
+        )
+        .unwrap();
+
+        let points: Vec<_> = bundle
+            .iter_in_range_f64(1, NumericRange::new(day(2, 1), day(5, 1)))
+            .collect();
+        assert_eq!(
+            points,
+            [(day(3, 1) - 1., 1.), (day(3, 15), 2.), (day(4, 15), 3.)]
+        );
+        assert_eq!(
+            bundle.value_at(1, day(4, 15), InterpolationStrategy::None),
+            Some((day(4, 15), 3.))
+        );
+    }
+}
@@ -0,0 +1,476 @@
+// https://github.com/madonoharu/tsify/issues/42
+#![allow(non_snake_case)]
+
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    data::TraceHandle,
+    error::{ChartError, Result},
+    trace::{
+        merged_rows_in_range, value_at_from_neighbors, Bundle, BundleRange, BundleRc,
+        InterpolationStrategy, TimeAxis,
+    },
+    types::NumericRange,
+};
+
+#[derive(Tsify, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "kebab-case")]
+pub enum SmoothingKind {
+    Mean,
+    /// Exponential moving average, with the window being its time constant
+    Ema,
+    Median,
+    /// Sample standard deviation
+    Std,
+    Min,
+    Max,
+}
+
+#[derive(Tsify, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(tag = "type", content = "value", rename_all = "kebab-case")]
+pub enum SmoothingWindow {
+    /// Points within this distance in x
+    Span(f64),
+    /// This many consecutive samples
+    Samples(usize),
+}
+
+#[derive(Tsify, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct SmoothingSpec {
+    pub kind: SmoothingKind,
+    pub window: SmoothingWindow,
+    /// Centers the window on each point instead of ending it there, not supported by `ema`
+    #[serde(default)]
+    pub centered: bool,
+}
+
+/// Relative weight below which the samples before an exponential moving average's
+/// warm-up are left out, so that it doesn't depend on where a query starts
+const EMA_TOLERANCE: f64 = 1e-9;
+
+/// How many times the range fetched for a sample-count window may be doubled
+const MAX_FETCHES: usize = 64;
+
+/// Values within a rolling window, missing samples are left out
+struct RollingWindow {
+    sorted: Vec<f64>,
+    /// Sums are taken relative to this value to keep the variance accurate
+    reference: f64,
+    sum: f64,
+    sum_sq: f64,
+}
+
+impl RollingWindow {
+    fn new(reference: f64) -> Self {
+        Self {
+            sorted: Vec::new(),
+            reference,
+            sum: 0.,
+            sum_sq: 0.,
+        }
+    }
+
+    fn push(&mut self, y: f64) {
+        if y.is_nan() {
+            return;
+        }
+
+        let index = self.sorted.partition_point(|v| *v < y);
+        self.sorted.insert(index, y);
+        self.sum += y - self.reference;
+        self.sum_sq += (y - self.reference).powi(2);
+    }
+
+    fn remove(&mut self, y: f64) {
+        if y.is_nan() {
+            return;
+        }
+
+        let index = self.sorted.partition_point(|v| *v < y);
+        self.sorted.remove(index);
+        self.sum -= y - self.reference;
+        self.sum_sq -= (y - self.reference).powi(2);
+    }
+
+    fn get(&self, kind: SmoothingKind) -> f64 {
+        let count = self.sorted.len();
+        if count == 0 {
+            return f64::NAN;
+        }
+
+        match kind {
+            SmoothingKind::Mean => self.reference + self.sum / count as f64,
+            SmoothingKind::Std if count < 2 => f64::NAN,
+            SmoothingKind::Std => {
+                let square_deviations = self.sum_sq - self.sum * self.sum / count as f64;
+                (square_deviations.max(0.) / (count - 1) as f64).sqrt()
+            }
+            SmoothingKind::Median if count.is_multiple_of(2) => {
+                (self.sorted[count / 2 - 1] + self.sorted[count / 2]) / 2.
+            }
+            SmoothingKind::Median => self.sorted[count / 2],
+            SmoothingKind::Min => self.sorted[0],
+            SmoothingKind::Max => self.sorted[count - 1],
+            SmoothingKind::Ema => unreachable!("exponential averages don't use a window"),
+        }
+    }
+}
+
+/// A bundle smoothing the traces of another one.
+///
+/// Each point of the source is replaced by a statistic of the points in a window around it.
+/// Queries fetch the source's points the windows of the returned ones need beyond the
+/// queried range, so the result doesn't depend on what part of the trace is in view.
+pub struct SmoothedBundle {
+    source: BundleRc,
+    spec: SmoothingSpec,
+}
+
+impl SmoothedBundle {
+    pub fn new(source: BundleRc, spec: SmoothingSpec) -> Result<Self> {
+        match spec.window {
+            SmoothingWindow::Span(span) if !(span.is_finite() && span > 0.) => {
+                return Err(ChartError::InvalidOption(format!(
+                    "window span {span} is not positive"
+                )))
+            }
+            SmoothingWindow::Samples(0) => {
+                return Err(ChartError::InvalidOption(
+                    "the window has to contain a sample".to_string(),
+                ))
+            }
+            _ => {}
+        }
+        if spec.kind == SmoothingKind::Ema && spec.centered {
+            return Err(ChartError::InvalidOption(
+                "exponential moving averages can't be centered".to_string(),
+            ));
+        }
+
+        Ok(Self { source, spec })
+    }
+
+    /// How far before and after a point its window reaches, in x and in samples
+    fn reach(&self) -> ((f64, usize), (f64, usize)) {
+        let warm_up = -EMA_TOLERANCE.ln();
+
+        match (self.spec.kind, self.spec.window, self.spec.centered) {
+            (SmoothingKind::Ema, SmoothingWindow::Span(span), _) => ((span * warm_up, 0), (0., 0)),
+            (SmoothingKind::Ema, SmoothingWindow::Samples(n), _) => {
+                let alpha = 2. / (n as f64 + 1.);
+                let samples = (warm_up / -(1. - alpha).ln()).ceil() as usize;
+                ((0., samples), (0., 0))
+            }
+            (_, SmoothingWindow::Span(span), false) => ((span, 0), (0., 0)),
+            (_, SmoothingWindow::Span(span), true) => ((span / 2., 0), (span / 2., 0)),
+            (_, SmoothingWindow::Samples(n), false) => ((0., n - 1), (0., 0)),
+            (_, SmoothingWindow::Samples(n), true) => ((0., (n - 1) / 2), (0., n / 2)),
+        }
+    }
+
+    /// Points of the source from `a` to `b`, along with the ones their windows reach
+    fn fetch(&self, trace: TraceHandle, a: f64, b: f64) -> Vec<(f64, f64)> {
+        let ((mut back, before), (mut ahead, after)) = self.reach();
+        let (source_from, source_to) = self.source.range().bounds();
+
+        // a guess of the span holding the samples, based on the average sampling step
+        let traces = self.source.traces().len().max(1);
+        let step = (source_to - source_from) / (self.source.point_count() / traces).max(1) as f64;
+        let step = if step.is_finite() && step > 0. {
+            step
+        } else {
+            1.
+        };
+        back = back.max(step * before as f64);
+        ahead = ahead.max(step * after as f64);
+
+        let mut fetches = 0;
+        loop {
+            let points: Vec<_> = self
+                .source
+                .iter_in_range_f64(trace, NumericRange::new(a - back, b + ahead))
+                .collect();
+
+            let fetched_before = points.partition_point(|(x, _)| *x < a);
+            let fetched_after = points.len() - points.partition_point(|(x, _)| *x <= b);
+            let done_before = fetched_before >= before || a - back <= source_from;
+            let done_after = fetched_after >= after || b + ahead >= source_to;
+
+            fetches += 1;
+            if (done_before && done_after) || fetches == MAX_FETCHES {
+                return points;
+            }
+            if !done_before {
+                back *= 2.;
+            }
+            if !done_after {
+                ahead *= 2.;
+            }
+        }
+    }
+
+    /// Smoothed values of all the points
+    fn smooth(&self, points: &[(f64, f64)]) -> Vec<f64> {
+        if self.spec.kind == SmoothingKind::Ema {
+            return self.ema(points);
+        }
+
+        let reference = points
+            .iter()
+            .map(|(_, y)| *y)
+            .find(|y| !y.is_nan())
+            .unwrap_or(0.);
+        let mut window = RollingWindow::new(reference);
+        let (mut lo, mut hi) = (0, 0);
+
+        let mut smoothed = Vec::with_capacity(points.len());
+        for (i, &(x, y)) in points.iter().enumerate() {
+            let (first, last) = match (self.spec.window, self.spec.centered) {
+                (SmoothingWindow::Span(span), false) => {
+                    (lo + points[lo..].partition_point(|p| p.0 <= x - span), i)
+                }
+                (SmoothingWindow::Span(span), true) => (
+                    lo + points[lo..].partition_point(|p| p.0 < x - span / 2.),
+                    hi.max(i) + points[hi.max(i)..].partition_point(|p| p.0 <= x + span / 2.) - 1,
+                ),
+                (SmoothingWindow::Samples(n), false) => (i.saturating_sub(n - 1), i),
+                (SmoothingWindow::Samples(n), true) => {
+                    let first = i.saturating_sub((n - 1) / 2);
+                    (first, (i + n / 2).min(points.len() - 1))
+                }
+            };
+
+            while hi <= last {
+                window.push(points[hi].1);
+                hi += 1;
+            }
+            while lo < first {
+                window.remove(points[lo].1);
+                lo += 1;
+            }
+
+            smoothed.push(if y.is_nan() {
+                f64::NAN
+            } else {
+                window.get(self.spec.kind)
+            });
+        }
+
+        smoothed
+    }
+
+    fn ema(&self, points: &[(f64, f64)]) -> Vec<f64> {
+        let mut average: Option<(f64, f64)> = None;
+
+        points
+            .iter()
+            .map(|&(x, y)| {
+                if y.is_nan() {
+                    return f64::NAN;
+                }
+
+                let value = match (average, self.spec.window) {
+                    (None, _) => y,
+                    (Some((_, avg)), SmoothingWindow::Samples(n)) => {
+                        avg + (y - avg) * 2. / (n as f64 + 1.)
+                    }
+                    (Some((last_x, avg)), SmoothingWindow::Span(span)) => {
+                        avg + (y - avg) * (1. - (-(x - last_x) / span).exp())
+                    }
+                };
+                average = Some((x, value));
+
+                value
+            })
+            .collect()
+    }
+
+    /// Smoothed points of the source returned by its `iter_in_range_with_neighbors_f64`
+    fn smoothed(&self, trace: TraceHandle, x_range: NumericRange) -> Vec<(f64, f64)> {
+        let edge = |x: f64| {
+            self.source
+                .iter_in_range_with_neighbors_f64(trace, NumericRange::new(x, x))
+                .map(|(x, _)| x)
+        };
+        let (Some(a), Some(b)) = (edge(x_range.from).next(), edge(x_range.to).last()) else {
+            return Vec::new();
+        };
+
+        let points = self.fetch(trace, a, b);
+        let smoothed = self.smooth(&points);
+
+        points
+            .into_iter()
+            .zip(smoothed)
+            .map(|((x, _), y)| (x, y))
+            .filter(|(x, _)| a <= *x && *x <= b)
+            .collect()
+    }
+}
+
+impl Bundle for SmoothedBundle {
+    fn traces(&self) -> Vec<TraceHandle> {
+        (*self.source).traces()
+    }
+
+    fn range(&self) -> BundleRange {
+        self.source.range()
+    }
+
+    fn point_count(&self) -> usize {
+        self.source.point_count()
+    }
+
+    fn contains_trace(&self, trace: TraceHandle) -> bool {
+        self.source.contains_trace(trace)
+    }
+
+    fn version(&self) -> u64 {
+        self.source.version()
+    }
+
+    fn time_axis(&self) -> Option<TimeAxis> {
+        self.source.time_axis()
+    }
+
+    fn iter_in_range_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        Box::new(
+            self.smoothed(handle, x_range)
+                .into_iter()
+                .filter(move |(x, _)| x_range.from <= *x && *x <= x_range.to),
+        )
+    }
+
+    fn iter_in_range_with_neighbors_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        Box::new(self.smoothed(handle, x_range).into_iter())
+    }
+
+    fn iter_many_in_range_f64<'a>(
+        &'a self,
+        handles: Vec<TraceHandle>,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = Vec<f64>> + 'a> {
+        merged_rows_in_range(self, handles, x_range)
+    }
+
+    fn value_at(
+        &self,
+        trace: TraceHandle,
+        x: f64,
+        interpolation_strategy: InterpolationStrategy,
+    ) -> Option<(f64, f64)> {
+        value_at_from_neighbors(self, trace, x, interpolation_strategy)
+    }
+}
+
+#[wasm_bindgen]
+impl BundleRc {
+    /// Creates a bundle with the traces of this one smoothed as described by `spec`
+    pub fn smooth(&self, spec: SmoothingSpec) -> Result<BundleRc> {
+        Ok(BundleRc::new(SmoothedBundle::new(self.clone(), spec)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SmoothedBundle, SmoothingKind, SmoothingSpec, SmoothingWindow};
+    use crate::{
+        trace::{Batch, Bundle, BundleRc},
+        types::NumericRange,
+    };
+
+    fn smoothed(kind: SmoothingKind, window: SmoothingWindow, centered: bool) -> SmoothedBundle {
+        let x: Vec<f64> = (0..100).map(f64::from).collect();
+        let y: Vec<f64> = x.iter().map(|x| (x * 7.) % 11.).collect();
+        let source = BundleRc::new(Batch::new(x, y, &[1]).unwrap());
+
+        SmoothedBundle::new(
+            source,
+            SmoothingSpec {
+                kind,
+                window,
+                centered,
+            },
+        )
+        .unwrap()
+    }
+
+    fn points(bundle: &SmoothedBundle, from: f64, to: f64) -> Vec<(f64, f64)> {
+        bundle
+            .iter_in_range_with_neighbors_f64(1, NumericRange::new(from, to))
+            .collect()
+    }
+
+    fn assert_close(actual: Vec<(f64, f64)>, expected: &[(f64, f64)]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?}");
+        for ((x, y), (ex, ey)) in actual.into_iter().zip(expected) {
+            assert!(x == *ex && (y - ey).abs() < 1e-9, "{x}, {y}");
+        }
+    }
+
+    #[test]
+    fn windows_reach_beyond_the_query() {
+        // y = 0, 7, 3, 10, 6, 2, 9, ...
+        let mean = smoothed(SmoothingKind::Mean, SmoothingWindow::Samples(3), false);
+        assert_close(points(&mean, 0., 1.), &[(0., 0.), (1., 3.5)]);
+        assert_close(
+            points(&mean, 3.5, 4.5),
+            &[(3., 20. / 3.), (4., 19. / 3.), (5., 6.)],
+        );
+
+        let median = smoothed(SmoothingKind::Median, SmoothingWindow::Span(2.), true);
+        assert_close(points(&median, 2., 2.), &[(2., 7.)]);
+        let max = smoothed(SmoothingKind::Max, SmoothingWindow::Span(2.), false);
+        assert_close(points(&max, 5., 5.), &[(5., 6.)]);
+
+        for (kind, window, centered) in [
+            (SmoothingKind::Ema, SmoothingWindow::Samples(5), false),
+            (SmoothingKind::Ema, SmoothingWindow::Span(3.), false),
+            (SmoothingKind::Std, SmoothingWindow::Samples(10), true),
+            (SmoothingKind::Median, SmoothingWindow::Span(6.5), true),
+        ] {
+            let bundle = smoothed(kind, window, centered);
+            let full = points(&bundle, 0., 99.);
+            for from in [40., 61.5, 90.] {
+                let panned = points(&bundle, from, from + 5.);
+                let start = panned[0].0 as usize;
+                for (i, (x, y)) in panned.into_iter().enumerate() {
+                    assert_eq!(x, full[start + i].0);
+                    assert!((y - full[start + i].1).abs() < 1e-6, "{kind:?} at {x}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_windows() {
+        let source = BundleRc::new(Batch::new(vec![0.], vec![1.], &[1]).unwrap());
+        let spec = |kind, window, centered| SmoothingSpec {
+            kind,
+            window,
+            centered,
+        };
+
+        for spec in [
+            spec(SmoothingKind::Mean, SmoothingWindow::Samples(0), false),
+            spec(SmoothingKind::Mean, SmoothingWindow::Span(-1.), false),
+            spec(SmoothingKind::Ema, SmoothingWindow::Span(1.), true),
+        ] {
+            assert!(SmoothedBundle::new(source.clone(), spec).is_err());
+        }
+    }
+}
@@ -0,0 +1,414 @@
+// https://github.com/madonoharu/tsify/issues/42
+#![allow(non_snake_case)]
+
+use std::{
+    cell::{Cell, Ref, RefCell},
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+};
+
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    data::TraceHandle,
+    error::{ChartError, Result},
+    types::NumericRange,
+};
+
+use super::{Bundle, BundleRange, BundleRc, InterpolationStrategy};
+
+/// Describes which rows of a [`LiveBatch`] are kept around.
+/// Rows violating either of the limits are dropped from the front of the buffer.
+#[derive(Tsify, Serialize, Deserialize, Clone, Copy, Default)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicy {
+    /// Maximum number of rows kept in the buffer.
+    pub max_points: Option<usize>,
+    /// Maximum distance of a row's x from the largest x in the buffer.
+    pub max_age: Option<f64>,
+}
+
+#[derive(Default)]
+struct LiveData {
+    x: VecDeque<f64>,
+    y: Vec<VecDeque<f64>>,
+}
+
+impl LiveData {
+    fn search(&self, x: f64) -> Result<usize, usize> {
+        self.x.binary_search_by(|p| p.total_cmp(&x))
+    }
+}
+
+/// An appendable bundle backed by a ring buffer per column.
+///
+/// Rows are added through [`LiveBatch::push_rows`] and old rows are evicted according
+/// to the bundle's [`RetentionPolicy`]. Every mutation bumps [`Bundle::version`].
+pub struct LiveBatch {
+    y_idx: HashMap<TraceHandle, usize>,
+    retention: RetentionPolicy,
+    data: RefCell<LiveData>,
+    version: Cell<u64>,
+}
+
+impl LiveBatch {
+    pub fn new(handles: &[TraceHandle], retention: RetentionPolicy) -> Self {
+        let y_idx = HashMap::from_iter(handles.iter().enumerate().map(|(i, handle)| (*handle, i)));
+        let capacity = retention.max_points.unwrap_or(0);
+
+        Self {
+            y_idx,
+            retention,
+            data: RefCell::new(LiveData {
+                x: VecDeque::with_capacity(capacity),
+                y: vec![VecDeque::with_capacity(capacity); handles.len()],
+            }),
+            version: Cell::new(0),
+        }
+    }
+
+    /// Appends rows to the buffer and evicts rows violating the retention policy.
+    /// * `ys` is row-major, i.e. it contains one y per handle for every x in `x`
+    /// * Rows arriving out of order are inserted at their sorted position
+    /// * Nothing is appended if any of the x values is NaN or infinite
+    pub fn push_rows(&self, x: &[f64], ys: &[f64]) -> Result<()> {
+        let mut data = self.data.borrow_mut();
+        let columns = data.y.len();
+
+        if ys.len() != x.len() * columns {
+            return Err(ChartError::LengthMismatch {
+                what: "y values",
+                expected: x.len() * columns,
+                actual: ys.len(),
+            });
+        }
+
+        if let Some(&bad) = x.iter().find(|x| !x.is_finite()) {
+            return Err(ChartError::NonFiniteX(bad));
+        }
+
+        if x.is_empty() {
+            return Ok(());
+        }
+
+        for (row_idx, &xi) in x.iter().enumerate() {
+            let row = &ys[row_idx * columns..(row_idx + 1) * columns];
+
+            let at = match data.x.back() {
+                Some(&last) if last > xi => match data.search(xi) {
+                    Ok(i) | Err(i) => i,
+                },
+                _ => data.x.len(),
+            };
+
+            data.x.insert(at, xi);
+            for (column, &yi) in data.y.iter_mut().zip(row) {
+                column.insert(at, yi);
+            }
+        }
+
+        self.evict(&mut data);
+        self.version.set(self.version.get() + 1);
+
+        Ok(())
+    }
+
+    fn evict(&self, data: &mut LiveData) {
+        let mut drop = 0;
+
+        if let Some(max_points) = self.retention.max_points {
+            drop = data.x.len().saturating_sub(max_points);
+        }
+
+        if let (Some(max_age), Some(&last)) = (self.retention.max_age, data.x.back()) {
+            let oldest = last - max_age;
+            drop = drop.max(data.x.partition_point(|&x| x < oldest));
+        }
+
+        if drop > 0 {
+            data.x.drain(..drop);
+            for column in data.y.iter_mut() {
+                column.drain(..drop);
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut data = self.data.borrow_mut();
+
+        data.x.clear();
+        data.y.iter_mut().for_each(|column| column.clear());
+
+        self.version.set(self.version.get() + 1);
+    }
+}
+
+impl Bundle for LiveBatch {
+    fn traces(&self) -> Vec<TraceHandle> {
+        self.y_idx.keys().copied().collect()
+    }
+
+    fn contains_trace(&self, trace: TraceHandle) -> bool {
+        self.y_idx.contains_key(&trace)
+    }
+
+    fn version(&self) -> u64 {
+        self.version.get()
+    }
+
+    fn range(&self) -> BundleRange {
+        let data = self.data.borrow();
+
+        match (data.x.front(), data.x.back()) {
+            (Some(&from), Some(&to)) => BundleRange::Bounded { from, to },
+            // an empty bundle doesn't contain nor intersect anything
+            _ => BundleRange::Bounded {
+                from: f64::INFINITY,
+                to: f64::NEG_INFINITY,
+            },
+        }
+    }
+
+    fn point_count(&self) -> usize {
+        self.data.borrow().x.len()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        let data = self.data.borrow();
+        let columns: usize = data.y.iter().map(|column| column.capacity()).sum();
+
+        (data.x.capacity() + columns) * size_of::<f64>()
+            + self.y_idx.capacity() * size_of::<(TraceHandle, usize)>()
+    }
+
+    fn iter_in_range_f64<'a>(
+        &'a self,
+        trace: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        let Some(&column) = self.y_idx.get(&trace) else {
+            return Box::new(std::iter::empty());
+        };
+
+        let data = self.data.borrow();
+        let from = data.x.partition_point(|&x| x < x_range.from);
+        let to = data.x.partition_point(|&x| x <= x_range.to);
+
+        Box::new(LiveIterator {
+            data,
+            column,
+            index: from,
+            end: to.max(from),
+        })
+    }
+
+    fn iter_in_range_with_neighbors_f64<'a>(
+        &'a self,
+        trace: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        let Some(&column) = self.y_idx.get(&trace) else {
+            return Box::new(std::iter::empty());
+        };
+
+        let data = self.data.borrow();
+        let len = data.x.len();
+
+        let from = match data.search(x_range.from) {
+            Ok(i) => i,
+            Err(0) => 0,
+            Err(i) if i == len => return Box::new(std::iter::empty()),
+            Err(i) => i - 1,
+        };
+
+        let to = match data.search(x_range.to) {
+            Ok(i) => i + 1,
+            Err(i) => (i + 1).min(len),
+        };
+
+        if to <= from {
+            return Box::new(std::iter::empty());
+        }
+
+        Box::new(LiveIterator {
+            data,
+            column,
+            index: from,
+            end: to,
+        })
+    }
+
+    fn iter_many_in_range_f64<'a>(
+        &'a self,
+        traces: Vec<TraceHandle>,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = Vec<f64>> + 'a> {
+        let columns = traces
+            .iter()
+            .filter_map(|t| self.y_idx.get(t).copied())
+            .collect();
+
+        let data = self.data.borrow();
+        let from = data.x.partition_point(|&x| x < x_range.from);
+        let to = data.x.partition_point(|&x| x <= x_range.to);
+
+        Box::new(LiveManyIterator {
+            data,
+            columns,
+            index: from,
+            end: to.max(from),
+        })
+    }
+
+    fn value_at(
+        &self,
+        trace: TraceHandle,
+        x: f64,
+        strategy: InterpolationStrategy,
+    ) -> Option<(f64, f64)> {
+        if !self.contains_point(x) {
+            return None;
+        }
+
+        let column = *self.y_idx.get(&trace)?;
+        let data = self.data.borrow();
+        let ys = &data.y[column];
+
+        match data.search(x) {
+            Err(0) => None,
+            Err(i) if i == data.x.len() => None,
+            Ok(i) => Some((x, ys[i])).filter(|(_, y)| !y.is_nan()),
+            Err(i) => strategy.interpolate(x, (data.x[i - 1], ys[i - 1]), (data.x[i], ys[i])),
+        }
+    }
+}
+
+struct LiveIterator<'a> {
+    data: Ref<'a, LiveData>,
+    column: usize,
+    index: usize,
+    end: usize,
+}
+
+impl Iterator for LiveIterator<'_> {
+    type Item = (f64, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.end {
+            return None;
+        }
+
+        let point = (
+            self.data.x[self.index],
+            self.data.y[self.column][self.index],
+        );
+        self.index += 1;
+
+        Some(point)
+    }
+}
+
+struct LiveManyIterator<'a> {
+    data: Ref<'a, LiveData>,
+    columns: Vec<usize>,
+    index: usize,
+    end: usize,
+}
+
+impl Iterator for LiveManyIterator<'_> {
+    type Item = Vec<f64>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.end {
+            return None;
+        }
+
+        let mut result = Vec::with_capacity(self.columns.len() + 1);
+        result.push(self.data.x[self.index]);
+
+        for &column in &self.columns {
+            result.push(self.data.y[column][self.index]);
+        }
+        self.index += 1;
+
+        Some(result)
+    }
+}
+
+/// JS-side handle of a [`LiveBatch`], used to push new rows into it.
+#[wasm_bindgen]
+pub struct LiveBundle {
+    batch: Rc<LiveBatch>,
+    bundle: BundleRc,
+}
+
+#[wasm_bindgen]
+impl LiveBundle {
+    #[wasm_bindgen(constructor)]
+    pub fn new(handles: Vec<TraceHandle>, retention: RetentionPolicy) -> LiveBundle {
+        let batch = Rc::new(LiveBatch::new(&handles, retention));
+
+        LiveBundle {
+            bundle: BundleRc::from_rc(batch.clone()),
+            batch,
+        }
+    }
+
+    /// The bundle backed by this live buffer. All clones share the same data.
+    pub fn bundle(&self) -> BundleRc {
+        self.bundle.clone()
+    }
+
+    /// ### Appends rows to the bundle
+    /// * `x` contains the x value of every row
+    /// * `ys` contains one y for every trace handle per row, i.e. \[y₁, y₂,… yₙ, y'₁, …]
+    pub fn push_rows(&self, x: &[f64], ys: &[f64]) -> Result<()> {
+        self.batch.push_rows(x, ys)
+    }
+
+    pub fn clear(&self) {
+        self.batch.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LiveBatch, RetentionPolicy};
+    use crate::{trace::Bundle, types::NumericRange};
+
+    #[test]
+    fn evicts_by_count_and_age() {
+        let batch = LiveBatch::new(
+            &[1, 2],
+            RetentionPolicy {
+                max_points: Some(4),
+                max_age: Some(10.),
+            },
+        );
+
+        batch
+            .push_rows(&[0., 1., 2.], &[0., 10., 1., 11., 2., 12.])
+            .unwrap();
+        batch.push_rows(&[3., 4.], &[3., 13., 4., 14.]).unwrap();
+        assert!(batch.push_rows(&[5.], &[5.]).is_err());
+        assert!(batch.push_rows(&[5., f64::NAN], &[5., 5., 6., 6.]).is_err());
+        assert_eq!(batch.point_count(), 4);
+        assert_eq!(batch.version(), 2);
+
+        batch.push_rows(&[13.5], &[5., 15.]).unwrap();
+        let points: Vec<_> = batch
+            .iter_in_range_f64(2, NumericRange::new(0., 20.))
+            .collect();
+        assert_eq!(points, vec![(4., 14.), (13.5, 15.)]);
+
+        // late rows are inserted in order
+        batch.push_rows(&[10.], &[6., 16.]).unwrap();
+        let points: Vec<_> = batch
+            .iter_in_range_with_neighbors_f64(1, NumericRange::new(5., 11.))
+            .collect();
+        assert_eq!(points, vec![(4., 4.), (10., 6.), (13.5, 5.)]);
+    }
+}
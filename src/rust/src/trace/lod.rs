@@ -0,0 +1,249 @@
+/// Number of samples summarized by a single bucket of the finest level.
+pub const LOD_BASE_BUCKET: usize = 16;
+
+/// Number of buckets of one level merged into a single bucket of the next level.
+pub const LOD_FACTOR: usize = 4;
+
+/// Bundles with fewer points than this aren't worth building a pyramid for.
+pub const LOD_MIN_POINTS: usize = 4 * LOD_BASE_BUCKET * LOD_FACTOR * LOD_FACTOR;
+
+/// Summary of a run of consecutive samples.
+///
+/// Stores the sample indices of the extrema, the first and last samples of the bucket
+/// are given by its boundaries. Indices are used instead of values so that the x
+/// coordinates of the peaks are preserved as well.
+#[derive(Clone, Copy)]
+pub struct LodBucket {
+    pub min: u32,
+    pub max: u32,
+}
+
+#[derive(Clone)]
+pub struct LodLevel {
+    pub bucket_size: usize,
+    buckets: Vec<Vec<LodBucket>>,
+}
+
+impl LodLevel {
+    pub fn buckets_of(&self, column: usize) -> &[LodBucket] {
+        &self.buckets[column]
+    }
+}
+
+/// Multi-resolution min/max pyramid over the columns of a batch.
+///
+/// Level `k` summarizes `LOD_BASE_BUCKET * LOD_FACTOR^k` samples per bucket.
+#[derive(Clone)]
+pub struct LodPyramid {
+    point_count: usize,
+    levels: Vec<LodLevel>,
+}
+
+/// NaN-aware comparison, a missing value is never preferred over a present one
+fn is_less(a: f64, b: f64) -> bool {
+    !a.is_nan() && (b.is_nan() || a < b)
+}
+
+impl LodPyramid {
+    pub fn build(point_count: usize, columns: usize, value: impl Fn(usize, usize) -> f64) -> Self {
+        let mut levels: Vec<LodLevel> = Vec::new();
+
+        let base = (0..columns)
+            .map(|col| {
+                (0..point_count)
+                    .step_by(LOD_BASE_BUCKET)
+                    .map(|start| {
+                        let end = (start + LOD_BASE_BUCKET).min(point_count);
+                        let (mut min, mut max) = (start, start);
+
+                        for i in start + 1..end {
+                            let v = value(col, i);
+                            if is_less(v, value(col, min)) {
+                                min = i;
+                            }
+                            if is_less(value(col, max), v) {
+                                max = i;
+                            }
+                        }
+
+                        LodBucket {
+                            min: min as u32,
+                            max: max as u32,
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        levels.push(LodLevel {
+            bucket_size: LOD_BASE_BUCKET,
+            buckets: base,
+        });
+
+        loop {
+            let prev = &levels[levels.len() - 1];
+            if prev.bucket_size * LOD_FACTOR >= point_count {
+                break;
+            }
+
+            let buckets = prev
+                .buckets
+                .iter()
+                .enumerate()
+                .map(|(col, prev)| {
+                    prev.chunks(LOD_FACTOR)
+                        .map(|chunk| {
+                            chunk[1..].iter().fold(chunk[0], |acc, b| LodBucket {
+                                min: if is_less(
+                                    value(col, b.min as usize),
+                                    value(col, acc.min as usize),
+                                ) {
+                                    b.min
+                                } else {
+                                    acc.min
+                                },
+                                max: if is_less(
+                                    value(col, acc.max as usize),
+                                    value(col, b.max as usize),
+                                ) {
+                                    b.max
+                                } else {
+                                    acc.max
+                                },
+                            })
+                        })
+                        .collect()
+                })
+                .collect();
+
+            levels.push(LodLevel {
+                bucket_size: prev.bucket_size * LOD_FACTOR,
+                buckets,
+            });
+        }
+
+        Self {
+            point_count,
+            levels,
+        }
+    }
+
+    /// Finds the coarsest level that still has at least `buckets` buckets
+    /// across a span of `span` samples.
+    /// Number of bytes taken by the buckets
+    pub fn memory_footprint(&self) -> usize {
+        self.levels
+            .iter()
+            .flat_map(|level| &level.buckets)
+            .map(|buckets| buckets.capacity() * std::mem::size_of::<LodBucket>())
+            .sum()
+    }
+
+    pub fn level_for(&self, span: usize, buckets: usize) -> Option<&LodLevel> {
+        self.levels
+            .iter()
+            .rev()
+            .find(|l| l.bucket_size.saturating_mul(buckets.max(1)) <= span)
+    }
+
+    /// Indices of the samples to draw for buckets covering samples `from..to`.
+    /// Every bucket yields its first, min, max and last sample in order, without duplicates.
+    pub fn decimated_indices<'a>(
+        &'a self,
+        level: &'a LodLevel,
+        column: usize,
+        from: usize,
+        to: usize,
+    ) -> impl Iterator<Item = usize> + 'a {
+        let size = level.bucket_size;
+        let point_count = self.point_count;
+
+        level.buckets_of(column)[from / size..to.div_ceil(size)]
+            .iter()
+            .enumerate()
+            .flat_map(move |(i, bucket)| {
+                let first = (from / size + i) * size;
+                let last = (first + size).min(point_count) - 1;
+
+                let mut indices = [first, bucket.min as usize, bucket.max as usize, last];
+                indices.sort_unstable();
+
+                let mut prev = None;
+                indices.into_iter().filter(move |&i| {
+                    let unique = prev != Some(i);
+                    prev = Some(i);
+                    unique
+                })
+            })
+    }
+
+    /// Computes the extents of samples `from..to` from the largest fully covered buckets,
+    /// only looking at individual samples near the edges.
+    pub fn extents(
+        &self,
+        column: usize,
+        from: usize,
+        to: usize,
+        value: impl Fn(usize) -> f64,
+    ) -> Option<(f64, f64)> {
+        let mut extents: Option<(f64, f64)> = None;
+        let mut add = |v: f64| {
+            if v.is_nan() {
+                return;
+            }
+            extents = Some(match extents {
+                Some((min, max)) => (min.min(v), max.max(v)),
+                None => (v, v),
+            });
+        };
+
+        let mut i = from;
+        while i < to {
+            let level = self
+                .levels
+                .iter()
+                .rev()
+                .find(|l| i.is_multiple_of(l.bucket_size) && i + l.bucket_size <= to);
+
+            match level {
+                Some(level) => {
+                    let bucket = level.buckets_of(column)[i / level.bucket_size];
+                    add(value(bucket.min as usize));
+                    add(value(bucket.max as usize));
+                    i += level.bucket_size;
+                }
+                None => {
+                    add(value(i));
+                    i += 1;
+                }
+            }
+        }
+
+        extents
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LodPyramid, LOD_BASE_BUCKET};
+
+    #[test]
+    fn keeps_peaks() {
+        let n = 10_000;
+        let ys: Vec<f64> = (0..n)
+            .map(|i| if i == 4321 { 100. } else { (i % 7) as f64 })
+            .collect();
+
+        let pyramid = LodPyramid::build(n, 1, |_, i| ys[i]);
+        let level = pyramid.level_for(n, 50).unwrap();
+        assert!(level.bucket_size > LOD_BASE_BUCKET);
+
+        let indices: Vec<_> = pyramid.decimated_indices(level, 0, 0, n).collect();
+        assert!(indices.len() < n / 10);
+        assert!(indices.windows(2).all(|w| w[0] < w[1]));
+        assert!(indices.contains(&4321));
+
+        assert_eq!(pyramid.extents(0, 3, 9000, |i| ys[i]), Some((0., 100.)));
+        assert_eq!(pyramid.extents(0, 4322, 4330, |i| ys[i]), Some((0., 6.)));
+    }
+}
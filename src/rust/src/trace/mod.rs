@@ -1,11 +1,28 @@
 mod batch;
 mod bundle;
+mod chained_bundle;
+mod compact;
+mod compressed;
 mod constant_batch;
+mod derived;
 pub mod extensions;
+mod live_batch;
+mod lod;
+mod ragged_batch;
+mod time_axis;
 mod traceops;
+mod validity;
 
 pub use batch::*;
 pub use bundle::*;
+pub use chained_bundle::*;
+pub use compressed::*;
 pub use constant_batch::*;
+pub use derived::*;
+pub use live_batch::*;
+pub use lod::*;
+pub use ragged_batch::*;
+pub use time_axis::*;
 #[allow(unused_imports)]
 pub use traceops::*;
+pub use validity::*;
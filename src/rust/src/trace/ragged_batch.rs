@@ -0,0 +1,324 @@
+use std::{
+    collections::{HashMap, HashSet},
+    iter::Peekable,
+};
+
+use crate::{
+    data::TraceHandle,
+    error::{ChartError, Result},
+    types::NumericRange,
+};
+
+use super::{
+    merged_rows_in_range, Batch, Bundle, BundleRange, InterpolationStrategy, TimeAxis,
+    LOD_MIN_POINTS, N,
+};
+
+/// A bundle where every trace has its own x values.
+///
+/// Each trace is stored as a single-trace [`Batch`], queries of a single trace are forwarded
+/// to it, while [`Bundle::iter_many_in_range_f64`] merges the x values of all the traces.
+#[derive(Clone)]
+pub struct RaggedBatch<X: N, Y: N> {
+    traces: HashMap<TraceHandle, Batch<X, Y>>,
+    /// Traces without any points, which a [`Batch`] can't hold
+    empty: HashSet<TraceHandle>,
+    time_axis: Option<TimeAxis>,
+
+    from: f64,
+    to: f64,
+}
+
+impl<X: N, Y: N> RaggedBatch<X, Y> {
+    /// Creates the bundle from `(handle, x, y)` triples with distinct handles, sorting
+    /// the points of traces whose x values are not sorted already.
+    pub fn new(traces: Vec<(TraceHandle, Vec<X>, Vec<Y>)>) -> Result<Self> {
+        let mut seen = HashSet::new();
+        if let Some(&(handle, _, _)) = traces.iter().find(|(handle, _, _)| !seen.insert(*handle)) {
+            return Err(ChartError::DuplicateTrace(handle));
+        }
+
+        let mut from = f64::INFINITY;
+        let mut to = f64::NEG_INFINITY;
+
+        let (empty, traces): (Vec<_>, Vec<_>) =
+            traces.into_iter().partition(|(_, x, _)| x.is_empty());
+        if let Some((_, _, y)) = empty.iter().find(|(_, _, y)| !y.is_empty()) {
+            return Err(ChartError::LengthMismatch {
+                what: "y values",
+                expected: 0,
+                actual: y.len(),
+            });
+        }
+
+        let traces = traces
+            .into_iter()
+            .map(|(handle, x, y)| {
+                if x.len() != y.len() {
+                    return Err(ChartError::LengthMismatch {
+                        what: "y values",
+                        expected: x.len(),
+                        actual: y.len(),
+                    });
+                }
+
+                // compared as `X` rather than `f64`, which can't tell large timestamps apart
+                let (x, y) = if x.is_sorted() {
+                    (x, y)
+                } else {
+                    let mut points: Vec<_> = x.into_iter().zip(y).collect();
+                    points.sort_by(|(a, _), (b, _)| {
+                        a.partial_cmp(b)
+                            .unwrap_or_else(|| a.as_f64().total_cmp(&b.as_f64()))
+                    });
+                    points.into_iter().unzip()
+                };
+
+                from = from.min(x[0].as_f64());
+                to = to.max(x[x.len() - 1].as_f64());
+
+                Ok((handle, Batch::new(x, y, &[handle])?))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            traces,
+            empty: empty.into_iter().map(|(handle, _, _)| handle).collect(),
+            time_axis: None,
+            from,
+            to,
+        })
+    }
+
+    /// Interprets the x values of all the traces as timestamps, see [`Batch::with_time_axis`]
+    pub fn with_time_axis(mut self, time_axis: TimeAxis) -> Self {
+        self.traces = self
+            .traces
+            .into_iter()
+            .map(|(handle, trace)| (handle, trace.with_time_axis(time_axis)))
+            .collect();
+
+        self.time_axis = Some(time_axis);
+        self.from = f64::INFINITY;
+        self.to = f64::NEG_INFINITY;
+        for trace in self.traces.values() {
+            if let BundleRange::Bounded { from, to } = trace.range() {
+                self.from = self.from.min(from);
+                self.to = self.to.max(to);
+            }
+        }
+        self
+    }
+
+    /// Builds level-of-detail pyramids for the traces large enough to benefit from it
+    pub fn with_lod(mut self) -> Self {
+        self.traces = self
+            .traces
+            .into_iter()
+            .map(|(handle, trace)| {
+                if trace.point_count() >= LOD_MIN_POINTS {
+                    (handle, trace.with_lod())
+                } else {
+                    (handle, trace)
+                }
+            })
+            .collect();
+        self
+    }
+}
+
+impl<X: N, Y: N> Bundle for RaggedBatch<X, Y> {
+    fn traces(&self) -> Vec<TraceHandle> {
+        self.traces.keys().chain(&self.empty).copied().collect()
+    }
+
+    fn contains_trace(&self, trace: TraceHandle) -> bool {
+        self.traces.contains_key(&trace) || self.empty.contains(&trace)
+    }
+
+    fn range(&self) -> BundleRange {
+        BundleRange::Bounded {
+            from: self.from,
+            to: self.to,
+        }
+    }
+
+    fn time_axis(&self) -> Option<TimeAxis> {
+        self.time_axis
+    }
+
+    fn stored_types(&self) -> Option<(&'static str, &'static str)> {
+        Some((X::NAME, Y::NAME))
+    }
+
+    /// The x values are only returned if all the traces share them
+    fn integer_column(&self, trace: Option<TraceHandle>) -> Option<Vec<i128>> {
+        if let Some(trace) = trace {
+            return self.traces.get(&trace)?.integer_column(Some(trace));
+        }
+
+        let mut columns = self.traces.values().map(|t| t.integer_column(None));
+        let first = columns.next()??;
+        columns
+            .all(|column| column.as_ref() == Some(&first))
+            .then_some(first)
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.traces
+            .values()
+            .map(|t| t.memory_footprint())
+            .sum::<usize>()
+            + self.empty.capacity() * size_of::<TraceHandle>()
+    }
+
+    fn point_count(&self) -> usize {
+        self.traces
+            .values()
+            .map(|t| t.point_count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn iter_in_range_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        match self.traces.get(&handle) {
+            Some(trace) => trace.iter_in_range_f64(handle, x_range),
+            None => Box::new(std::iter::empty()),
+        }
+    }
+
+    fn iter_in_range_with_neighbors_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        match self.traces.get(&handle) {
+            Some(trace) => trace.iter_in_range_with_neighbors_f64(handle, x_range),
+            None => Box::new(std::iter::empty()),
+        }
+    }
+
+    fn iter_in_range_decimated_f64<'a>(
+        &'a self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+        buckets: usize,
+    ) -> Box<dyn Iterator<Item = (f64, f64)> + 'a> {
+        match self.traces.get(&handle) {
+            Some(trace) => trace.iter_in_range_decimated_f64(handle, x_range, buckets),
+            None => Box::new(std::iter::empty()),
+        }
+    }
+
+    fn extents_in_range_with_neighbors_f64(
+        &self,
+        handle: TraceHandle,
+        x_range: NumericRange,
+    ) -> Option<(f64, f64)> {
+        self.traces
+            .get(&handle)?
+            .extents_in_range_with_neighbors_f64(handle, x_range)
+    }
+
+    fn iter_many_in_range_f64<'a>(
+        &'a self,
+        handles: Vec<TraceHandle>,
+        x_range: NumericRange,
+    ) -> Box<dyn Iterator<Item = Vec<f64>> + 'a> {
+        merged_rows_in_range(self, handles, x_range)
+    }
+
+    fn value_at(
+        &self,
+        trace: TraceHandle,
+        x: f64,
+        interpolation_strategy: InterpolationStrategy,
+    ) -> Option<(f64, f64)> {
+        self.traces
+            .get(&trace)?
+            .value_at(trace, x, interpolation_strategy)
+    }
+}
+
+type PeekablePoints<'a> = Peekable<Box<dyn Iterator<Item = (f64, f64)> + 'a>>;
+
+/// Merges several sorted point iterators into rows of \[x, y₁, y₂,… yₙ].
+/// Traces without a point at the given x get a NaN in the row.
+pub struct MergedPointsIterator<'a> {
+    iters: Vec<PeekablePoints<'a>>,
+}
+
+impl<'a> MergedPointsIterator<'a> {
+    pub fn new(iters: Vec<Box<dyn Iterator<Item = (f64, f64)> + 'a>>) -> Self {
+        Self {
+            iters: iters.into_iter().map(|i| i.peekable()).collect(),
+        }
+    }
+}
+
+impl Iterator for MergedPointsIterator<'_> {
+    type Item = Vec<f64>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self
+            .iters
+            .iter_mut()
+            .filter_map(|i| i.peek().map(|(x, _)| *x))
+            .min_by(f64::total_cmp)?;
+
+        let mut result = Vec::with_capacity(self.iters.len() + 1);
+        result.push(x);
+
+        for iter in self.iters.iter_mut() {
+            match iter.next_if(|(xi, _)| *xi == x) {
+                Some((_, y)) => result.push(y),
+                None => result.push(f64::NAN),
+            }
+        }
+
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RaggedBatch;
+    use crate::{error::ChartError, trace::Bundle, types::NumericRange};
+
+    #[test]
+    fn keeps_empty_traces() {
+        let batch = RaggedBatch::<f64, f64>::new(vec![
+            (1, vec![2., 1.], vec![20., 10.]),
+            (2, Vec::new(), Vec::new()),
+        ])
+        .unwrap();
+
+        assert!(batch.contains_trace(2));
+        assert_eq!(batch.traces().len(), 2);
+        assert_eq!(
+            batch
+                .iter_in_range_f64(2, NumericRange::new(0., 3.))
+                .count(),
+            0
+        );
+        assert_eq!(
+            batch
+                .iter_in_range_f64(1, NumericRange::new(0., 3.))
+                .collect::<Vec<_>>(),
+            [(1., 10.), (2., 20.)]
+        );
+
+        assert!(RaggedBatch::<f64, f64>::new(vec![(3, Vec::new(), vec![1.])]).is_err());
+        assert!(matches!(
+            RaggedBatch::<f64, f64>::new(vec![
+                (1, vec![1.], vec![1.]),
+                (1, Vec::new(), Vec::new())
+            ]),
+            Err(ChartError::DuplicateTrace(1))
+        ));
+    }
+}
@@ -0,0 +1,122 @@
+// https://github.com/madonoharu/tsify/issues/42
+#![allow(non_snake_case)]
+
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use super::BundleRc;
+
+#[derive(Tsify, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "lowercase")]
+pub enum EpochUnit {
+    S,
+    Ms,
+    Us,
+    Ns,
+}
+
+impl EpochUnit {
+    pub fn seconds(self) -> f64 {
+        match self {
+            EpochUnit::S => 1.,
+            EpochUnit::Ms => 1e-3,
+            EpochUnit::Us => 1e-6,
+            EpochUnit::Ns => 1e-9,
+        }
+    }
+
+    pub fn per_second(self) -> f64 {
+        match self {
+            EpochUnit::S => 1.,
+            EpochUnit::Ms => 1e3,
+            EpochUnit::Us => 1e6,
+            EpochUnit::Ns => 1e9,
+        }
+    }
+}
+
+/// Describes x values stored as integer timestamps.
+///
+/// Bundles expose them as seconds since `epoch`. Picking an epoch close to the data keeps
+/// those small enough for every nanosecond to stay distinguishable, which holds
+/// for roughly seven weeks on either side of the epoch.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TimeAxis {
+    pub unit: EpochUnit,
+    /// Timestamp in `unit` which is mapped to x = 0
+    pub epoch: i64,
+}
+
+impl TimeAxis {
+    pub fn new(unit: EpochUnit, epoch: i64) -> Self {
+        Self { unit, epoch }
+    }
+
+    pub fn to_x(&self, timestamp: i64) -> f64 {
+        (timestamp as i128 - self.epoch as i128) as f64 / self.unit.per_second()
+    }
+
+    /// The timestamp closest to `x`
+    pub fn to_timestamp(&self, x: f64) -> i64 {
+        ((x * self.unit.per_second()).round() as i128 + self.epoch as i128) as i64
+    }
+}
+
+/// The time axis of the bundles, if they all share the same one
+pub fn common_time_axis(bundles: &[BundleRc]) -> Option<TimeAxis> {
+    let first = bundles.first()?.time_axis();
+
+    bundles
+        .iter()
+        .all(|b| b.time_axis() == first)
+        .then_some(first)
+        .flatten()
+}
+
+#[wasm_bindgen]
+impl BundleRc {
+    /// Unit of the timestamps the bundle's x values were loaded from
+    pub fn time_unit(&self) -> Option<EpochUnit> {
+        self.time_axis().map(|axis| axis.unit)
+    }
+
+    /// Timestamp mapped to x = 0, in the bundle's `time_unit`
+    pub fn epoch(&self) -> Option<i64> {
+        self.time_axis().map(|axis| axis.epoch)
+    }
+
+    /// Converts an x value of this bundle to an exact timestamp in its `time_unit`
+    pub fn x_to_timestamp(&self, x: f64) -> Option<i64> {
+        self.time_axis().map(|axis| axis.to_timestamp(x))
+    }
+
+    /// Converts a timestamp in the bundle's `time_unit` to an x value of this bundle
+    pub fn timestamp_to_x(&self, timestamp: i64) -> Option<f64> {
+        self.time_axis().map(|axis| axis.to_x(timestamp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EpochUnit, TimeAxis};
+
+    #[test]
+    fn keeps_nanoseconds_near_the_epoch() {
+        let epoch = 1_700_000_000_000_000_000;
+        let axis = TimeAxis::new(EpochUnit::Ns, epoch);
+
+        for offset in [
+            0,
+            1,
+            999_999_999,
+            3_000_000_000_000_001,
+            -86_400_000_000_007,
+        ] {
+            let x = axis.to_x(epoch + offset);
+            assert_eq!(axis.to_timestamp(x), epoch + offset);
+            assert!(axis.to_x(epoch + offset + 1) > x);
+        }
+    }
+}
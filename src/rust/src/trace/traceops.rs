@@ -4,6 +4,7 @@ use wasm_bindgen::prelude::*;
 
 use crate::{
     data::TraceHandle,
+    error::{ChartError, Result},
     structs::AdaptiveGrid,
     trace::BundleRc,
     types::{NumericRange, TraceMetas, TracePoint},
@@ -168,12 +169,8 @@ pub fn find_closest_in_stack(
     x: f64,
     y: f64,
     interpolation: InterpolationStrategy,
-) -> Box<[JsValue]> {
-    assert_eq!(
-        bundles.len(),
-        factors.len(),
-        "there must be a factor for each bundle"
-    );
+) -> Result<Box<[JsValue]>> {
+    let bundles = upgrade_with_factors(bundles, factors)?;
 
     let mut sum = 0.;
     let mut traces = Vec::<TracePoint>::with_capacity(how_many);
@@ -222,7 +219,7 @@ pub fn find_closest_in_stack(
 
     traces
         .into_iter()
-        .map(|tp| serde_wasm_bindgen::to_value(&tp).unwrap())
+        .map(|tp| Ok(serde_wasm_bindgen::to_value(&tp)?))
         .collect()
 }
 
@@ -232,11 +229,11 @@ pub fn find_list_extents(
     factors: &[f64],
     trace_list: &[TraceHandle],
     x_range: NumericRange,
-) -> NumericRange {
-    assert!(
-        !trace_list.is_empty(),
-        "empty lists should be handled on the JS side"
-    );
+) -> Result<NumericRange> {
+    if trace_list.is_empty() {
+        return Err(ChartError::EmptyList("traces"));
+    }
+    let bundles = upgrade_with_factors(bundles, factors)?;
 
     let mut y_range = NumericRange {
         from: f64::MAX,
@@ -249,10 +246,8 @@ pub fn find_list_extents(
         }
 
         for trace in trace_list {
-            if bundle.contains_trace(*trace) {
-                for (_, y) in bundle.iter_in_range_with_neighbors_f64(*trace, x_range) {
-                    let y = y * factor;
-
+            if let Some((min, max)) = bundle.extents_in_range_with_neighbors_f64(*trace, x_range) {
+                for y in [min * factor, max * factor] {
                     y_range.from = y_range.from.min(y);
                     y_range.to = y_range.to.max(y);
                 }
@@ -260,7 +255,7 @@ pub fn find_list_extents(
         }
     }
 
-    y_range
+    Ok(y_range)
 }
 
 #[wasm_bindgen]
@@ -269,11 +264,11 @@ pub fn find_stack_extents(
     factors: &[f64],
     stack: &[TraceHandle],
     x_range: NumericRange,
-) -> NumericRange {
-    assert!(
-        !stack.is_empty(),
-        "empty stacks should be handled on the JS side"
-    );
+) -> Result<NumericRange> {
+    if stack.is_empty() {
+        return Err(ChartError::EmptyList("stacked traces"));
+    }
+    let bundles = upgrade_with_factors(bundles, factors)?;
 
     let mut grid = AdaptiveGrid::new();
     let mut y_range = NumericRange {
@@ -298,5 +293,18 @@ pub fn find_stack_extents(
         }
     }
 
-    y_range
+    Ok(y_range)
+}
+
+/// Upgrades the bundles, checking that there is a factor for each of them
+fn upgrade_with_factors(bundles: &BundleVec, factors: &[f64]) -> Result<Vec<BundleRc>> {
+    if bundles.len() != factors.len() {
+        return Err(ChartError::LengthMismatch {
+            what: "factors",
+            expected: bundles.len(),
+            actual: factors.len(),
+        });
+    }
+
+    bundles.upgrade_all()
 }
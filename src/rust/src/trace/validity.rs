@@ -0,0 +1,65 @@
+/// Bitmap marking which samples of a column are present.
+/// A cleared bit marks a missing sample, which bundles report as a NaN y.
+#[derive(Clone, Default)]
+pub struct ValidityMask {
+    bits: Vec<u64>,
+    len: usize,
+}
+
+impl ValidityMask {
+    /// Creates a mask of `len` present samples.
+    pub fn new_valid(len: usize) -> Self {
+        Self {
+            bits: vec![u64::MAX; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Number of bytes taken by the bits
+    pub fn memory_footprint(&self) -> usize {
+        self.bits.capacity() * std::mem::size_of::<u64>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_valid(&self, idx: usize) -> bool {
+        self.bits[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    pub fn set(&mut self, idx: usize, valid: bool) {
+        if valid {
+            self.bits[idx / 64] |= 1 << (idx % 64);
+        } else {
+            self.bits[idx / 64] &= !(1 << (idx % 64));
+        }
+    }
+
+    pub fn push(&mut self, valid: bool) {
+        if self.len.is_multiple_of(64) {
+            self.bits.push(0);
+        }
+        self.len += 1;
+        self.set(self.len - 1, valid);
+    }
+
+    /// Whether any of the samples is missing.
+    pub fn has_missing(&self) -> bool {
+        (0..self.len).any(|i| !self.is_valid(i))
+    }
+}
+
+impl FromIterator<bool> for ValidityMask {
+    fn from_iter<T: IntoIterator<Item = bool>>(iter: T) -> Self {
+        let mut mask = Self::default();
+        for valid in iter {
+            mask.push(valid);
+        }
+        mask
+    }
+}
@@ -37,7 +37,7 @@ impl ResolvedColorIndices {
 
                         if should_assign_index_to_color(&color) {
                             used_indices.entry(color.clone()).or_default().insert(i);
-                            if largest_index.get(&color).map_or(false, |&j| i > j) {
+                            if largest_index.get(&color).is_some_and(|&j| i > j) {
                                 largest_index.insert(color.clone(), i);
                             }
                         }
@@ -51,7 +51,7 @@ impl ResolvedColorIndices {
                         let last_index = last_consecutive_index.entry(color.clone()).or_insert(0);
                         while used_indices
                             .get(&color)
-                            .map_or(false, |s| s.contains(last_index))
+                            .is_some_and(|s| s.contains(last_index))
                         {
                             *last_index += 1;
                         }
@@ -60,7 +60,7 @@ impl ResolvedColorIndices {
 
                         indices.insert(trace, i);
 
-                        if largest_index.get(&color).map_or(true, |&j| i > j) {
+                        if largest_index.get(&color).is_none_or(|&j| i > j) {
                             largest_index.insert(color.clone(), i);
                         }
                     }
@@ -0,0 +1,148 @@
+//! Proleptic Gregorian calendar arithmetic on UTC timestamps in seconds.
+
+pub const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Days since 1970-01-01 of the given date
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let (month, day) = (month as i64, day as i64);
+    let year = if month <= 2 { year - 1 } else { year };
+
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Date of the day the given number of days after 1970-01-01, as `(year, month, day)`
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = era * 400 + year_of_era + (month <= 2) as i64;
+
+    (year, month, day)
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Cursor<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        let matches = self.peek() == Some(byte);
+        if matches {
+            self.pos += 1;
+        }
+        matches
+    }
+
+    fn digits(&mut self, count: usize) -> Option<u32> {
+        let digits = self.bytes.get(self.pos..self.pos + count)?;
+        if !digits.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        self.pos += count;
+
+        Some(digits.iter().fold(0, |acc, d| acc * 10 + (d - b'0') as u32))
+    }
+}
+
+/// Parses an ISO-8601 date or date-time into seconds since the Unix epoch.
+///
+/// Accepts `YYYY-MM-DD`, optionally followed by `T` or a space and `hh:mm`, `hh:mm:ss`
+/// or `hh:mm:ss.fff`, and a `Z` or `±hh:mm` offset. Times without an offset are taken as UTC.
+pub fn parse_iso8601(text: &str) -> Option<f64> {
+    let (seconds, nanos) = parse_iso8601_parts(text)?;
+
+    Some(seconds as f64 + nanos as f64 / 1e9)
+}
+
+/// Parses an ISO-8601 date or date-time into exact nanoseconds since the Unix epoch,
+/// see [`parse_iso8601`]. Digits of the fraction past nanoseconds are ignored.
+/// Returns `None` for dates outside of the years 1677 to 2262, which `i64` can't hold.
+pub fn parse_iso8601_nanos(text: &str) -> Option<i64> {
+    let (seconds, nanos) = parse_iso8601_parts(text)?;
+
+    seconds.checked_mul(1_000_000_000)?.checked_add(nanos)
+}
+
+/// Whole seconds since the Unix epoch and the nanoseconds past them
+fn parse_iso8601_parts(text: &str) -> Option<(i64, i64)> {
+    let mut c = Cursor {
+        bytes: text.trim().as_bytes(),
+        pos: 0,
+    };
+
+    let year = c.digits(4)?;
+    c.eat(b'-').then_some(())?;
+    let month = c.digits(2).filter(|m| (1..=12).contains(m))?;
+    c.eat(b'-').then_some(())?;
+    let day = c.digits(2).filter(|d| (1..=31).contains(d))?;
+
+    let mut seconds = days_from_civil(year as i64, month, day) * SECONDS_PER_DAY;
+    let mut nanos = 0;
+
+    if c.eat(b'T') || c.eat(b't') || c.eat(b' ') {
+        let hour = c.digits(2).filter(|&h| h < 24)?;
+        c.eat(b':').then_some(())?;
+        let minute = c.digits(2).filter(|&m| m < 60)?;
+        let second = if c.eat(b':') {
+            c.digits(2).filter(|&s| s <= 60)?
+        } else {
+            0
+        };
+
+        seconds += (hour * 3600 + minute * 60 + second) as i64;
+
+        if c.eat(b'.') || c.eat(b',') {
+            let start = c.pos;
+            while c.peek().is_some_and(|b| b.is_ascii_digit()) {
+                c.pos += 1;
+            }
+            if c.pos == start {
+                return None;
+            }
+
+            // padded or cut to nine digits
+            nanos = c.bytes[start..c.pos]
+                .iter()
+                .chain(std::iter::repeat(&b'0'))
+                .take(9)
+                .fold(0, |acc, d| acc * 10 + (d - b'0') as i64);
+        }
+
+        match c.peek() {
+            Some(b'Z' | b'z') => c.pos += 1,
+            Some(sign @ (b'+' | b'-')) => {
+                c.pos += 1;
+                let hours = c.digits(2)?;
+                c.eat(b':');
+                let minutes = c.digits(2).unwrap_or(0);
+
+                let offset = (hours * 3600 + minutes * 60) as i64;
+                seconds += if sign == b'+' { -offset } else { offset };
+            }
+            _ => {}
+        }
+    }
+
+    (c.pos == c.bytes.len()).then_some((seconds, nanos))
+}
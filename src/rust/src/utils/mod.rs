@@ -1,3 +1,4 @@
+pub mod calendar;
 mod color;
 
 pub use color::*;
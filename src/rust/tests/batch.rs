@@ -0,0 +1,108 @@
+use libchartium::{
+    trace::{Batch, Bundle, BundleRange, EpochUnit, InterpolationStrategy, RaggedBatch, TimeAxis},
+    types::NumericRange,
+};
+
+#[test]
+fn float_x_is_not_truncated() {
+    let batch = Batch::new(vec![0.25, 0.5, 0.75, 1.5], vec![1., 2., 3., 4.], &[7]).unwrap();
+
+    let points: Vec<_> = batch
+        .iter_in_range_f64(7, NumericRange::new(0.4, 0.8))
+        .collect();
+    assert_eq!(points, vec![(0.5, 2.), (0.75, 3.)]);
+
+    assert_eq!(
+        batch.value_at(7, 1.125, InterpolationStrategy::Linear),
+        Some((1.125, 3.5))
+    );
+    assert_eq!(
+        batch.value_at(7, 0.6, InterpolationStrategy::Previous),
+        Some((0.5, 2.))
+    );
+}
+
+#[test]
+fn ragged_traces_are_merged() {
+    let batch = RaggedBatch::new(vec![
+        (1, vec![3., 0., 2.], vec![30., 0., 20.]),
+        (2, vec![1., 2.], vec![-1., -2.]),
+    ])
+    .unwrap();
+
+    assert!(matches!(
+        batch.range(),
+        BundleRange::Bounded { from, to } if from == 0. && to == 3.
+    ));
+
+    let rows: Vec<_> = batch
+        .iter_many_in_range_f64(vec![1, 2], NumericRange::new(0.5, 3.))
+        .map(|row| {
+            row.iter()
+                .map(|v| if v.is_nan() { -99. } else { *v })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    assert_eq!(
+        rows,
+        vec![vec![1., -99., -1.], vec![2., 20., -2.], vec![3., 30., -99.]]
+    );
+
+    assert_eq!(
+        batch.value_at(2, 1.5, InterpolationStrategy::Linear),
+        Some((1.5, -1.5))
+    );
+}
+
+#[test]
+fn invalid_input_is_an_error() {
+    assert!(Batch::<f64, f64>::new(vec![], vec![], &[1]).is_err());
+    assert!(Batch::new(vec![1., 2.], vec![1., 2., 3.], &[1, 2]).is_err());
+    assert!(RaggedBatch::new(vec![(1, vec![1., 2.], vec![1.])]).is_err());
+}
+
+#[test]
+fn missing_samples_are_nan() {
+    let batch = Batch::new(vec![0i64, 1, 2, 3], vec![1u8, 2, 3, 4], &[1])
+        .and_then(|b| b.with_validity([true, false, true, true].into_iter().collect()))
+        .unwrap();
+
+    let ys: Vec<_> = batch
+        .iter_in_range_f64(1, NumericRange::new(0., 3.))
+        .map(|(_, y)| y)
+        .collect();
+    assert!(ys[1].is_nan());
+
+    assert_eq!(batch.value_at(1, 1., InterpolationStrategy::Linear), None);
+    assert_eq!(batch.value_at(1, 0.5, InterpolationStrategy::Linear), None);
+    assert_eq!(
+        batch.value_at(1, 2.5, InterpolationStrategy::Linear),
+        Some((2.5, 3.5))
+    );
+    assert_eq!(
+        batch.extents_in_range_with_neighbors_f64(1, NumericRange::new(0., 2.)),
+        Some((1., 3.))
+    );
+}
+
+#[test]
+fn nanosecond_timestamps_are_exact_near_the_epoch() {
+    let epoch = 1_700_000_000_000_000_000i64;
+    let x: Vec<i64> = (0..5).map(|i| epoch + i).collect();
+    let batch = Batch::new(x, vec![0., 1., 2., 3., 4.], &[1])
+        .unwrap()
+        .with_time_axis(TimeAxis::new(EpochUnit::Ns, epoch));
+
+    assert!(matches!(batch.range(), BundleRange::Bounded { from, to } if from == 0. && to == 4e-9));
+
+    let ys: Vec<_> = batch
+        .iter_in_range_f64(1, NumericRange::new(1e-9, 3e-9))
+        .map(|(_, y)| y)
+        .collect();
+    assert_eq!(ys, vec![1., 2., 3.]);
+
+    let (_, y) = batch
+        .value_at(1, 2.5e-9, InterpolationStrategy::Linear)
+        .unwrap();
+    assert!((y - 2.5).abs() < 1e-9);
+}